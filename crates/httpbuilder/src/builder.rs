@@ -1,10 +1,11 @@
 use std::{collections::HashMap, str::FromStr};
 
-use http::{HeaderName, Uri};
+use http::{HeaderName, HeaderValue, Uri};
 
 use crate::{
     errors::HttpBuilderError,
-    http_reference::HttpMultiReference,
+    http_reference::{HttpMultiReference, HttpReference},
+    modifier::HttpModifier,
 };
 
 pub struct HttpBuilder<'a> {
@@ -28,22 +29,8 @@ impl<'a> HttpBuilder<'a> {
         let scheme = dst.scheme().ok_or(HttpBuilderError::NoScheme)?;
         let hostname = dst.authority().ok_or(HttpBuilderError::NoHostname)?;
         let path = dst.path();
-        let decoded_query = dst.query().ok_or(HttpBuilderError::NoQuery)?;
-        let mut query = HashMap::new();
-        let mut src_query = HashMap::new();
-        for (k, v) in form_urlencoded::parse(decoded_query.as_bytes()) {
-            query
-                .entry(k.to_string())
-                .or_insert(Vec::new())
-                .push(v.to_string());
-        }
-        let src_decoded_query = src.uri.query().ok_or(HttpBuilderError::NoQuery)?;
-        for (k, v) in form_urlencoded::parse(src_decoded_query.as_bytes()) {
-            src_query
-                .entry(k.to_string())
-                .or_insert(Vec::new())
-                .push(v.to_string());
-        }
+        let query = parse_query(dst.query());
+        let src_query = parse_query(src.uri.query());
         let headers = http::HeaderMap::new();
         Ok(HttpBuilder {
             src,
@@ -56,46 +43,139 @@ impl<'a> HttpBuilder<'a> {
         })
     }
 
-    pub fn apply<'b, A: IntoIterator<Item = &'b HttpMultiReference>>(
+    pub fn apply<'b, A: IntoIterator<Item = &'b HttpModifier>>(
         mut self,
         elem_iter: A,
     ) -> Result<Self, HttpBuilderError> {
         for elem in elem_iter.into_iter() {
             match elem {
-                HttpMultiReference::Header(s) => {
-                    if let Some(value) = self.src.headers.get(s) {
-                        self.headers
-                            .append(HeaderName::from_str(s).unwrap(), value.clone());
+                HttpModifier::Copy { from, rename } => self.copy(from, rename.as_deref())?,
+                HttpModifier::Set { set, value } => self.set(set, value)?,
+                HttpModifier::Remove { remove } => self.remove(remove),
+            }
+        }
+        Ok(self)
+    }
+
+    fn copy(
+        &mut self,
+        from: &HttpMultiReference,
+        rename: Option<&str>,
+    ) -> Result<(), HttpBuilderError> {
+        match from {
+            HttpMultiReference::Header(s) => {
+                for value in self.src.headers.get_all(s.as_str()) {
+                    self.headers
+                        .append(header_name(rename.unwrap_or(s))?, value.clone());
+                }
+            }
+            HttpMultiReference::Query(s) => {
+                if let Some(value) = self.src_query.get(s) {
+                    self.query
+                        .entry(rename.unwrap_or(s).to_string())
+                        .or_default()
+                        .extend(value.iter().cloned());
+                }
+            }
+            HttpMultiReference::HeaderRegex(r) => {
+                for (k, v) in self.src.headers.iter() {
+                    if r.is_match(k.as_str()) {
+                        let name = match rename {
+                            Some(rename) => r.replace(k.as_str(), rename).into_owned(),
+                            None => k.to_string(),
+                        };
+                        self.headers.append(header_name(&name)?, v.clone());
                     }
                 }
-                HttpMultiReference::Query(s) => {
-                    if let Some(value) = self.src_query.get(s) {
+            }
+            HttpMultiReference::QueryRegex(r) => {
+                for (k, v) in self.src_query.iter() {
+                    if r.is_match(k.as_str()) {
+                        let name = match rename {
+                            Some(rename) => r.replace(k.as_str(), rename).into_owned(),
+                            None => k.clone(),
+                        };
                         self.query
-                            .entry(s.clone())
+                            .entry(name)
                             .or_default()
-                            .extend(value.iter().cloned());
+                            .extend(v.iter().cloned());
                     }
                 }
-                HttpMultiReference::HeaderRegex(r) => {
-                    for (k, v) in self.src.headers.iter() {
-                        if r.is_match(k.as_str()) {
-                            self.headers
-                                .append(HeaderName::from_str(k.as_str()).unwrap(), v.clone());
-                        }
-                    }
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, target: &HttpReference, value: &str) -> Result<(), HttpBuilderError> {
+        match target {
+            HttpReference::Header(s) => {
+                let value = HeaderValue::from_str(value)
+                    .map_err(|_| HttpBuilderError::InvalidHeaderValue(s.clone()))?;
+                self.headers.insert(header_name(s)?, value);
+            }
+            HttpReference::Query(s) => {
+                self.query.insert(s.clone(), vec![value.to_string()]);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, target: &HttpMultiReference) {
+        match target {
+            HttpMultiReference::Header(s) => {
+                self.headers.remove(s.as_str());
+            }
+            HttpMultiReference::Query(s) => {
+                self.query.remove(s);
+            }
+            HttpMultiReference::HeaderRegex(r) => {
+                let names = self
+                    .headers
+                    .keys()
+                    .filter(|k| r.is_match(k.as_str()))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for name in names {
+                    self.headers.remove(name);
                 }
-                HttpMultiReference::QueryRegex(r) => {
-                    for (k, v) in self.src_query.iter() {
-                        if r.is_match(k.as_str()) {
-                            self.query
-                                .entry(k.clone())
-                                .or_default()
-                                .extend(v.iter().cloned());
-                        }
-                    }
+            }
+            HttpMultiReference::QueryRegex(r) => {
+                self.query.retain(|k, _| !r.is_match(k));
+            }
+        }
+    }
+
+    pub fn uri(&self) -> Result<Uri, HttpBuilderError> {
+        let mut path_and_query = self.path.clone();
+        if !self.query.is_empty() {
+            let mut serializer = form_urlencoded::Serializer::new(String::new());
+            for (k, values) in self.query.iter() {
+                for v in values {
+                    serializer.append_pair(k, v);
                 }
             }
+            path_and_query.push('?');
+            path_and_query.push_str(&serializer.finish());
         }
-        Ok(self)
+        Ok(Uri::builder()
+            .scheme(self.scheme.clone())
+            .authority(self.hostname.clone())
+            .path_and_query(path_and_query)
+            .build()?)
     }
 }
+
+fn parse_query(query: Option<&str>) -> HashMap<String, Vec<String>> {
+    let mut parsed = HashMap::new();
+    for (k, v) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        parsed
+            .entry(k.to_string())
+            .or_insert(Vec::new())
+            .push(v.to_string());
+    }
+    parsed
+}
+
+fn header_name(name: &str) -> Result<HeaderName, HttpBuilderError> {
+    HeaderName::from_str(name).map_err(|_| HttpBuilderError::InvalidHeaderName(name.to_string()))
+}
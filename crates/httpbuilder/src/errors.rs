@@ -1,5 +1,15 @@
+#[derive(Debug, thiserror::Error)]
 pub enum HttpBuilderError {
+    #[error("No scheme in uri")]
     NoScheme,
+    #[error("No hostname in uri")]
     NoHostname,
+    #[error("No query in uri")]
     NoQuery,
+    #[error("Invalid header name: {0}")]
+    InvalidHeaderName(String),
+    #[error("Invalid header value for {0}")]
+    InvalidHeaderValue(String),
+    #[error("Invalid uri: {0}")]
+    InvalidUri(#[from] http::Error),
 }
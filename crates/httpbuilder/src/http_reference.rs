@@ -20,7 +20,7 @@ pub enum HttpMultiReference {
     QueryRegex(Regex),
 }
 
-static REGEX_VALUE_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^/[.+]/$").unwrap());
+static REGEX_VALUE_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^/.+/$").unwrap());
 
 impl Serialize for HttpReference {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -78,18 +78,21 @@ impl<'de> Deserialize<'de> for HttpMultiReference {
             where
                 E: serde::de::Error,
             {
-                let splited = v.split(":").collect::<Vec<&str>>();
-                let [prefix, value, ..] = splited.as_slice() else {
+                let Some((prefix, value)) = v.split_once(":") else {
                     return Err(E::custom("expected format: header:name or query:name or header:/{regex}/ or query:/{regex}/ or context:{json pointer}"));
                 };
                 let is_regex = REGEX_VALUE_PATTERN.is_match(value);
-                match (*prefix, is_regex) {
+                let pattern = value
+                    .strip_prefix('/')
+                    .and_then(|value| value.strip_suffix('/'))
+                    .unwrap_or(value);
+                match (prefix, is_regex) {
                     ("header", false) => Ok(HttpMultiReference::Header(value.to_string())),
                     ("query", false) => Ok(HttpMultiReference::Query(value.to_string())),
-                    ("header", true) => Regex::new(value)
+                    ("header", true) => Regex::new(pattern)
                         .map(HttpMultiReference::HeaderRegex)
                         .map_err(E::custom),
-                    ("query", true) => Regex::new(value)
+                    ("query", true) => Regex::new(pattern)
                         .map(HttpMultiReference::QueryRegex)
                         .map_err(E::custom),
                     (prefix, _) => Err(E::custom(format!(
@@ -104,6 +107,15 @@ impl<'de> Deserialize<'de> for HttpMultiReference {
     }
 }
 
+impl From<HttpReference> for HttpMultiReference {
+    fn from(reference: HttpReference) -> Self {
+        match reference {
+            HttpReference::Header(s) => Self::Header(s),
+            HttpReference::Query(s) => Self::Query(s),
+        }
+    }
+}
+
 impl Display for HttpReference {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -3,6 +3,7 @@ use http_reference::HttpReference;
 pub mod builder;
 pub mod errors;
 pub mod http_reference;
+pub mod modifier;
 
 pub fn resolve(http_ref: &HttpReference, src: &http::request::Parts) -> Option<String> {
     match http_ref {
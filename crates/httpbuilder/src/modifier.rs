@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::http_reference::{HttpMultiReference, HttpReference};
+
+/// Rule applied by [`crate::builder::HttpBuilder::apply`], in declared order.
+///
+/// - `{"from": "header:X-Api-Key"}` copies from the source request, `rename` changes the target name.
+///   For regex references `rename` is a replacement pattern, e.g. `{"from": "header:/^x-(.*)$/", "rename": "x-upstream-$1"}`.
+/// - `{"set": "header:X-Env", "value": "prod"}` sets a static value.
+/// - `{"remove": "query:/^debug/"}` removes matching components from the built request.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum HttpModifier {
+    Copy {
+        from: HttpMultiReference,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rename: Option<String>,
+    },
+    Set {
+        set: HttpReference,
+        value: String,
+    },
+    Remove {
+        remove: HttpMultiReference,
+    },
+}
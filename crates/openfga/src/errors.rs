@@ -18,4 +18,6 @@ pub enum Error {
     StoreNotFound,
     #[error("Check failed: code: {}, message: {}", .0.code, .0.message)]
     CheckFailed(OpenfgaFailure),
+    #[error("Join error: {0}")]
+    Join(#[from] tokio::task::JoinError),
 }
@@ -1,5 +1,6 @@
 use std::{
     borrow::{Borrow, Cow},
+    collections::HashMap,
     str::FromStr,
     sync::Arc,
 };
@@ -12,6 +13,9 @@ use crate::{
     ContextualTuple, Error, ListAllStoresQuery, ListAllStoresResponse, OpenfgaFailure, Tuple,
 };
 
+/// Checks per batch check request, the default limit of openfga.
+const BATCH_CHECK_CHUNK_SIZE: usize = 50;
+
 #[derive(Debug, Clone)]
 pub struct Openfga {
     inner: Arc<OpenfgaRef>,
@@ -216,13 +220,28 @@ impl Openfga {
                 }
             })
             .collect::<Vec<_>>();
-        let body = BatchCheckBody { checks };
-        let mut resp = self.batch_check(None, &body).await?;
-        let mut result = Vec::new();
-        for c in &body.checks {
-            let r = resp.result.remove(&c.correlation_id);
-            result.push(r.is_some_and(|r| r.allowed));
+        let correlation_ids = checks
+            .iter()
+            .map(|check| check.correlation_id.clone())
+            .collect::<Vec<_>>();
+        // openfga rejects batches over its max_checks_per_batch_check (50 by default)
+        let mut requests = tokio::task::JoinSet::new();
+        let mut checks = checks.into_iter().peekable();
+        while checks.peek().is_some() {
+            let body = BatchCheckBody {
+                checks: checks.by_ref().take(BATCH_CHECK_CHUNK_SIZE).collect(),
+            };
+            let openfga = self.clone();
+            requests.spawn(async move { openfga.batch_check(None, &body).await });
+        }
+        let mut results = HashMap::new();
+        while let Some(resp) = requests.join_next().await {
+            let resp = resp??;
+            results.extend(resp.result);
         }
-        Ok(result)
+        Ok(correlation_ids
+            .iter()
+            .map(|id| results.remove(id).is_some_and(|r| r.allowed))
+            .collect())
     }
 }
@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use chrono::Utc;
+use overlay_mcp_core::{auth::ApikeyEntry, Error, Error403, FatalError};
+use sha2::{Digest, Sha256};
+
+/// Api keys of `authn.apikey.keys`, looked up by the hash of the presented secret.
+pub(crate) struct Apikeys {
+    sha256: HashMap<String, Arc<ApikeyEntry>>,
+    // by id, they are presented as `<id>.<secret>`
+    argon2: HashMap<String, Arc<ApikeyEntry>>,
+    // argon2 keys already verified, by the sha256 of their secret
+    verified: Mutex<HashMap<String, Arc<ApikeyEntry>>>,
+}
+
+impl Apikeys {
+    pub(crate) fn new(keys: &[ApikeyEntry]) -> Result<Option<Self>, Error> {
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        let mut sha256 = HashMap::new();
+        let mut argon2 = HashMap::new();
+        for key in keys {
+            let entry = Arc::new(key.clone());
+            if let Some(digest) = key.hash.strip_prefix("sha256:") {
+                sha256.insert(digest.to_ascii_lowercase(), entry);
+            } else if PasswordHash::new(&key.hash).is_ok() {
+                if key.id.contains('.') {
+                    return Err(FatalError::InvalidApikeyId(key.id.clone()).into());
+                }
+                argon2.insert(key.id.clone(), entry);
+            } else {
+                return Err(FatalError::InvalidApikeyHash(key.id.clone()).into());
+            }
+        }
+        Ok(Some(Self {
+            sha256,
+            argon2,
+            verified: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Key of the secret, unknown and expired keys are forbidden like blacklisted ones.
+    pub(crate) async fn find(self: &Arc<Self>, apikey: &str) -> Result<Arc<ApikeyEntry>, Error> {
+        let digest = Sha256::digest(apikey.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let known = self
+            .sha256
+            .get(&digest)
+            .or(self.verified.lock().unwrap().get(&digest))
+            .cloned();
+        let key = match known {
+            Some(key) => key,
+            None => {
+                // only the key of the id is verified, unknown keys cost no argon2 run
+                let entry = apikey
+                    .split_once('.')
+                    .and_then(|(id, secret)| Some((self.argon2.get(id)?.clone(), secret)));
+                let Some((entry, secret)) = entry else {
+                    return Err(Error403::AuthorizationFailed.into());
+                };
+                // argon2 is slow on purpose, keep it off the runtime threads
+                let hash = entry.hash.clone();
+                let secret = secret.to_string();
+                let verified = tokio::task::spawn_blocking(move || verify_argon2(&hash, &secret))
+                    .await
+                    .unwrap_or(false);
+                if !verified {
+                    return Err(Error403::AuthorizationFailed.into());
+                }
+                self.verified.lock().unwrap().insert(digest, entry.clone());
+                entry
+            }
+        };
+        if key
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            tracing::info!(id = key.id, "api key expired");
+            return Err(Error403::AuthorizationFailed.into());
+        }
+        Ok(key)
+    }
+}
+
+fn verify_argon2(hash: &str, secret: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(secret.as_bytes(), &hash)
+            .is_ok()
+    })
+}
@@ -1,6 +1,7 @@
 use axum::http::{header, request};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use httpbuilder::http_reference::HttpReference;
-use jsonwebtoken::{jwk::JwkSet, DecodingKey, Validation};
+use jsonwebtoken::{DecodingKey, Validation};
 use oauth2::{
     basic::BasicClient, AuthUrl, ClientId, ClientSecret, EndpointMaybeSet, EndpointNotSet,
     EndpointSet, Scope, TokenUrl,
@@ -8,18 +9,20 @@ use oauth2::{
 use openidconnect::{core::CoreProviderMetadata, IssuerUrl};
 use overlay_mcp_core::{
     auth::{
-        AuthenticaterConfig, AuthenticaterJwtConfig, IdpClientConfig, JwtAudConfig,
-        JwtValidatorConfig, JwtVerifierConfig,
+        AuthenticaterConfig, AuthenticaterJwtConfig, IdpClientConfig, JwksRefreshConfig,
+        JwtAudConfig, JwtValidatorConfig, JwtVerifierConfig,
     },
     Authentication, Error, Error400, GeneralAuthn,
 };
-use std::{
-    collections::{HashMap, HashSet},
-    ops::Deref,
-    sync::Arc,
-};
+use std::{collections::HashSet, ops::Deref, sync::Arc};
 use url::Url;
 
+use crate::{
+    apikey::Apikeys,
+    introspection::Introspection,
+    jwks::{JwkSource, Jwks},
+};
+
 #[derive(Clone)]
 pub struct AuthnBasic(pub(crate) Arc<InnerAuthn>);
 
@@ -33,48 +36,98 @@ impl Deref for AuthnBasic {
 
 pub struct InnerAuthn {
     pub(crate) apikey_from: Vec<HttpReference>,
+    pub(crate) apikeys: Option<Arc<Apikeys>>,
     pub(crate) issuer: Url,
     pub(crate) oauth_client:
         BasicClient<EndpointSet, EndpointNotSet, EndpointNotSet, EndpointNotSet, EndpointMaybeSet>,
-    pub(crate) kid_map: HashMap<String, DecodingKey>,
-    pub(crate) no_kid_keys: Vec<DecodingKey>,
-    pub(crate) empty_key: DecodingKey,
-    pub(crate) jwt_validator: Option<JwtValidatorConfig>,
     pub(crate) client_config: IdpClientConfig,
+    /// Token providers, the first is `jwt` used by the OAuth endpoints.
+    pub(crate) providers: Vec<JwtProvider>,
+}
+
+/// Issuer of bearer tokens with its own keys and validation rules.
+pub(crate) struct JwtProvider {
+    issuer: Url,
+    jwks: Option<Arc<Jwks>>,
+    introspection: Option<Introspection>,
+    empty_key: DecodingKey,
+    jwt_validator: Option<JwtValidatorConfig>,
+    client_config: IdpClientConfig,
 }
 
 impl AuthnBasic {
     pub async fn new(config: &AuthenticaterConfig) -> Result<Self, Error> {
         let reqwest_client = reqwest::Client::new();
-        let (issuer, client, jwt_validator, client_config) =
-            Self::load_idp(&config.jwt, &reqwest_client).await?;
-        let (kid_map, no_kid_keys, jwt_validator) = match jwt_validator {
-            Some((jwk, validator)) => {
-                let mut kid_map = HashMap::new();
-                let mut no_kid_keys = Vec::new();
-                for key in &jwk.keys {
-                    if let Some(kid) = &key.common.key_id {
-                        kid_map.insert(kid.clone(), DecodingKey::from_jwk(key).unwrap());
-                    } else {
-                        no_kid_keys.push(DecodingKey::from_jwk(key).unwrap());
-                    }
-                }
-
-                (kid_map, no_kid_keys, Some(validator))
-            }
-            None => (HashMap::new(), Vec::new(), None),
-        };
+        let (issuer, client, interactive) =
+            Self::load_provider(&config.jwt, &config.jwks, &reqwest_client).await?;
+        let client_config = interactive.client_config.clone();
+        let mut providers = vec![interactive];
+        for provider in &config.providers {
+            let (_, _, provider) =
+                Self::load_provider(provider, &config.jwks, &reqwest_client).await?;
+            providers.push(provider);
+        }
 
         Ok(AuthnBasic(Arc::new(InnerAuthn {
             apikey_from: config.apikey.key_from.clone(),
+            apikeys: Apikeys::new(&config.apikey.keys)?.map(Arc::new),
             issuer,
             oauth_client: client,
-            kid_map,
-            no_kid_keys,
+            client_config,
+            providers,
+        })))
+    }
+
+    async fn load_provider(
+        config: &AuthenticaterJwtConfig,
+        jwks_config: &JwksRefreshConfig,
+        reqwest_client: &reqwest::Client,
+    ) -> Result<
+        (
+            Url,
+            BasicClient<
+                EndpointSet,
+                EndpointNotSet,
+                EndpointNotSet,
+                EndpointNotSet,
+                EndpointMaybeSet,
+            >,
+            JwtProvider,
+        ),
+        Error,
+    > {
+        let (issuer, client, jwt_validator, client_config) =
+            Self::load_idp(config, reqwest_client).await?;
+        let (jwks, jwt_validator) = match jwt_validator {
+            Some((source, validator)) => {
+                let jwks = Jwks::load(source, reqwest_client, jwks_config).await?;
+                (Some(jwks), Some(validator))
+            }
+            None => (None, None),
+        };
+        let introspection = match config {
+            AuthenticaterJwtConfig::Introspection {
+                introspection_url,
+                validator,
+                ..
+            } => Some(Introspection::new(
+                introspection_url.clone(),
+                issuer.clone(),
+                reqwest_client.clone(),
+                client_config.clone(),
+                validator.clone(),
+            )),
+            _ => None,
+        };
+        let provider = JwtProvider {
+            issuer: issuer.clone(),
+            jwks,
+            introspection,
             empty_key: DecodingKey::from_secret(&[]),
             jwt_validator,
             client_config,
-        })))
+        };
+        Ok((issuer, client, provider))
     }
 
     async fn load_idp(
@@ -90,7 +143,7 @@ impl AuthnBasic {
                 EndpointNotSet,
                 EndpointMaybeSet,
             >,
-            Option<(JwkSet, JwtValidatorConfig)>,
+            Option<(JwkSource, JwtValidatorConfig)>,
             IdpClientConfig,
         ),
         Error,
@@ -119,7 +172,7 @@ impl AuthnBasic {
                         Ok((
                             issuer.clone(),
                             oauth_client,
-                            Self::load_validator(verifier, reqwest_client).await?,
+                            Self::load_validator(verifier),
                             client.clone(),
                         ))
                     }
@@ -128,6 +181,19 @@ impl AuthnBasic {
                     }
                 }
             }
+            AuthenticaterJwtConfig::Introspection {
+                issuer,
+                auth_url,
+                token_url,
+                client,
+                ..
+            } => {
+                let oauth_client = BasicClient::new(ClientId::new(client.id.clone()))
+                    .set_client_secret(ClientSecret::new(client.secret.expose_secret().to_string()))
+                    .set_auth_uri(AuthUrl::from_url(auth_url.clone()))
+                    .set_token_uri_option(Some(TokenUrl::from_url(token_url.clone())));
+                Ok((issuer.clone(), oauth_client, None, client.clone()))
+            }
             AuthenticaterJwtConfig::OidcDiscovery {
                 verifier,
                 client,
@@ -153,12 +219,7 @@ impl AuthnBasic {
                 }
                 tracing::info!(url = ?issuer, "OIDC Discovered");
 
-                let jwk = reqwest_client
-                    .get(provider_metadata.jwks_uri().to_string())
-                    .send()
-                    .await?
-                    .json()
-                    .await?;
+                let jwks_uri = provider_metadata.jwks_uri().url().clone();
                 let oauth_client = BasicClient::new(ClientId::new(client.id.clone()))
                     .set_client_secret(ClientSecret::new(client.secret.expose_secret().to_string()))
                     .set_auth_uri(provider_metadata.authorization_endpoint().clone())
@@ -166,48 +227,99 @@ impl AuthnBasic {
                 Ok((
                     result_issuer_url,
                     oauth_client,
-                    Some((jwk, verifier.clone())),
+                    Some((JwkSource::Url(jwks_uri), verifier.clone())),
                     client.clone(),
                 ))
             }
         }
     }
 
-    async fn load_validator(
-        config: &JwtVerifierConfig,
-        reqwest_client: &reqwest::Client,
-    ) -> Result<Option<(JwkSet, JwtValidatorConfig)>, Error> {
+    fn load_validator(config: &JwtVerifierConfig) -> Option<(JwkSource, JwtValidatorConfig)> {
         match config {
             JwtVerifierConfig::EmbededJwk { jwk, validator } => {
-                Ok(Some((jwk.clone(), validator.clone())))
+                Some((JwkSource::Embedded(jwk.clone()), validator.clone()))
             }
             JwtVerifierConfig::JwkUrl { jwk_url, validator } => {
-                let jwks = reqwest_client
-                    .get(jwk_url.as_str())
-                    .send()
-                    .await?
-                    .json()
-                    .await?;
-                Ok(Some((jwks, validator.clone())))
+                Some((JwkSource::Url(jwk_url.clone()), validator.clone()))
             }
-            JwtVerifierConfig::NoCheck(_) => Ok(None),
+            JwtVerifierConfig::NoCheck(_) => None,
         }
     }
 }
+
 impl InnerAuthn {
-    fn pick_jwtkey_by_jwtheader<'a>(
-        &self,
-        header: &'a jsonwebtoken::Header,
-    ) -> Box<dyn Iterator<Item = &DecodingKey> + '_> {
-        if self.jwt_validator.is_none() {
-            return Box::new(std::iter::once(&self.empty_key));
+    // providers a bearer token is checked by, in order. A token with `iss` goes to the provider
+    // accepting it, which pins it when validating so a forged claim only picks the keys the token
+    // is checked with. Tokens without `iss`, opaque ones included, go to the introspecting
+    // providers, or the interactive one when none introspects.
+    fn select_providers(&self, token: &str) -> Result<Vec<&JwtProvider>, Error> {
+        let Some(issuer) = unverified_issuer(token) else {
+            let introspecting = self
+                .providers
+                .iter()
+                .filter(|provider| provider.introspection.is_some())
+                .collect::<Vec<_>>();
+            if introspecting.is_empty() {
+                return Ok(vec![&self.providers[0]]);
+            }
+            return Ok(introspecting);
+        };
+        self.providers
+            .iter()
+            .find(|provider| provider.accepts_issuer(&issuer))
+            .map(|provider| vec![provider])
+            .ok_or(Error::BadRequest(Error400::InvalidToken(
+                "Unknown token issuer",
+            )))
+    }
+
+    // the first provider accepting the token, or the failure of the last one
+    async fn authenticate_bearer(&self, token: &str) -> Result<Authentication, Error> {
+        let mut failure = None;
+        for provider in self.select_providers(token)? {
+            match provider.authenticate(token).await {
+                Ok(authn) => return Ok(authn),
+                Err(err) => failure = Some(err),
+            }
         }
-        if let Some(kid) = &header.kid {
-            if let Some(dec_key) = self.kid_map.get(kid) {
-                return Box::new(std::iter::once(dec_key));
+        Err(failure.unwrap_or(Error::BadRequest(Error400::InvalidToken(
+            "Unknown token issuer",
+        ))))
+    }
+}
+
+impl JwtProvider {
+    async fn authenticate(&self, token: &str) -> Result<Authentication, Error> {
+        if let Some(introspection) = &self.introspection {
+            let jwt = introspection.introspect(token).await?;
+            ensure_identified(&jwt.claims)?;
+            return Ok(Authentication::Jwt { jwt });
+        }
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|_| Error::BadRequest(Error400::InvalidToken("Invalid token header")))?;
+        let validator = self.prepare_validator(&header);
+        let mut failures = Vec::new();
+        for key in self.pick_jwtkey_by_jwtheader(&header).await {
+            let token = jsonwebtoken::decode::<serde_json::Value>(token, &key, &validator);
+            match token {
+                Ok(data) => {
+                    ensure_identified(&data.claims)?;
+                    return Ok(Authentication::Jwt { jwt: data });
+                }
+                Err(e) => failures.push(e),
             }
         }
-        Box::new(self.no_kid_keys.iter().chain(self.kid_map.values()))
+        tracing::info!(issuer = %self.issuer, "Failed to validate token: {:#?}", &failures);
+        Err(Error::BadRequest(Error400::InvalidToken(
+            "No valid key for jwt token",
+        )))
+    }
+
+    async fn pick_jwtkey_by_jwtheader(&self, header: &jsonwebtoken::Header) -> Vec<DecodingKey> {
+        match &self.jwks {
+            Some(jwks) => jwks.keys(header.kid.as_deref()).await,
+            None => vec![self.empty_key.clone()],
+        }
     }
 
     fn prepare_validator(&self, header: &jsonwebtoken::Header) -> Validation {
@@ -231,8 +343,9 @@ impl InnerAuthn {
                     validator.set_audience(auds);
                 }
             }
-            if let Some(iss) = &config.iss {
-                validator.set_issuer(iss);
+            match &config.iss {
+                Some(iss) => validator.set_issuer(iss),
+                None => self.pin_issuer(&mut validator),
             }
         } else {
             validator.required_spec_claims = HashSet::new();
@@ -242,9 +355,27 @@ impl InnerAuthn {
             validator.validate_aud = false;
 
             validator.insecure_disable_signature_validation();
+            self.pin_issuer(&mut validator);
         }
         validator
     }
+
+    // `validator.iss` when configured, tokens may name another issuer than the discovery url
+    fn accepts_issuer(&self, issuer: &str) -> bool {
+        match self
+            .jwt_validator
+            .as_ref()
+            .and_then(|config| config.iss.as_ref())
+        {
+            Some(iss) => iss.iter().any(|iss| iss == issuer),
+            None => same_issuer(self.issuer.as_str(), issuer),
+        }
+    }
+
+    fn pin_issuer(&self, validator: &mut Validation) {
+        let issuer = self.issuer.as_str();
+        validator.set_issuer(&[issuer, issuer.trim_end_matches('/')]);
+    }
 }
 
 impl GeneralAuthn for InnerAuthn {
@@ -268,9 +399,14 @@ impl GeneralAuthn for InnerAuthn {
                 continue;
             };
 
+            let key = match &self.apikeys {
+                Some(apikeys) => Some(apikeys.find(&apikey_value).await?),
+                None => None,
+            };
             return Ok(Authentication::ApiKey {
                 apikey: apikey_value,
                 apikey_from: http_ref.clone(),
+                key,
             });
         }
         if let Some(authorization) = target.headers.get(header::AUTHORIZATION) {
@@ -285,23 +421,7 @@ impl GeneralAuthn for InnerAuthn {
                 second_data.trim(),
             ) {
                 ("bearer", token) => {
-                    let header = jsonwebtoken::decode_header(token).map_err(|_| {
-                        Error::BadRequest(Error400::InvalidToken("Invalid token header"))
-                    })?;
-                    let validator = self.prepare_validator(&header);
-                    let mut failures = Vec::new();
-                    for key in self.pick_jwtkey_by_jwtheader(&header) {
-                        let token =
-                            jsonwebtoken::decode::<serde_json::Value>(token, key, &validator);
-                        match token {
-                            Ok(data) => return Ok(Authentication::Jwt { jwt: data }),
-                            Err(e) => failures.push(e),
-                        }
-                    }
-                    tracing::info!("Failed to validate token: {:#?}", &failures);
-                    return Err(Error::BadRequest(Error400::InvalidToken(
-                        "No valid key for jwt token",
-                    )));
+                    return self.authenticate_bearer(token).await;
                 }
                 (authn_type, _) => {
                     return Err(Error::BadRequest(Error400::BearerTokenExpected(
@@ -313,3 +433,29 @@ impl GeneralAuthn for InnerAuthn {
         Ok(Authentication::NoAuth)
     }
 }
+
+// `iss` claim of a jwt before its signature is verified, only to pick the provider verifying it
+fn unverified_issuer(token: &str) -> Option<String> {
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let claims = serde_json::from_slice::<serde_json::Value>(&payload).ok()?;
+    claims.get("iss")?.as_str().map(str::to_string)
+}
+
+// issuer urls are compared ignoring the trailing slash `Url` adds to an empty path
+pub(crate) fn same_issuer(provider: &str, token: &str) -> bool {
+    provider.trim_end_matches('/') == token.trim_end_matches('/')
+}
+
+// the principal of a token is its `iss` and `sub`, tokens missing either would share one
+// principal and with it each other's sessions
+fn ensure_identified(claims: &serde_json::Value) -> Result<(), Error> {
+    if claims.get("iss").is_some_and(|iss| iss.is_string())
+        && claims.get("sub").is_some_and(|sub| sub.is_string())
+    {
+        return Ok(());
+    }
+    Err(Error::BadRequest(Error400::InvalidToken(
+        "Token lacks iss or sub claim",
+    )))
+}
@@ -1,14 +1,20 @@
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc, time::Instant};
 
 use openfga::{CheckBody, CheckResponse, ContextualTuple, Openfga, Tuple};
 use overlay_mcp_core::{
     auth::{
-        ApikeyTupleConfig, AuthorizerFgaConfig, FgaCheckConfig, JwtContextPointerType,
-        JwtTupleConfig,
+        ApikeyTupleConfig, AuthorizerFgaConfig, FgaCheckConfig, FgaObjectConfig,
+        JwtContextPointerType, JwtTupleConfig,
     },
-    Authentication, AuthorizationResult, Error, Error401, FatalError, GeneralAuthz,
+    record_openfga_check, Authentication, AuthorizationResult, Error, Error401, FatalError,
+    GeneralAuthz,
 };
-use rmcp::model::{ClientJsonRpcMessage, ClientRequest, JsonRpcRequest, ServerJsonRpcMessage};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientRequest, JsonRpcRequest, JsonRpcResponse, Reference,
+    ServerJsonRpcMessage, ServerResult,
+};
+use sha2::{Digest, Sha256};
 
 #[derive(Clone)]
 pub struct OpenfgaAuthz {
@@ -19,6 +25,7 @@ pub struct OpenfgaAuthzConfig {
     check: FgaCheckConfig,
     apikey: ApikeyTupleConfig,
     jwt: JwtTupleConfig,
+    objects: FgaObjectConfig,
 }
 
 impl OpenfgaAuthz {
@@ -34,6 +41,7 @@ impl OpenfgaAuthz {
                 check: config.check.clone(),
                 apikey: config.apikey.clone(),
                 jwt: config.jwt.clone(),
+                objects: config.objects.clone(),
             }),
         })
     }
@@ -76,8 +84,9 @@ impl OpenfgaAuthz {
         object_value: &str,
     ) -> Result<CheckBody, Error> {
         match user {
-            Authentication::ApiKey { apikey, .. } => {
-                let user = format!("{}:{}", self.config.apikey.group, apikey);
+            Authentication::ApiKey { apikey, key, .. } => {
+                let id = key.as_ref().map_or(apikey.as_str(), |key| key.id.as_str());
+                let user = format!("{}:{}", self.config.apikey.group, id);
                 Ok(CheckBody {
                     tuple_key: Tuple {
                         user,
@@ -148,10 +157,71 @@ impl OpenfgaAuthz {
     }
 }
 
+impl OpenfgaAuthz {
+    // one batch check request for the whole list
+    async fn batch_check_objects(
+        &self,
+        target: &Authentication,
+        objects: impl Iterator<Item = String>,
+    ) -> Result<Vec<bool>, Error> {
+        let tuples = objects
+            .map(|object| self.build_tuple_user(target, &object).map(Cow::Owned))
+            .collect::<Result<Vec<_>, _>>()?;
+        if tuples.is_empty() {
+            return Ok(Vec::new());
+        }
+        let started = Instant::now();
+        let result = self.openfga.batch_simple_check(tuples).await;
+        record_openfga_check("batch_check", started.elapsed(), result.is_ok());
+        Ok(result?)
+    }
+
+    async fn check(&self, tuple: CheckBody) -> Result<CheckResponse, Error> {
+        let started = Instant::now();
+        let result = self.openfga.check(None, tuple).await;
+        record_openfga_check("check", started.elapsed(), result.is_ok());
+        Ok(result?)
+    }
+}
+
+// whitespace, `#` and `:` are not allowed in object ids, `%` keeps the escaping unambiguous
+const OBJECT_ID_ESCAPE: &AsciiSet = &CONTROLS.add(b' ').add(b'#').add(b':').add(b'%');
+
+/// Longest escaped value kept as is, longer ones are replaced by their digest to keep the object
+/// id within openfga's 256 characters.
+const MAX_OBJECT_VALUE_LEN: usize = 128;
+
+fn render_object(template: &Option<String>, values: &[(&str, &str)]) -> Option<String> {
+    template.as_ref().map(|template| {
+        values
+            .iter()
+            .fold(template.clone(), |object, (key, value)| {
+                object.replace(&format!("{{{}}}", key), &object_value(value))
+            })
+    })
+}
+
+fn object_value(value: &str) -> String {
+    let escaped = utf8_percent_encode(value, OBJECT_ID_ESCAPE).to_string();
+    if escaped.len() <= MAX_OBJECT_VALUE_LEN {
+        return escaped;
+    }
+    let digest = Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("sha256-{}", digest)
+}
+
+fn retain_allowed<T>(items: &mut Vec<T>, allowed: Vec<bool>) {
+    let mut allowed = allowed.into_iter();
+    items.retain(|_| allowed.next().unwrap_or(false));
+}
+
 impl GeneralAuthz for OpenfgaAuthz {
     async fn authorize_enter(&self, target: &Authentication) -> Result<AuthorizationResult, Error> {
         let tuple = self.build_tuple_user(target, ".system/enter")?;
-        let check_resp = self.openfga.check(None, tuple).await?;
+        let check_resp = self.check(tuple).await?;
         Ok(Self::to_authz_result(&check_resp))
     }
 
@@ -160,25 +230,84 @@ impl GeneralAuthz for OpenfgaAuthz {
         target: &Authentication,
         message: &ClientJsonRpcMessage,
     ) -> Result<AuthorizationResult, Error> {
-        match message {
-            ClientJsonRpcMessage::Request(JsonRpcRequest {
-                request: ClientRequest::CallToolRequest(tool_req),
-                ..
-            }) => {
-                let tuple =
-                    self.build_tuple_user(target, &format!("tools/call/{}", tool_req.params.name))?;
-                let check_resp = self.openfga.check(None, tuple).await?;
-                Ok(Self::to_authz_result(&check_resp))
+        let ClientJsonRpcMessage::Request(JsonRpcRequest { request, .. }) = message else {
+            return Ok(AuthorizationResult::Allow);
+        };
+        let objects = &self.config.objects;
+        let object = match request {
+            ClientRequest::CallToolRequest(req) => {
+                render_object(&objects.tools_call, &[("name", &req.params.name)])
             }
-            _ => Ok(AuthorizationResult::Allow),
-        }
+            ClientRequest::ReadResourceRequest(req) => {
+                render_object(&objects.resources_read, &[("uri", &req.params.uri)])
+            }
+            ClientRequest::GetPromptRequest(req) => {
+                render_object(&objects.prompts_get, &[("name", &req.params.name)])
+            }
+            ClientRequest::SubscribeRequest(req) => {
+                render_object(&objects.resources_subscribe, &[("uri", &req.params.uri)])
+            }
+            ClientRequest::CompleteRequest(req) => {
+                let (ref_type, reference) = match &req.params.r#ref {
+                    Reference::Prompt(prompt) => ("ref/prompt", prompt.name.as_str()),
+                    Reference::Resource(resource) => ("ref/resource", resource.uri.as_str()),
+                };
+                render_object(
+                    &objects.completion_complete,
+                    &[("ref_type", ref_type), ("ref", reference)],
+                )
+            }
+            ClientRequest::SetLevelRequest(req) => {
+                let level = serde_json::to_value(&req.params.level)?;
+                render_object(
+                    &objects.logging_set_level,
+                    &[("level", level.as_str().unwrap_or_default())],
+                )
+            }
+            _ => None,
+        };
+        let Some(object) = object else {
+            return Ok(AuthorizationResult::Allow);
+        };
+        let tuple = self.build_tuple_user(target, &object)?;
+        let check_resp = self.check(tuple).await?;
+        Ok(Self::to_authz_result(&check_resp))
     }
     async fn authorize_server_message(
         &self,
-        _target: &Authentication,
-        _message: &ServerJsonRpcMessage,
+        target: &Authentication,
+        message: &mut ServerJsonRpcMessage,
     ) -> Result<AuthorizationResult, Error> {
-        // TODO: Implement server message authorization
+        let ServerJsonRpcMessage::Response(JsonRpcResponse { result, .. }) = message else {
+            return Ok(AuthorizationResult::Allow);
+        };
+        let objects = &self.config.objects;
+        match result {
+            ServerResult::ListToolsResult(list) if objects.tools_call.is_some() => {
+                let names = list.tools.iter().map(|tool| {
+                    render_object(&objects.tools_call, &[("name", &tool.name)]).unwrap_or_default()
+                });
+                let allowed = self.batch_check_objects(target, names).await?;
+                retain_allowed(&mut list.tools, allowed);
+            }
+            ServerResult::ListPromptsResult(list) if objects.prompts_get.is_some() => {
+                let names = list.prompts.iter().map(|prompt| {
+                    render_object(&objects.prompts_get, &[("name", &prompt.name)])
+                        .unwrap_or_default()
+                });
+                let allowed = self.batch_check_objects(target, names).await?;
+                retain_allowed(&mut list.prompts, allowed);
+            }
+            ServerResult::ListResourcesResult(list) if objects.resources_read.is_some() => {
+                let uris = list.resources.iter().map(|resource| {
+                    render_object(&objects.resources_read, &[("uri", &resource.uri)])
+                        .unwrap_or_default()
+                });
+                let allowed = self.batch_check_objects(target, uris).await?;
+                retain_allowed(&mut list.resources, allowed);
+            }
+            _ => {}
+        }
         Ok(AuthorizationResult::Allow)
     }
 }
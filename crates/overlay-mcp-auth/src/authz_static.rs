@@ -8,19 +8,26 @@ use overlay_mcp_core::{
     },
     Authentication, AuthorizationResult, Error, GeneralAuthz,
 };
-use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientRequest, JsonRpcRequest, JsonRpcResponse, ServerJsonRpcMessage,
+    ServerResult,
+};
 
 #[derive(Clone)]
 pub struct StaticAuthz(pub(crate) Arc<InnerStaticAuthz>);
 pub struct InnerStaticAuthz {
     pub apikey: WhitelistAndBlacklist,
     pub jwt: Vec<JwtWhitelistAndBlacklist>,
+    pub tools: WhitelistAndBlacklist,
 }
 
 impl GeneralAuthz for StaticAuthz {
     async fn authorize_enter(&self, target: &Authentication) -> Result<AuthorizationResult, Error> {
         match target {
-            Authentication::ApiKey { apikey, .. } => self.authorize_apikey(apikey).await,
+            Authentication::ApiKey { apikey, key, .. } => match key {
+                Some(key) => self.authorize_apikey_id(&key.id).await,
+                None => self.authorize_apikey(apikey).await,
+            },
             Authentication::Jwt { jwt } => self.authorize_jwt(jwt).await,
             Authentication::NoAuth => Ok(AuthorizationResult::Unauthorized),
         }
@@ -29,20 +36,30 @@ impl GeneralAuthz for StaticAuthz {
     async fn authorize_client_message(
         &self,
         _target: &Authentication,
-        _message: &ClientJsonRpcMessage,
+        message: &ClientJsonRpcMessage,
     ) -> Result<AuthorizationResult, Error> {
-        // Static Authz not support client message authorization
-        // Static Authz only for enter authorization
-        Ok(AuthorizationResult::Allow)
+        match message {
+            ClientJsonRpcMessage::Request(JsonRpcRequest {
+                request: ClientRequest::CallToolRequest(tool_req),
+                ..
+            }) if !self.is_tool_allowed(&tool_req.params.name) => Ok(AuthorizationResult::Deny),
+            _ => Ok(AuthorizationResult::Allow),
+        }
     }
 
     async fn authorize_server_message(
         &self,
         _target: &Authentication,
-        _message: &ServerJsonRpcMessage,
+        message: &mut ServerJsonRpcMessage,
     ) -> Result<AuthorizationResult, Error> {
-        // Static Authz not support client message authorization
-        // Static Authz only for enter authorization
+        // Static Authz only knows tool rules, prompts and resources are always listed
+        if let ServerJsonRpcMessage::Response(JsonRpcResponse {
+            result: ServerResult::ListToolsResult(list),
+            ..
+        }) = message
+        {
+            list.tools.retain(|tool| self.is_tool_allowed(&tool.name));
+        }
         Ok(AuthorizationResult::Allow)
     }
 }
@@ -51,7 +68,14 @@ impl StaticAuthz {
     pub async fn new(config: &AuthorizerConstantConfig) -> Result<Self, Error> {
         let apikey = config.apikey.clone().unwrap_or_default();
         let jwt = config.jwt.clone();
-        Ok(Self(Arc::new(InnerStaticAuthz { apikey, jwt })))
+        let tools = config.tools.clone();
+        Ok(Self(Arc::new(InnerStaticAuthz { apikey, jwt, tools })))
+    }
+    fn is_tool_allowed(&self, name: &str) -> bool {
+        if self.0.tools.blacklist.contains(name) {
+            return false;
+        }
+        self.0.tools.whitelist.is_empty() || self.0.tools.whitelist.contains(name)
     }
     async fn authorize_apikey(&self, apikey: &str) -> Result<AuthorizationResult, Error> {
         if self.0.apikey.blacklist.contains(apikey) {
@@ -62,6 +86,16 @@ impl StaticAuthz {
             Ok(AuthorizationResult::Deny)
         }
     }
+    // configured keys are listed by id, an empty whitelist denies every key like for raw keys
+    async fn authorize_apikey_id(&self, id: &str) -> Result<AuthorizationResult, Error> {
+        if self.0.apikey.blacklist.contains(id) {
+            Ok(AuthorizationResult::Deny)
+        } else if self.0.apikey.whitelist.contains(id) {
+            Ok(AuthorizationResult::Allow)
+        } else {
+            Ok(AuthorizationResult::Deny)
+        }
+    }
     async fn authorize_jwt(
         &self,
         jwt: &TokenData<serde_json::Value>,
@@ -96,12 +130,10 @@ impl StaticAuthz {
                     })?
                     .iter()
                     .map(|x| {
-                        x.as_str().ok_or_else(|| {
-                            Error::JwtClaimTypeError {
-                                path: path.clone(),
-                                expected_type: "string",
-                                actual_type: get_actual_type(x),
-                            }
+                        x.as_str().ok_or_else(|| Error::JwtClaimTypeError {
+                            path: path.clone(),
+                            expected_type: "string",
+                            actual_type: get_actual_type(x),
                         })
                     })
                     .collect::<Result<Vec<_>, _>>()?,
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use jsonwebtoken::{Header, TokenData};
+use overlay_mcp_core::{
+    auth::{IdpClientConfig, JwtAudConfig, JwtValidatorConfig},
+    unix_now, Error, Error400,
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::authn_basic::same_issuer;
+
+/// RFC 7662 token introspection of opaque access tokens.
+///
+/// Active responses are cached until their `exp`, keyed by the digest of the token so raw tokens
+/// are never kept in memory.
+pub(crate) struct Introspection {
+    url: Url,
+    issuer: Url,
+    client: reqwest::Client,
+    client_config: IdpClientConfig,
+    validator: JwtValidatorConfig,
+    cache: Mutex<HashMap<String, CachedClaims>>,
+}
+
+struct CachedClaims {
+    claims: Value,
+    exp: u64,
+}
+
+impl Introspection {
+    pub(crate) fn new(
+        url: Url,
+        issuer: Url,
+        client: reqwest::Client,
+        client_config: IdpClientConfig,
+        validator: JwtValidatorConfig,
+    ) -> Self {
+        Self {
+            url,
+            issuer,
+            client,
+            client_config,
+            validator,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claims of an active token, as if they were the claims of a jwt.
+    pub(crate) async fn introspect(&self, token: &str) -> Result<TokenData<Value>, Error> {
+        let digest = Sha256::digest(token.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let now = unix_now();
+        if let Some(cached) = self.cache.lock().unwrap().get(&digest) {
+            if cached.exp > now {
+                return Ok(token_data(cached.claims.clone()));
+            }
+        }
+
+        let mut claims = self
+            .client
+            .post(self.url.as_str())
+            .basic_auth(
+                &self.client_config.id,
+                Some(self.client_config.secret.expose_secret()),
+            )
+            .form(&[("token", token), ("token_type_hint", "access_token")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+        if claims.get("active").and_then(Value::as_bool) != Some(true) {
+            return Err(Error::BadRequest(Error400::InvalidToken(
+                "Token is not active",
+            )));
+        }
+        let exp = claims.get("exp").and_then(Value::as_u64);
+        if exp.is_some_and(|exp| exp <= now) {
+            return Err(Error::BadRequest(Error400::InvalidToken(
+                "Token is expired",
+            )));
+        }
+        // `iss` is optional in the response, the token was issued by the introspecting idp
+        if let Some(claims) = claims.as_object_mut() {
+            claims
+                .entry("iss")
+                .or_insert_with(|| Value::String(self.issuer.to_string()));
+        }
+
+        self.validate(&claims, now)?;
+
+        // tokens without `exp` are introspected on every request
+        if let Some(exp) = exp {
+            let mut cache = self.cache.lock().unwrap();
+            cache.retain(|_, cached| cached.exp > now);
+            cache.insert(
+                digest,
+                CachedClaims {
+                    claims: claims.clone(),
+                    exp,
+                },
+            );
+        }
+        Ok(token_data(claims))
+    }
+
+    /// The `nbf`, `aud` and `iss` checks of the validator, `exp` is optional and checked as is.
+    /// The audience is required since nothing else binds an opaque token to this client.
+    fn validate(&self, claims: &Value, now: u64) -> Result<(), Error> {
+        let validator = &self.validator;
+        if validator.validate_nbf {
+            let nbf = claims.get("nbf").and_then(Value::as_u64);
+            if nbf.is_some_and(|nbf| nbf > now + validator.leeway) {
+                return Err(Error::BadRequest(Error400::InvalidToken(
+                    "Token is not yet valid",
+                )));
+            }
+        }
+        let audiences = match claims.get("aud") {
+            Some(Value::String(aud)) => vec![aud.as_str()],
+            Some(Value::Array(auds)) => auds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let audience_matched = match &validator.aud {
+            JwtAudConfig::NoCheck => true,
+            JwtAudConfig::ClientId => {
+                let client_id = self.client_config.id.as_str();
+                audiences.contains(&client_id)
+                    || claims.get("client_id").and_then(Value::as_str) == Some(client_id)
+            }
+            JwtAudConfig::Audience(expected) => audiences
+                .iter()
+                .any(|aud| expected.iter().any(|expected| expected == aud)),
+        };
+        if !audience_matched {
+            return Err(Error::BadRequest(Error400::InvalidToken(
+                "Token audience mismatch",
+            )));
+        }
+        let iss = claims
+            .get("iss")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let issuer_matched = match &validator.iss {
+            Some(issuers) => issuers.iter().any(|issuer| issuer == iss),
+            None => same_issuer(self.issuer.as_str(), iss),
+        };
+        if !issuer_matched {
+            return Err(Error::BadRequest(Error400::InvalidToken(
+                "Token issuer mismatch",
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn token_data(claims: Value) -> TokenData<Value> {
+    TokenData {
+        header: Header::default(),
+        claims,
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock, Weak},
+    time::Duration,
+};
+
+use axum::http::header;
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    DecodingKey,
+};
+use overlay_mcp_core::{auth::JwksRefreshConfig, Error};
+use tokio::{sync::Mutex, time::Instant};
+use url::Url;
+
+/// Where the JWK set of the verifier comes from.
+pub(crate) enum JwkSource {
+    Embedded(JwkSet),
+    Url(Url),
+}
+
+/// JWK set of the jwt verifier, refetched from its url in the background and when a token
+/// carries an unknown `kid`.
+pub(crate) struct Jwks {
+    url: Option<Url>,
+    client: reqwest::Client,
+    config: JwksRefreshConfig,
+    keys: RwLock<JwkKeys>,
+    // last fetch caused by an unknown kid, fetches wait on it one after another
+    unknown_kid_fetch: Mutex<Option<Instant>>,
+}
+
+#[derive(Default)]
+struct JwkKeys {
+    jwks: Vec<Jwk>,
+    kid_map: HashMap<String, DecodingKey>,
+    no_kid_keys: Vec<DecodingKey>,
+    retired: Vec<RetiredKey>,
+}
+
+/// Key removed from the set, accepted until `until`.
+struct RetiredKey {
+    kid: Option<String>,
+    key: DecodingKey,
+    until: Instant,
+}
+
+impl Jwks {
+    /// Fetch the set and, for a url, refresh it until the verifier is dropped.
+    pub(crate) async fn load(
+        source: JwkSource,
+        client: &reqwest::Client,
+        config: &JwksRefreshConfig,
+    ) -> Result<Arc<Self>, Error> {
+        let (url, jwks, refresh) = match source {
+            JwkSource::Embedded(jwks) => (None, jwks, None),
+            JwkSource::Url(url) => {
+                let (jwks, max_age) = fetch(client, &url).await?;
+                (Some(url), jwks, Some(max_age))
+            }
+        };
+        let this = Arc::new(Self {
+            url,
+            client: client.clone(),
+            config: config.clone(),
+            keys: RwLock::new(JwkKeys::default()),
+            unknown_kid_fetch: Mutex::new(None),
+        });
+        this.replace(jwks.keys);
+        if let Some(max_age) = refresh {
+            tokio::spawn(refresh_periodically(
+                Arc::downgrade(&this),
+                this.next_refresh(max_age),
+            ));
+        }
+        Ok(this)
+    }
+
+    /// Keys to try for a token signed with `kid`, a key is looked up once more after fetching
+    /// the set if the kid is unknown.
+    pub(crate) async fn keys(&self, kid: Option<&str>) -> Vec<DecodingKey> {
+        let Some(kid) = kid else {
+            return self.keys.read().unwrap().all();
+        };
+        if let Some(key) = self.keys.read().unwrap().get(kid) {
+            return vec![key];
+        }
+        if self.refresh_unknown_kid(kid).await {
+            if let Some(key) = self.keys.read().unwrap().get(kid) {
+                return vec![key];
+            }
+        }
+        self.keys.read().unwrap().all()
+    }
+
+    // whether the set was fetched again, at most once in `unknown_kid_cooldown`
+    async fn refresh_unknown_kid(&self, kid: &str) -> bool {
+        let Some(url) = &self.url else {
+            return false;
+        };
+        let mut last_fetch = self.unknown_kid_fetch.lock().await;
+        // fetched by a token waiting before this one
+        if self.keys.read().unwrap().get(kid).is_some() {
+            return true;
+        }
+        let cooldown = Duration::from_secs(self.config.unknown_kid_cooldown);
+        if last_fetch.is_some_and(|last_fetch| last_fetch.elapsed() < cooldown) {
+            tracing::debug!(kid, "unknown jwt kid, jwks fetched recently");
+            return false;
+        }
+        *last_fetch = Some(Instant::now());
+        tracing::info!(kid, "unknown jwt kid, fetching jwks");
+        match fetch(&self.client, url).await {
+            Ok((jwks, _)) => {
+                self.replace(jwks.keys);
+                true
+            }
+            Err(err) => {
+                tracing::error!(%url, "failed to fetch jwks: {}", err);
+                false
+            }
+        }
+    }
+
+    // keys missing from the new set are retired for the grace period
+    fn replace(&self, jwks: Vec<Jwk>) {
+        let mut keys = self.keys.write().unwrap();
+        let now = Instant::now();
+        let until = now + Duration::from_secs(self.config.grace_period);
+        keys.retired.retain(|retired| retired.until > now);
+        let removed = keys
+            .jwks
+            .iter()
+            .filter(|jwk| !jwks.contains(jwk))
+            .filter_map(|jwk| decoding_key(jwk).map(|key| (jwk.common.key_id.clone(), key)))
+            .collect::<Vec<_>>();
+        for (kid, key) in removed {
+            tracing::info!(?kid, "jwk removed from the set, retired");
+            keys.retired.push(RetiredKey { kid, key, until });
+        }
+        keys.kid_map.clear();
+        keys.no_kid_keys.clear();
+        for jwk in &jwks {
+            let Some(key) = decoding_key(jwk) else {
+                continue;
+            };
+            match &jwk.common.key_id {
+                Some(kid) => {
+                    keys.kid_map.insert(kid.clone(), key);
+                }
+                None => keys.no_kid_keys.push(key),
+            }
+        }
+        keys.jwks = jwks;
+    }
+
+    fn next_refresh(&self, max_age: Option<Duration>) -> Duration {
+        let min = self.config.min_refresh_interval;
+        let max = self.config.refresh_interval.max(min);
+        let interval = max_age.map_or(max, |max_age| max_age.as_secs().clamp(min, max));
+        Duration::from_secs(interval)
+    }
+}
+
+impl JwkKeys {
+    fn get(&self, kid: &str) -> Option<DecodingKey> {
+        let now = Instant::now();
+        self.kid_map.get(kid).cloned().or_else(|| {
+            self.retired
+                .iter()
+                .find(|retired| retired.until > now && retired.kid.as_deref() == Some(kid))
+                .map(|retired| retired.key.clone())
+        })
+    }
+
+    fn all(&self) -> Vec<DecodingKey> {
+        let now = Instant::now();
+        let retired = self
+            .retired
+            .iter()
+            .filter(|retired| retired.until > now)
+            .map(|retired| &retired.key);
+        self.no_kid_keys
+            .iter()
+            .chain(self.kid_map.values())
+            .chain(retired)
+            .cloned()
+            .collect()
+    }
+}
+
+async fn refresh_periodically(jwks: Weak<Jwks>, mut next: Duration) {
+    loop {
+        tokio::time::sleep(next).await;
+        let Some(jwks) = jwks.upgrade() else {
+            return;
+        };
+        let Some(url) = &jwks.url else {
+            return;
+        };
+        next = match fetch(&jwks.client, url).await {
+            Ok((set, max_age)) => {
+                tracing::debug!(%url, "jwks refreshed");
+                jwks.replace(set.keys);
+                jwks.next_refresh(max_age)
+            }
+            Err(err) => {
+                tracing::error!(%url, "failed to refresh jwks: {}", err);
+                Duration::from_secs(jwks.config.min_refresh_interval)
+            }
+        };
+    }
+}
+
+async fn fetch(client: &reqwest::Client, url: &Url) -> Result<(JwkSet, Option<Duration>), Error> {
+    let response = client.get(url.as_str()).send().await?.error_for_status()?;
+    let max_age = response
+        .headers()
+        .get(header::CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(max_age);
+    Ok((response.json().await?, max_age))
+}
+
+fn max_age(cache_control: &str) -> Option<Duration> {
+    cache_control
+        .split(',')
+        .find_map(|directive| directive.trim().strip_prefix("max-age="))
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+}
+
+fn decoding_key(jwk: &Jwk) -> Option<DecodingKey> {
+    DecodingKey::from_jwk(jwk)
+        .inspect_err(|err| tracing::warn!(kid = ?jwk.common.key_id, "invalid jwk: {}", err))
+        .ok()
+}
@@ -1,18 +1,31 @@
+mod apikey;
 mod authn_basic;
 mod authz_fga;
 mod authz_static;
+mod introspection;
+mod jwks;
 
 pub use authn_basic::*;
 use authz_fga::OpenfgaAuthz;
 pub use authz_static::*;
 use axum::http::request;
 use overlay_mcp_core::{
-    AuthConfig, Authentication, AuthorizationResult, Config, Error, GeneralAuthn, GeneralAuthz,
+    record_authz_decision, Audit, AuthConfig, Authentication, AuthorizationResult, Config, Error,
+    GeneralAuthn, GeneralAuthz,
+};
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientRequest, JsonRpcRequest, JsonRpcResponse, ServerJsonRpcMessage,
+    ServerResult,
 };
-use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
 
 #[derive(Clone)]
-pub enum Authz {
+pub struct Authz {
+    authorizer: Authorizer,
+    audit: Audit,
+}
+
+#[derive(Clone)]
+enum Authorizer {
     Static(StaticAuthz),
     OpenFga(OpenfgaAuthz),
 }
@@ -22,42 +35,81 @@ pub enum Authn {
     Basic(AuthnBasic),
 }
 
-impl GeneralAuthz for Authz {
-    async fn authorize_enter(&self, target: &Authentication) -> Result<AuthorizationResult, Error> {
+impl Authorizer {
+    fn as_str(&self) -> &'static str {
         match self {
-            Authz::Static(static_authz) => static_authz.authorize_enter(target).await,
-            Authz::OpenFga(openfga_authz) => openfga_authz.authorize_enter(target).await,
+            Authorizer::Static(_) => "static",
+            Authorizer::OpenFga(_) => "openfga",
         }
     }
+}
+
+impl GeneralAuthz for Authz {
+    async fn authorize_enter(&self, target: &Authentication) -> Result<AuthorizationResult, Error> {
+        let authorizer = self.authorizer.as_str();
+        let result = match &self.authorizer {
+            Authorizer::Static(static_authz) => static_authz.authorize_enter(target).await,
+            Authorizer::OpenFga(openfga_authz) => openfga_authz.authorize_enter(target).await,
+        };
+        record_authz_decision(authorizer, "enter", &result);
+        self.audit.authorize_enter(authorizer, target, &result);
+        result
+    }
 
     async fn authorize_client_message(
         &self,
         target: &Authentication,
         message: &ClientJsonRpcMessage,
     ) -> Result<AuthorizationResult, Error> {
-        match self {
-            Authz::Static(authz) => authz.authorize_client_message(target, message).await,
-            Authz::OpenFga(openfga_authz) => {
+        let authorizer = self.authorizer.as_str();
+        let result = match &self.authorizer {
+            _ if !allows_tool_call(target, message) => Ok(AuthorizationResult::Deny),
+            Authorizer::Static(authz) => authz.authorize_client_message(target, message).await,
+            Authorizer::OpenFga(openfga_authz) => {
                 openfga_authz
                     .authorize_client_message(target, message)
                     .await
             }
-        }
+        };
+        record_authz_decision(authorizer, "client_message", &result);
+        self.audit
+            .authorize_client_message(authorizer, target, message, &result);
+        result
     }
 
     async fn authorize_server_message(
         &self,
         target: &Authentication,
-        message: &ServerJsonRpcMessage,
+        message: &mut ServerJsonRpcMessage,
     ) -> Result<AuthorizationResult, Error> {
-        match self {
-            Authz::Static(authz) => authz.authorize_server_message(target, message).await,
-            Authz::OpenFga(openfga_authz) => {
+        let result = match &self.authorizer {
+            Authorizer::Static(authz) => authz.authorize_server_message(target, message).await,
+            Authorizer::OpenFga(openfga_authz) => {
                 openfga_authz
                     .authorize_server_message(target, message)
                     .await
             }
+        };
+        if let ServerJsonRpcMessage::Response(JsonRpcResponse {
+            result: ServerResult::ListToolsResult(list),
+            ..
+        }) = message
+        {
+            list.tools.retain(|tool| target.allows_tool(&tool.name));
         }
+        record_authz_decision(self.authorizer.as_str(), "server_message", &result);
+        result
+    }
+}
+
+// tool allowlist of the api key, checked before the authorizer
+fn allows_tool_call(target: &Authentication, message: &ClientJsonRpcMessage) -> bool {
+    match message {
+        ClientJsonRpcMessage::Request(JsonRpcRequest {
+            request: ClientRequest::CallToolRequest(call),
+            ..
+        }) => target.allows_tool(&call.params.name),
+        _ => true,
     }
 }
 
@@ -104,16 +156,16 @@ impl Authn {
 }
 
 impl Authz {
-    pub async fn new(config: &Config) -> Result<Self, Error> {
-        match &config.auth {
+    /// Decisions on entering and on client messages are written to `audit`.
+    pub async fn new(config: &Config, audit: Audit) -> Result<Self, Error> {
+        let authorizer = match &config.auth {
             AuthConfig::OpenFga { openfga, .. } => {
-                let authz = OpenfgaAuthz::new(openfga).await?;
-                Ok(Authz::OpenFga(authz))
+                Authorizer::OpenFga(OpenfgaAuthz::new(openfga).await?)
             }
             AuthConfig::Static { constant, .. } => {
-                let authz = StaticAuthz::new(constant).await?;
-                Ok(Authz::Static(authz))
+                Authorizer::Static(StaticAuthz::new(constant).await?)
             }
-        }
+        };
+        Ok(Self { authorizer, audit })
     }
 }
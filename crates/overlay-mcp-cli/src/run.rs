@@ -1,14 +1,16 @@
+use std::net::SocketAddr;
+
 use anyhow::{Context, Result};
 use figment::{
     providers::{Format, Json as FigmentJson},
     Figment,
 };
 use overlay_mcp_core::Config;
-use overlay_mcp_svr::router;
+use overlay_mcp_svr::{router, telemetry};
 use serde_json::{json, Value};
 use tokio::signal;
 use tokio_util::sync::CancellationToken;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 use crate::utils::clean_json;
 
@@ -32,9 +34,23 @@ pub async fn run(cli: &SubcommandRun) -> Result<()> {
         .parse::<EnvFilter>()
         .unwrap();
 
-    tracing_subscriber::fmt()
-        .pretty()
-        .with_env_filter(env_filter)
+    // OpenTelemetry 설정
+    let tracer_provider = config
+        .otel
+        .as_ref()
+        .map(telemetry::tracer_provider)
+        .transpose()
+        .context("Failed to build OpenTelemetry exporter")?;
+
+    tracing_subscriber::registry()
+        .with(env_filter)
+        // logs go to stderr, stdout is left to the audit log
+        .with(
+            tracing_subscriber::fmt::layer()
+                .pretty()
+                .with_writer(std::io::stderr),
+        )
+        .with(tracer_provider.as_ref().map(telemetry::layer))
         .init();
     tracing::info!("{}", serde_json::to_string_pretty(&config).unwrap());
 
@@ -45,9 +61,17 @@ pub async fn run(cli: &SubcommandRun) -> Result<()> {
     let app = router::router(cancel.clone(), config).await?;
 
     // 서버 실행
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(cancel))
-        .await?;
+    // client ip of the connection is read by `application.ip_extract`
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(cancel))
+    .await?;
+
+    if let Some(tracer_provider) = tracer_provider {
+        tokio::task::spawn_blocking(move || tracer_provider.shutdown()).await??;
+    }
 
     Ok(())
 }
@@ -0,0 +1,309 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+
+use jsonptr::PointerBuf;
+use rmcp::model::{ClientJsonRpcMessage, ClientRequest, JsonRpcMessage};
+use serde_json::{json, Value};
+
+use crate::{
+    audit::AuditSink, unix_now_millis, AuditConfig, Authentication, AuthorizationResult, Error,
+    Principal, UpstreamEndpoint,
+};
+
+/// Replaces redacted `tools/call` argument values.
+const REDACTED: &str = "[REDACTED]";
+
+/// Audit log writer, a no-op unless `application.audit` is set.
+///
+/// Records are written in order by a dedicated thread, so callers never wait on the sink.
+#[derive(Clone, Default)]
+pub struct Audit {
+    inner: Option<Arc<AuditInner>>,
+}
+
+struct AuditInner {
+    send: mpsc::Sender<Value>,
+    redact: Vec<PointerBuf>,
+}
+
+/// Session the tool calls of an upstream connection are audited for.
+#[derive(Clone, Default)]
+pub struct AuditScope {
+    audit: Audit,
+    session_id: String,
+    principal: Option<Principal>,
+}
+
+enum AuditWriter {
+    Stdout(io::Stdout),
+    File {
+        file: File,
+        path: PathBuf,
+        size: u64,
+        max_size: u64,
+        max_files: usize,
+    },
+}
+
+impl Audit {
+    pub fn new(config: Option<&AuditConfig>) -> Result<Self, Error> {
+        let Some(config) = config else {
+            return Ok(Self::default());
+        };
+        // the file is opened now, a misconfigured sink fails startup
+        let mut writer = AuditWriter::open(&config.sink)?;
+        let (send, recv) = mpsc::channel::<Value>();
+        std::thread::Builder::new()
+            .name("overlay-mcp-audit".to_string())
+            .spawn(move || {
+                for record in recv {
+                    if let Err(err) = writer.write(&record) {
+                        tracing::error!(error = %err, "failed to write audit record");
+                    }
+                }
+            })?;
+        Ok(Self {
+            inner: Some(Arc::new(AuditInner {
+                send,
+                redact: config.redact.clone(),
+            })),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    pub fn scope(&self, session_id: &str, principal: &Principal) -> AuditScope {
+        AuditScope {
+            audit: self.clone(),
+            session_id: session_id.to_string(),
+            principal: self.is_enabled().then(|| principal.clone()),
+        }
+    }
+
+    pub fn session_opened(
+        &self,
+        session_id: &str,
+        principal: &Principal,
+        upstream: &UpstreamEndpoint,
+    ) {
+        self.record(
+            "session_open",
+            principal,
+            json!({ "session_id": session_id, "upstream": upstream.location() }),
+        );
+    }
+
+    pub fn session_closed(&self, session_id: &str, principal: &Principal) {
+        self.record(
+            "session_close",
+            principal,
+            json!({ "session_id": session_id }),
+        );
+    }
+
+    pub fn authorize_enter(
+        &self,
+        authorizer: &'static str,
+        target: &Authentication,
+        result: &Result<AuthorizationResult, Error>,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.record(
+            "authorize_enter",
+            &target.principal(),
+            json!({ "authorizer": authorizer, "result": decision(result) }),
+        );
+    }
+
+    /// `tools/call` requests are logged with their tool and redacted arguments.
+    pub fn authorize_client_message(
+        &self,
+        authorizer: &'static str,
+        target: &Authentication,
+        message: &ClientJsonRpcMessage,
+        result: &Result<AuthorizationResult, Error>,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut fields = json!({ "authorizer": authorizer, "result": decision(result) });
+        if let Ok(Value::Object(message)) = serde_json::to_value(message) {
+            for field in ["id", "method"] {
+                if let Some(value) = message.get(field) {
+                    fields[field] = value.clone();
+                }
+            }
+        }
+        if let Some((tool, arguments)) = self.tool_call(message) {
+            fields["tool"] = json!(tool);
+            fields["arguments"] = arguments;
+        }
+        self.record("authorize_client_message", &target.principal(), fields);
+    }
+
+    /// Tool and redacted arguments of a `tools/call` request.
+    pub fn tool_call(&self, message: &ClientJsonRpcMessage) -> Option<(String, Value)> {
+        let inner = self.inner.as_ref()?;
+        let JsonRpcMessage::Request(request) = message else {
+            return None;
+        };
+        let ClientRequest::CallToolRequest(call) = &request.request else {
+            return None;
+        };
+        let mut arguments = call
+            .params
+            .arguments
+            .clone()
+            .map(Value::Object)
+            .unwrap_or(Value::Null);
+        for pointer in &inner.redact {
+            if let Ok(value) = pointer.resolve_mut(&mut arguments) {
+                *value = json!(REDACTED);
+            }
+        }
+        Some((call.params.name.to_string(), arguments))
+    }
+
+    fn record(&self, event: &'static str, principal: &Principal, fields: Value) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let mut record = json!({
+            "timestamp": unix_now_millis(),
+            "event": event,
+            "principal": principal,
+        });
+        if let (Value::Object(record), Value::Object(fields)) = (&mut record, fields) {
+            record.extend(fields);
+        }
+        if inner.send.send(record).is_err() {
+            tracing::error!(event, "audit writer stopped, record lost");
+        }
+    }
+}
+
+impl AuditScope {
+    pub fn tool_call(&self, message: &ClientJsonRpcMessage) -> Option<(String, Value)> {
+        self.audit.tool_call(message)
+    }
+
+    /// Result of a `tools/call` request forwarded to upstream.
+    pub fn tool_called(
+        &self,
+        id: &Value,
+        tool: &str,
+        arguments: &Value,
+        is_error: bool,
+        elapsed: Duration,
+    ) {
+        let Some(principal) = &self.principal else {
+            return;
+        };
+        self.audit.record(
+            "tool_call",
+            principal,
+            json!({
+                "session_id": self.session_id,
+                "id": id,
+                "tool": tool,
+                "arguments": arguments,
+                "result": if is_error { "error" } else { "ok" },
+                "duration_ms": elapsed.as_millis() as u64,
+            }),
+        );
+    }
+}
+
+impl AuditWriter {
+    fn open(sink: &AuditSink) -> io::Result<Self> {
+        match sink {
+            AuditSink::Stdout => Ok(Self::Stdout(io::stdout())),
+            AuditSink::File {
+                path,
+                max_size,
+                max_files,
+            } => {
+                let file = append(path)?;
+                Ok(Self::File {
+                    size: file.metadata()?.len(),
+                    file,
+                    path: path.clone(),
+                    max_size: *max_size,
+                    max_files: *max_files,
+                })
+            }
+        }
+    }
+
+    fn write(&mut self, record: &Value) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        match self {
+            Self::Stdout(stdout) => {
+                let mut stdout = stdout.lock();
+                stdout.write_all(&line)?;
+                stdout.flush()
+            }
+            Self::File {
+                file,
+                path,
+                size,
+                max_size,
+                max_files,
+            } => {
+                if *size > 0 && *size + line.len() as u64 > *max_size {
+                    *file = rotate(path, *max_files)?;
+                    *size = 0;
+                }
+                file.write_all(&line)?;
+                file.flush()?;
+                *size += line.len() as u64;
+                Ok(())
+            }
+        }
+    }
+}
+
+// shift `<path>.N` to `<path>.N+1`, dropping the oldest, and start a new file
+fn rotate(path: &Path, max_files: usize) -> io::Result<File> {
+    let rotated = |index: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    };
+    if max_files == 0 {
+        return OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path);
+    }
+    for index in (1..max_files).rev() {
+        match std::fs::rename(rotated(index), rotated(index + 1)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    std::fs::rename(path, rotated(1))?;
+    append(path)
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn decision(result: &Result<AuthorizationResult, Error>) -> &'static str {
+    match result {
+        Ok(result) => result.as_str(),
+        Err(_) => "error",
+    }
+}
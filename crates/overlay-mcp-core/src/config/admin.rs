@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+
+use jsonptr::PointerBuf;
+use redact::Secret;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use super::auth::JwtContextPointerType;
+
+/// Access to the session admin api under `/.meta/sessions`, the api is not served without it.
+///
+/// Callers authenticate like any other client, an api key listed in `apikeys` or a jwt matching
+/// one of the `jwt` claims is an admin.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminConfig {
+    #[serde(default, serialize_with = "redact_apikeys")]
+    pub apikeys: Vec<Secret<String>>,
+    /// Ids of keys in `authn.apikey.keys`.
+    #[serde(default)]
+    pub apikey_ids: HashSet<String>,
+    #[serde(default)]
+    pub jwt: Vec<AdminJwtClaim>,
+}
+
+/// Claim at `path` holding one of `values`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminJwtClaim {
+    pub path: PointerBuf,
+    #[serde(default = "default_type")]
+    pub r#type: JwtContextPointerType,
+    pub values: HashSet<String>,
+}
+
+impl AdminConfig {
+    pub fn is_admin_apikey(&self, apikey: &str, id: Option<&str>) -> bool {
+        if let Some(id) = id {
+            return self.apikey_ids.contains(id);
+        }
+        // digests have the same length and are compared without short circuit, so the time
+        // spent tells nothing about the admin keys
+        let digest = Sha256::digest(apikey.as_bytes());
+        self.apikeys.iter().fold(false, |found, admin| {
+            let admin = Sha256::digest(admin.expose_secret().as_bytes());
+            found
+                | (admin
+                    .iter()
+                    .zip(&digest)
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0)
+        })
+    }
+
+    pub fn is_admin_claims(&self, claims: &serde_json::Value) -> bool {
+        self.jwt.iter().any(|claim| {
+            let Ok(value) = claim.path.resolve(claims) else {
+                return false;
+            };
+            match claim.r#type {
+                JwtContextPointerType::String => value
+                    .as_str()
+                    .is_some_and(|value| claim.values.contains(value)),
+                JwtContextPointerType::StringArray => value.as_array().is_some_and(|values| {
+                    values
+                        .iter()
+                        .filter_map(|value| value.as_str())
+                        .any(|value| claim.values.contains(value))
+                }),
+            }
+        })
+    }
+}
+
+fn default_type() -> JwtContextPointerType {
+    JwtContextPointerType::String
+}
+
+fn redact_apikeys<S: Serializer>(
+    apikeys: &[Secret<String>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(apikeys.iter().map(|apikey| format!("{:?}", apikey)))
+}
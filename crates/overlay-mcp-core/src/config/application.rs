@@ -1,7 +1,10 @@
 use axum_client_ip::ClientIpSource;
 use serde::{Deserialize, Serialize};
 
-use super::reqmodifier::BaseModifiers;
+use super::{
+    admin::AdminConfig, audit::AuditConfig, identity::IdentityConfig, ratelimit::RateLimitConfig,
+    reqmodifier::BaseModifiers,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ApplicationConfig {
@@ -9,5 +12,14 @@ pub struct ApplicationConfig {
     pub ip_extract: Option<ClientIpSource>,
     pub prometheus: bool,
     pub health_check: bool,
+    #[serde(default)]
     pub passthrough: BaseModifiers,
-} 
\ No newline at end of file
+    #[serde(default)]
+    pub identity: IdentityConfig,
+    #[serde(default)]
+    pub admin: Option<AdminConfig>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    #[serde(default)]
+    pub audit: Option<AuditConfig>,
+}
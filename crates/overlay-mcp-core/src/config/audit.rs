@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use jsonptr::PointerBuf;
+use serde::{Deserialize, Serialize};
+
+/// Audit log of sessions, authorization decisions and tool calls, one JSON object per line.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub sink: AuditSink,
+    /// Values of `tools/call` arguments replaced before they are logged, relative to the
+    /// arguments object.
+    #[serde(default)]
+    pub redact: Vec<PointerBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum AuditSink {
+    #[default]
+    Stdout,
+    /// File rotated once it grows past `max_size` bytes, keeping `max_files` rotated files as
+    /// `<path>.1` (newest) to `<path>.<max_files>`.
+    File {
+        path: PathBuf,
+        #[serde(default = "default_max_size")]
+        max_size: u64,
+        #[serde(default = "default_max_files")]
+        max_files: usize,
+    },
+}
+
+fn default_max_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_max_files() -> usize {
+    5
+}
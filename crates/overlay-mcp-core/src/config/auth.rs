@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
+use chrono::{DateTime, Utc};
 use httpbuilder::http_reference::HttpReference;
 use jsonptr::PointerBuf;
 use jsonwebtoken::jwk::JwkSet;
@@ -38,7 +39,54 @@ impl AuthConfig {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AuthenticaterConfig {
     pub apikey: AuthenticaterApikeyConfig,
+    /// Provider used by the OAuth endpoints, also accepted for bearer tokens.
     pub jwt: AuthenticaterJwtConfig,
+    /// Further token providers, a token is verified by the provider matching its `iss` claim.
+    #[serde(default)]
+    pub providers: Vec<AuthenticaterJwtConfig>,
+    #[serde(default)]
+    pub jwks: JwksRefreshConfig,
+}
+
+/// Refresh of a JWK set fetched from `jwk_url` or the discovered `jwks_uri`, in seconds.
+///
+/// The set is fetched again after the `Cache-Control: max-age` of the last response, bounded by
+/// `min_refresh_interval` and `refresh_interval`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JwksRefreshConfig {
+    #[serde(default = "default_jwks_refresh_interval")]
+    pub refresh_interval: u64,
+    #[serde(default = "default_jwks_min_refresh_interval")]
+    pub min_refresh_interval: u64,
+    /// Least time between fetches caused by tokens with an unknown `kid`.
+    #[serde(default = "default_jwks_min_refresh_interval")]
+    pub unknown_kid_cooldown: u64,
+    /// How long keys removed from the set are still accepted.
+    #[serde(default = "default_jwks_grace_period")]
+    pub grace_period: u64,
+}
+
+impl Default for JwksRefreshConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval: default_jwks_refresh_interval(),
+            min_refresh_interval: default_jwks_min_refresh_interval(),
+            unknown_kid_cooldown: default_jwks_min_refresh_interval(),
+            grace_period: default_jwks_grace_period(),
+        }
+    }
+}
+
+fn default_jwks_refresh_interval() -> u64 {
+    3600
+}
+
+fn default_jwks_min_refresh_interval() -> u64 {
+    60
+}
+
+fn default_jwks_grace_period() -> u64 {
+    3600
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -47,6 +95,25 @@ pub struct AuthenticaterApikeyConfig {
     #[serde(default)]
     #[serde_as(as = "OneOrMany<_, PreferOne>")]
     pub key_from: Vec<HttpReference>,
+    /// Accepted api keys by their hash, unset keys are rejected once any key is listed.
+    /// Without keys every api key is authenticated and left to the authorizer.
+    #[serde(default)]
+    pub keys: Vec<ApikeyEntry>,
+}
+
+/// Api key known by the hash of its secret, authorized and audited by its `id`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApikeyEntry {
+    pub id: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// `sha256:<hex digest>`, or an argon2 PHC string(`$argon2id$...`).
+    pub hash: String,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Tools the key may list and call, every tool when unset.
+    #[serde(default)]
+    pub tools: Option<HashSet<String>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -79,6 +146,21 @@ pub enum AuthenticaterJwtConfig {
         #[serde(default)]
         verifier: JwtValidatorConfig,
 
+        client: IdpClientConfig,
+    },
+    /// Opaque access tokens, checked at an RFC 7662 introspection endpoint with the client
+    /// credentials instead of being verified as jwt.
+    #[serde(rename = "introspection")]
+    Introspection {
+        issuer: Url,
+        auth_url: Url,
+        token_url: Url,
+        introspection_url: Url,
+
+        /// Its `nbf`, `aud` and `iss` checks apply to the introspected claims.
+        #[serde(default)]
+        validator: JwtValidatorConfig,
+
         client: IdpClientConfig,
     },
 }
@@ -93,6 +175,48 @@ pub struct AuthorizerFgaConfig {
     pub check: FgaCheckConfig,
     pub apikey: ApikeyTupleConfig,
     pub jwt: JwtTupleConfig,
+    #[serde(default)]
+    pub objects: FgaObjectConfig,
+}
+
+/// Object templates checked in `check.group` for each MCP method, `null` templates are not checked.
+/// Only `tools/call` is checked by default, the others need tuples in the model once set.
+/// `tools/call`, `prompts/get` and `resources/read` are also the objects their lists are filtered by.
+///
+/// Placeholders: `{name}` for tools and prompts, `{uri}` for resources, `{ref_type}`(`ref/prompt`,
+/// `ref/resource`) and `{ref}` for completions, `{level}` for logging. Values are escaped to valid
+/// object ids.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FgaObjectConfig {
+    #[serde(default = "default_tools_call_object")]
+    pub tools_call: Option<String>,
+    #[serde(default)]
+    pub resources_read: Option<String>,
+    #[serde(default)]
+    pub prompts_get: Option<String>,
+    #[serde(default)]
+    pub resources_subscribe: Option<String>,
+    #[serde(default)]
+    pub completion_complete: Option<String>,
+    #[serde(default)]
+    pub logging_set_level: Option<String>,
+}
+
+impl Default for FgaObjectConfig {
+    fn default() -> Self {
+        Self {
+            tools_call: default_tools_call_object(),
+            resources_read: None,
+            prompts_get: None,
+            resources_subscribe: None,
+            completion_complete: None,
+            logging_set_level: None,
+        }
+    }
+}
+
+fn default_tools_call_object() -> Option<String> {
+    Some("tools/call/{name}".to_string())
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -136,6 +260,11 @@ pub struct AuthorizerConstantConfig {
 
     #[serde_as(as = "OneOrMany<_>")]
     pub jwt: Vec<JwtWhitelistAndBlacklist>,
+
+    /// Tool names allowed to be listed and called, empty whitelist allows every tool. The same
+    /// rules apply to every principal.
+    #[serde(default)]
+    pub tools: WhitelistAndBlacklist,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -151,8 +280,7 @@ pub struct JwtWhitelistAndBlacklist {
     pub blacklist: HashSet<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[derive(Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct WhitelistAndBlacklist {
     #[serde(default)]
     pub whitelist: HashSet<String>,
@@ -160,7 +288,6 @@ pub struct WhitelistAndBlacklist {
     pub blacklist: HashSet<String>,
 }
 
-
 fn default_type() -> JwtContextPointerType {
     JwtContextPointerType::String
 }
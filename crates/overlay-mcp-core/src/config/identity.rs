@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use httpbuilder::http_reference::HttpReference;
+use jsonptr::PointerBuf;
+use redact::Secret;
+use serde::{Deserialize, Serialize};
+
+use super::auth::JwtContextPointerType;
+
+/// Caller identity injected into upstream requests.
+///
+/// Pointers are resolved against the identity document of the authentication,
+/// `{"type": "jwt", "jwt": {claims}}`, `{"type": "apikey", "apikey": {"fingerprint", "from"}}` or `{"type": "anonymous"}`.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct IdentityConfig {
+    #[serde(default)]
+    pub fields: Vec<IdentityField>,
+    #[serde(default)]
+    pub token: Option<IdentityTokenConfig>,
+}
+
+/// Single value copied from the identity document, missing values are not injected.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IdentityField {
+    pub to: HttpReference,
+    pub path: PointerBuf,
+    #[serde(default = "default_type")]
+    pub r#type: JwtContextPointerType,
+}
+
+/// HS256 token signed by overlay, issued for every upstream request when sent in a header.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IdentityTokenConfig {
+    #[serde(default = "default_token_to")]
+    pub to: HttpReference,
+    #[serde(serialize_with = "redact::serde::redact_secret")]
+    pub secret: Secret<String>,
+    #[serde(default = "default_token_issuer")]
+    pub issuer: String,
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// Token lifetime in seconds.
+    #[serde(default = "default_token_ttl")]
+    pub ttl: u64,
+    /// Extra claims, claim name to pointer in the identity document.
+    #[serde(default)]
+    pub claims: HashMap<String, PointerBuf>,
+}
+
+fn default_type() -> JwtContextPointerType {
+    JwtContextPointerType::String
+}
+
+fn default_token_to() -> HttpReference {
+    HttpReference::Header("X-Overlay-Identity".to_string())
+}
+
+fn default_token_issuer() -> String {
+    "overlay-mcp".to_string()
+}
+
+fn default_token_ttl() -> u64 {
+    3600
+}
@@ -1,15 +1,23 @@
+pub mod admin;
 pub mod application;
+pub mod audit;
 pub mod auth;
+pub mod identity;
 pub mod otel;
+pub mod ratelimit;
 pub mod reqmodifier;
 pub mod server;
 pub mod upstream;
 
 use serde::{Deserialize, Serialize};
 
+pub use admin::AdminConfig;
 pub use application::ApplicationConfig;
+pub use audit::AuditConfig;
 pub use auth::AuthConfig;
+pub use identity::IdentityConfig;
 pub use otel::OpenTelemetryConfig;
+pub use ratelimit::RateLimitConfig;
 pub use reqmodifier::BaseModifiers;
 pub use server::ServerConfig;
 pub use upstream::UpstreamConfig;
@@ -21,4 +29,4 @@ pub struct Config {
     pub upstream: UpstreamConfig,
     pub auth: AuthConfig,
     pub otel: Option<OpenTelemetryConfig>,
-} 
\ No newline at end of file
+}
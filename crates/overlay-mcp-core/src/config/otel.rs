@@ -3,4 +3,22 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OpenTelemetryConfig {
     pub endpoint: String,
-} 
\ No newline at end of file
+    #[serde(default)]
+    pub protocol: OtlpProtocol,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+/// OTLP exporter protocol, `http/protobuf` posts to `{endpoint}/v1/traces`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OtlpProtocol {
+    #[default]
+    #[serde(rename = "grpc")]
+    Grpc,
+    #[serde(rename = "http/protobuf")]
+    HttpProtobuf,
+}
+
+fn default_service_name() -> String {
+    "overlay-mcp".to_string()
+}
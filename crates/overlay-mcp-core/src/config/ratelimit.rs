@@ -0,0 +1,93 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use jsonptr::PointerBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::Authentication;
+
+/// Limits applied to every caller, keyed by the first of `key` the caller resolves to.
+///
+/// Callers resolving to no key are not limited.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RateLimitConfig {
+    #[serde(default = "default_key")]
+    pub key: Vec<RateLimitKey>,
+    /// Sessions created through `/sse` or an `initialize` to `/mcp`.
+    #[serde(default)]
+    pub sessions: Option<RateLimit>,
+    /// Client messages of every session.
+    #[serde(default)]
+    pub messages: Option<RateLimit>,
+    /// `tools/call` requests, counted per tool.
+    #[serde(default)]
+    pub tools: Option<RateLimit>,
+    /// `tools/call` requests of a single tool, replaces `tools` for it.
+    #[serde(default)]
+    pub tool: HashMap<String, RateLimit>,
+    /// Open sessions of a principal.
+    #[serde(default)]
+    pub max_sessions: Option<usize>,
+}
+
+/// At most `limit` hits in every fixed window of `window` seconds.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct RateLimit {
+    pub limit: u64,
+    #[serde(default = "default_window")]
+    pub window: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum RateLimitKey {
+    /// String or number claim of the jwt.
+    Jwt { path: PointerBuf },
+    /// Fingerprint of the api key.
+    Apikey,
+    /// Client ip, extracted as configured by `application.ip_extract`.
+    Ip,
+}
+
+impl RateLimitConfig {
+    pub fn key(&self, authn: &Authentication, ip: Option<IpAddr>) -> Option<String> {
+        self.key.iter().find_map(|key| key.resolve(authn, ip))
+    }
+
+    pub fn tool(&self, name: &str) -> Option<RateLimit> {
+        self.tool.get(name).copied().or(self.tools)
+    }
+}
+
+impl RateLimitKey {
+    fn resolve(&self, authn: &Authentication, ip: Option<IpAddr>) -> Option<String> {
+        match (self, authn) {
+            (RateLimitKey::Jwt { path }, Authentication::Jwt { jwt }) => {
+                match path.resolve(&jwt.claims).ok()? {
+                    serde_json::Value::String(value) => Some(format!("jwt:{}", value)),
+                    serde_json::Value::Number(value) => Some(format!("jwt:{}", value)),
+                    _ => None,
+                }
+            }
+            (RateLimitKey::Apikey, Authentication::ApiKey { .. }) => authn
+                .principal()
+                .subject()
+                .map(|fingerprint| format!("apikey:{}", fingerprint)),
+            (RateLimitKey::Ip, _) => ip.map(|ip| format!("ip:{}", ip)),
+            _ => None,
+        }
+    }
+}
+
+fn default_key() -> Vec<RateLimitKey> {
+    vec![
+        RateLimitKey::Jwt {
+            path: PointerBuf::parse("/sub").expect("valid pointer"),
+        },
+        RateLimitKey::Apikey,
+        RateLimitKey::Ip,
+    ]
+}
+
+fn default_window() -> u64 {
+    60
+}
@@ -1,4 +1,12 @@
+use httpbuilder::modifier::HttpModifier;
 use serde::{Deserialize, Serialize};
+use serde_with::{formats::PreferMany, serde_as, OneOrMany};
 
+/// Rules copying components of the downstream request into upstream requests, applied in order.
+#[serde_as]
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct BaseModifiers {}
+#[serde(transparent)]
+pub struct BaseModifiers {
+    #[serde_as(as = "OneOrMany<_, PreferMany>")]
+    pub modifiers: Vec<HttpModifier>,
+}
@@ -8,6 +8,83 @@ pub struct ServerConfig {
     pub hostname: Url,
     #[serde(default)]
     pub cluster: ClusterConfig,
+    /// Seconds a POST to `/mcp` answered with json waits for the upstream responses before it
+    /// fails, event stream answers are not limited.
+    ///
+    /// Defaults to `300`.
+    #[serde(default = "default_response_timeout")]
+    pub response_timeout: u64,
+    /// Seconds a session may go without client or server messages before it is closed.
+    ///
+    /// Not limited by default.
+    #[serde(default)]
+    pub idle_timeout: Option<u64>,
+    /// Seconds a session may live at most, however active it is.
+    ///
+    /// Not limited by default.
+    #[serde(default)]
+    pub max_lifetime: Option<u64>,
+    /// What happens to the event streams of a session once the caller's jwt expired.
+    #[serde(default)]
+    pub token_expiry: TokenExpiryPolicy,
+    /// Lifetime of clients registered at `/register`.
+    #[serde(default)]
+    pub registration: ClientRegistrationConfig,
+}
+
+/// Seconds registered clients and their secrets live, `0` never expires a secret.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClientRegistrationConfig {
+    #[serde(default = "default_client_ttl")]
+    pub client_ttl: u64,
+    #[serde(default = "default_client_secret_ttl")]
+    pub client_secret_ttl: u64,
+    /// `/token` forwards the `client_credentials` grant with the caller's own idp client credentials.
+    #[serde(default)]
+    pub client_credentials: bool,
+}
+
+impl Default for ClientRegistrationConfig {
+    fn default() -> Self {
+        Self {
+            client_ttl: default_client_ttl(),
+            client_secret_ttl: default_client_secret_ttl(),
+            client_credentials: false,
+        }
+    }
+}
+
+impl ClientRegistrationConfig {
+    /// Grants proxied by `/token`.
+    pub fn grant_types_supported(&self) -> Vec<&'static str> {
+        let mut grant_types = vec!["authorization_code", "refresh_token"];
+        if self.client_credentials {
+            grant_types.push("client_credentials");
+        }
+        grant_types
+    }
+}
+
+fn default_client_ttl() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_client_secret_ttl() -> u64 {
+    0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenExpiryPolicy {
+    /// The stream is closed.
+    #[default]
+    Close,
+    /// The stream is paused until a `/message` renews the token, server messages meanwhile are dropped.
+    Reauthenticate,
+}
+
+fn default_response_timeout() -> u64 {
+    300
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -30,6 +107,11 @@ pub struct RaftConfig {
     pub id: Option<u64>,
     pub index: Option<usize>,
     pub secret: String,
+    /// Directory of the raft log and snapshots, relative to the working directory.
+    ///
+    /// Defaults to `hiqlite`.
+    #[serde(default = "default_data_dir")]
+    pub data_dir: String,
     #[serde(default)]
     pub cluster: hiqlite::RaftConfig, // Consider moving hiqlite specific parts to overlay-mcp-raft crate later
     pub nodes: Vec<Node>,
@@ -44,6 +126,10 @@ pub struct Node {
     pub read_pool_size: usize,
 }
 
+fn default_data_dir() -> String {
+    "hiqlite".to_string()
+}
+
 fn default_read_pool_size() -> usize {
     10
 }
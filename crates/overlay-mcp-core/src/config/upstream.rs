@@ -1,4 +1,7 @@
-use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf};
+
+use redact::Secret;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_with::{formats::PreferOne, serde_as, OneOrMany};
 use url::Url;
 
@@ -8,17 +11,138 @@ use url::Url;
 pub enum UpstreamConfig {
     Static(StaticUpstream),
     Discovery(DiscoveryUpstream),
+    Command(CommandUpstream),
 }
 
 #[serde_as]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StaticUpstream {
     #[serde_as(as = "OneOrMany<_, PreferOne>")]
-    pub urls: Vec<Url>,
+    pub urls: Vec<StaticUpstreamUrl>,
+    #[serde(default)]
+    pub transport: UpstreamTransportType,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum StaticUpstreamUrl {
+    Url(Url),
+    Detail {
+        url: Url,
+        transport: Option<UpstreamTransportType>,
+    },
 }
 
 #[serde_as]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DiscoveryUpstream {
     pub discovery: Url,
-} 
\ No newline at end of file
+    #[serde(default)]
+    pub transport: UpstreamTransportType,
+}
+
+/// Local stdio MCP server, spawned as a child process for every session.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommandUpstream {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment of the child process, typically credentials of the upstream.
+    #[serde(default, serialize_with = "redact_env")]
+    pub env: HashMap<String, Secret<String>>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+}
+
+/// Transport used by overlay to talk to the upstream MCP server.
+///
+/// `auto` tries the legacy SSE handshake first and falls back to Streamable HTTP.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum UpstreamTransportType {
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "sse")]
+    Sse,
+    #[serde(rename = "streamable-http")]
+    StreamableHttp,
+}
+
+impl StaticUpstreamUrl {
+    pub fn url(&self) -> &Url {
+        match self {
+            StaticUpstreamUrl::Url(url) => url,
+            StaticUpstreamUrl::Detail { url, .. } => url,
+        }
+    }
+
+    pub fn transport(&self) -> Option<UpstreamTransportType> {
+        match self {
+            StaticUpstreamUrl::Url(_) => None,
+            StaticUpstreamUrl::Detail { transport, .. } => *transport,
+        }
+    }
+}
+
+fn redact_env<S: Serializer>(
+    env: &HashMap<String, Secret<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        env.iter()
+            .map(|(name, value)| (name, format!("{:?}", value))),
+    )
+}
+
+impl CommandUpstream {
+    /// Whether `other` spawns the same program, whatever its env.
+    pub fn same_command(&self, other: &CommandUpstream) -> bool {
+        self.program == other.program && self.args == other.args && self.cwd == other.cwd
+    }
+}
+
+/// (De)serializes a command upstream without its env for the raft session rows, every node
+/// spawns the process with the env of its own config.
+pub(crate) mod without_env {
+    use super::*;
+    use serde::{Deserializer, Serialize};
+
+    // same fields in the same order both ways, bincode is not self describing
+    #[derive(Serialize)]
+    struct Borrowed<'a> {
+        program: &'a String,
+        args: &'a Vec<String>,
+        cwd: &'a Option<PathBuf>,
+    }
+
+    #[derive(Deserialize)]
+    struct Owned {
+        program: String,
+        args: Vec<String>,
+        cwd: Option<PathBuf>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        command: &CommandUpstream,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Borrowed {
+            program: &command.program,
+            args: &command.args,
+            cwd: &command.cwd,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<CommandUpstream, D::Error> {
+        let Owned { program, args, cwd } = Owned::deserialize(deserializer)?;
+        Ok(CommandUpstream {
+            program,
+            args,
+            env: HashMap::new(),
+            cwd,
+        })
+    }
+}
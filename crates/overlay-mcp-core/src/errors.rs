@@ -2,6 +2,7 @@ use axum::{body::Body, response::IntoResponse};
 use http::{Response, StatusCode};
 use jsonptr::PointerBuf;
 use oauth2::ConfigurationError;
+use serde::Serialize;
 
 use crate::auth::JwtContextPointerType;
 
@@ -20,6 +21,9 @@ pub enum Error {
     #[error("Not Found: {0}")]
     NotFound(#[from] Error404),
 
+    #[error("Too Many Requests: {0}")]
+    TooManyRequests(#[from] Error429),
+
     #[error("Service Unavailable: {0}")]
     ServiceUnavailable(#[from] Error503),
 
@@ -67,6 +71,15 @@ pub enum Error {
     #[error("SSE transport error: {0}")]
     SseTransportError(#[from] rmcp::transport::sse::SseTransportError),
 
+    #[error("Io error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Passthrough error: {0}")]
+    PassthroughError(#[from] httpbuilder::errors::HttpBuilderError),
+
+    #[error("Transport error: {0}")]
+    TransportError(#[from] crate::TransportError),
+
     #[error("Hickory resolver error: {0}")]
     HickoryResolverError(#[from] hickory_resolver::ResolveError),
 
@@ -74,12 +87,18 @@ pub enum Error {
     ConfigurationError(#[from] ConfigurationError),
 
     #[error("Hiqlite error: {0}")]
-    HiqliteError(#[from] hiqlite::Error),
+    HiqliteError(Box<hiqlite::Error>),
 
     #[error("Tokio broadcast error")]
     TokioBroadcastError,
 }
 
+impl From<hiqlite::Error> for Error {
+    fn from(err: hiqlite::Error) -> Self {
+        Self::HiqliteError(Box::new(err))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error400 {
     #[error("Invalid request")]
@@ -93,18 +112,45 @@ pub enum Error400 {
 
     #[error("Invalid token: {0}")]
     InvalidToken(&'static str),
+
+    #[error("Session id required")]
+    SessionIdRequired,
+
+    #[error("Subject required")]
+    SubjectRequired,
+
+    #[error("Invalid client metadata: {0}")]
+    InvalidClientMetadata(&'static str),
+
+    #[error("Redirect uri not registered for the client")]
+    InvalidRedirectUri,
+
+    #[error("Client not registered")]
+    UnknownClient,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error401 {
     #[error("Authentication failed")]
     AuthenticationFailed,
+
+    #[error("Client authentication failed")]
+    InvalidClient,
+
+    #[error("Invalid token: {0}")]
+    InvalidToken(&'static str),
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error403 {
     #[error("Authorization failed")]
     AuthorizationFailed,
+
+    #[error("Session belongs to another principal")]
+    SessionPrincipalMismatch,
+
+    #[error("Admin privileges required")]
+    AdminRequired,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -113,6 +159,18 @@ pub enum Error404 {
     SessionNotFound { session_id: String },
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum Error429 {
+    #[error("Rate limit of {bucket} exceeded, retry after {retry_after} seconds")]
+    RateLimited {
+        bucket: &'static str,
+        retry_after: u64,
+    },
+
+    #[error("Too many open sessions")]
+    TooManySessions,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error503 {
     #[error("No upstream mcp server found")]
@@ -124,9 +182,6 @@ pub enum FatalError {
     #[error("Timeout")]
     Timeout,
 
-    #[error("Token request error")]
-    TokenRequestError,
-
     #[error("Claim path not found")]
     ClaimPathNotFound(PointerBuf),
 
@@ -135,31 +190,186 @@ pub enum FatalError {
 
     #[error("Raft config error: {0}")]
     RaftUnresolvedNodeId(&'static str),
+
+    #[error("Invalid hash of api key {0}")]
+    InvalidApikeyHash(String),
+
+    #[error("Id of argon2 api key {0} contains '.', the key is presented as <id>.<secret>")]
+    InvalidApikeyId(String),
+}
+
+impl Error400 {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidUrl(_) => "invalid_url",
+            Self::InvalidHeaderString(_) => "invalid_header",
+            Self::BearerTokenExpected(_) => "bearer_token_expected",
+            Self::InvalidToken(_) => "invalid_token",
+            Self::SessionIdRequired => "session_id_required",
+            Self::SubjectRequired => "subject_required",
+            Self::InvalidClientMetadata(_) => "invalid_client_metadata",
+            Self::InvalidRedirectUri => "invalid_redirect_uri",
+            Self::UnknownClient => "unknown_client",
+        }
+    }
+}
+
+impl Error401 {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AuthenticationFailed => "authentication_required",
+            Self::InvalidClient => "invalid_client",
+            Self::InvalidToken(_) => "invalid_token",
+        }
+    }
+}
+
+impl Error403 {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AuthorizationFailed => "authorization_failed",
+            Self::SessionPrincipalMismatch => "session_principal_mismatch",
+            Self::AdminRequired => "admin_required",
+        }
+    }
+}
+
+impl Error404 {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::SessionNotFound { .. } => "session_not_found",
+        }
+    }
+}
+
+impl Error429 {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::RateLimited { .. } => "rate_limited",
+            Self::TooManySessions => "too_many_sessions",
+        }
+    }
+}
+
+impl Error503 {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoUpstreamMcpServer => "no_upstream",
+        }
+    }
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            // closed sessions are gone for the client, it starts a new one (MCP 2025-03-26)
+            Self::NotFound(_) | Self::AlreadyClosedSession(_) => StatusCode::NOT_FOUND,
+            Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::ServiceUnavailable(_) | Self::HiqliteError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Fatal(FatalError::Timeout) => StatusCode::GATEWAY_TIMEOUT,
+            Self::ReqwestError(err) if err.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            Self::ReqwestError(_)
+            | Self::OpenfgaError(_)
+            | Self::DiscoveryError(_)
+            | Self::SseTransportError(_)
+            | Self::TransportError(_)
+            | Self::HickoryResolverError(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable code of the error for clients, the `code` of its problem details.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest(e) => e.code(),
+            Self::Unauthorized(e) => e.code(),
+            Self::Forbidden(e) => e.code(),
+            Self::NotFound(e) => e.code(),
+            Self::AlreadyClosedSession(_) => "session_closed",
+            Self::TooManyRequests(e) => e.code(),
+            Self::ServiceUnavailable(e) => e.code(),
+            Self::HiqliteError(_) => "cluster_unavailable",
+            _ => match self.status() {
+                StatusCode::GATEWAY_TIMEOUT => "upstream_timeout",
+                StatusCode::BAD_GATEWAY => "upstream_error",
+                _ => "internal_error",
+            },
+        }
+    }
+}
+
+/// Problem details (RFC 9457) of an error response. The server fills in `request_id`, the
+/// response carries a copy as extension for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    pub title: &'static str,
+    pub status: u16,
+    pub code: &'static str,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl Problem {
+    pub const CONTENT_TYPE: &'static str = "application/problem+json";
+
+    pub fn body(&self) -> Body {
+        Body::from(serde_json::to_vec(self).expect("problem is serializable"))
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response<Body> {
-        match self {
-            Self::BadRequest(e) => Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Body::from(e.to_string()))
-                .unwrap(),
-            Self::Unauthorized(e) => Response::builder()
-                .status(StatusCode::UNAUTHORIZED)
-                .body(Body::from(e.to_string()))
-                .unwrap(),
-            Self::Forbidden(e) => Response::builder()
-                .status(StatusCode::FORBIDDEN)
-                .body(Body::from(e.to_string()))
-                .unwrap(),
-            Self::Fatal(e) => Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::from(e.to_string()))
-                .unwrap(),
-            _ => Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap(),
+        let status = self.status();
+        // details of server side errors stay in the log
+        let detail = match status {
+            StatusCode::INTERNAL_SERVER_ERROR => {
+                tracing::error!(error = ?self, "internal error");
+                "Internal server error".to_string()
+            }
+            StatusCode::BAD_GATEWAY => {
+                tracing::error!(error = ?self, "upstream error");
+                "Upstream request failed".to_string()
+            }
+            StatusCode::GATEWAY_TIMEOUT => {
+                tracing::error!(error = ?self, "upstream timeout");
+                "Upstream request timed out".to_string()
+            }
+            StatusCode::SERVICE_UNAVAILABLE => {
+                tracing::warn!(error = ?self, "service unavailable");
+                match &self {
+                    Self::ServiceUnavailable(e) => e.to_string(),
+                    _ => "Service unavailable".to_string(),
+                }
+            }
+            _ => match &self {
+                Self::BadRequest(e) => e.to_string(),
+                Self::Unauthorized(e) => e.to_string(),
+                Self::Forbidden(e) => e.to_string(),
+                Self::NotFound(e) => e.to_string(),
+                Self::TooManyRequests(e) => e.to_string(),
+                e => e.to_string(),
+            },
+        };
+        let problem = Problem {
+            title: status.canonical_reason().unwrap_or_default(),
+            status: status.as_u16(),
+            code: self.code(),
+            detail,
+            request_id: None,
+        };
+
+        let mut response = Response::builder()
+            .status(status)
+            .header(http::header::CONTENT_TYPE, Problem::CONTENT_TYPE);
+        if let Self::TooManyRequests(Error429::RateLimited { retry_after, .. }) = &self {
+            response = response.header(http::header::RETRY_AFTER, *retry_after);
         }
+        let mut response = response.body(problem.body()).unwrap();
+        response.extensions_mut().insert(problem);
+        response
     }
 }
@@ -0,0 +1,162 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use rmcp::model::{
+    JsonRpcNotification, JsonRpcVersion2_0, LoggingLevel, LoggingMessageNotification,
+    LoggingMessageNotificationMethod, LoggingMessageNotificationParam, ServerJsonRpcMessage,
+    ServerNotification,
+};
+use serde_json::json;
+use tokio::sync::watch;
+
+use crate::server::ServerConfig;
+
+/// Idle timeout and maximum lifetime of sessions, activity and deadlines are unix milliseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionLimits {
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionExpiry {
+    IdleTimeout,
+    MaxLifetime,
+    TokenExpired,
+    TokenRenewalRequired,
+}
+
+/// Unix milliseconds of the last client or server message of a session.
+#[derive(Debug)]
+pub struct Activity(AtomicU64);
+
+impl SessionLimits {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            idle_timeout: config.idle_timeout.map(Duration::from_secs),
+            max_lifetime: config.max_lifetime.map(Duration::from_secs),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.idle_timeout.is_none() && self.max_lifetime.is_none()
+    }
+
+    /// The earliest time a session created at `created_at` (unix seconds) expires, and why.
+    pub fn deadline(&self, created_at: u64, last_active: u64) -> Option<(u64, SessionExpiry)> {
+        let idle = self.idle_timeout.map(|idle| {
+            (
+                last_active + idle.as_millis() as u64,
+                SessionExpiry::IdleTimeout,
+            )
+        });
+        let lifetime = self.max_lifetime.map(|lifetime| {
+            (
+                created_at * 1000 + lifetime.as_millis() as u64,
+                SessionExpiry::MaxLifetime,
+            )
+        });
+        match (idle, lifetime) {
+            (Some(idle), Some(lifetime)) => Some(if idle.0 < lifetime.0 { idle } else { lifetime }),
+            (idle, lifetime) => idle.or(lifetime),
+        }
+    }
+
+    /// The expiry of a session at `now`, if it is over.
+    pub fn expired(&self, created_at: u64, last_active: u64, now: u64) -> Option<SessionExpiry> {
+        self.deadline(created_at, last_active)
+            .filter(|(deadline, _)| *deadline <= now)
+            .map(|(_, expiry)| expiry)
+    }
+
+    /// Seconds a stored session may outlive its deadline by `grace`, `None` if unlimited.
+    pub fn ttl(&self, created_at: u64, last_active: u64, grace: Duration) -> Option<i64> {
+        self.deadline(created_at, last_active).map(|(deadline, _)| {
+            let remaining = deadline.saturating_sub(unix_now_millis()) / 1000;
+            (remaining + grace.as_secs()) as i64
+        })
+    }
+}
+
+impl SessionExpiry {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::IdleTimeout => "idle_timeout",
+            Self::MaxLifetime => "max_lifetime",
+            Self::TokenExpired => "token_expired",
+            Self::TokenRenewalRequired => "token_renewal_required",
+        }
+    }
+
+    /// `notifications/message` sent downstream right before the session or stream is closed.
+    pub fn notification(&self) -> ServerJsonRpcMessage {
+        let message = match self {
+            Self::IdleTimeout => "session closed after being idle",
+            Self::MaxLifetime => "session closed after reaching its maximum lifetime",
+            Self::TokenExpired => "stream closed after the access token expired",
+            Self::TokenRenewalRequired => {
+                "access token expired, send a message with a renewed token to resume the stream"
+            }
+        };
+        ServerJsonRpcMessage::Notification(JsonRpcNotification {
+            jsonrpc: JsonRpcVersion2_0,
+            notification: ServerNotification::LoggingMessageNotification(
+                LoggingMessageNotification {
+                    method: LoggingMessageNotificationMethod,
+                    params: LoggingMessageNotificationParam {
+                        level: LoggingLevel::Warning,
+                        logger: Some("overlay-mcp".to_string()),
+                        data: json!({ "reason": self.as_str(), "message": message }),
+                    },
+                },
+            ),
+        })
+    }
+}
+
+impl Activity {
+    pub fn new(last_active: u64) -> Self {
+        Self(AtomicU64::new(last_active))
+    }
+
+    pub fn touch(&self) {
+        self.0.fetch_max(unix_now_millis(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self::new(unix_now_millis())
+    }
+}
+
+/// Milliseconds since the unix epoch.
+pub fn unix_now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Sleep until `deadline` (unix milliseconds).
+pub async fn sleep_until_millis(deadline: u64) {
+    let remaining = deadline.saturating_sub(unix_now_millis());
+    tokio::time::sleep(Duration::from_millis(remaining)).await;
+}
+
+/// Raise the token expiry (unix seconds) of a session, watchers wake only if it is later.
+pub fn raise_token_expiry(token_expires_at: &watch::Sender<u64>, expires_at: u64) {
+    token_expires_at.send_if_modified(|current| {
+        let later = *current < expires_at;
+        if later {
+            *current = expires_at;
+        }
+        later
+    });
+}
@@ -1,16 +1,20 @@
 use crate::{
-    Authentication, AuthorizationResult, BypassDownstream, Downstream, Error, SessionGuard,
-    StreamGuard, Upstream,
+    Authentication, AuthorizationResult, BypassDownstream, Downstream, Error, Error403, Principal,
+    RegisteredClient, SessionGuard, SessionInfo, StreamGuard, Upstream, UpstreamEndpoint,
 };
 use oauth2::{basic::BasicClient, EndpointMaybeSet, EndpointNotSet, EndpointSet, Scope};
 use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
-use std::{borrow::Cow, future::Future};
+use std::{borrow::Cow, future::Future, time::Duration};
+use tokio::sync::watch;
+use tokio_util::sync::WaitForCancellationFutureOwned;
 use url::Url;
 
 pub trait GeneralSession: Sync {
-    fn session_id(&self) -> Cow<str>;
+    fn session_id(&self) -> Cow<'_, str>;
 
-    fn upstream_url(&self) -> Cow<Url>;
+    fn upstream(&self) -> Cow<'_, UpstreamEndpoint>;
+
+    fn principal(&self) -> Cow<'_, Principal>;
 
     fn guard_upstream(&self) -> impl Future<Output = Result<StreamGuard<Upstream>, Error>> + Send;
     fn guard_downstream(
@@ -28,6 +32,39 @@ pub trait GeneralSession: Sync {
     ) -> impl Future<Output = Result<(), Error>> + Send;
     fn stop(&self) -> impl Future<Output = Result<(), Error>> + Send;
     fn close(&self) -> impl Future<Output = Result<(), Error>> + Send;
+    /// Resolves once the session is closed, downstream streams end with it.
+    fn closed(&self) -> WaitForCancellationFutureOwned;
+
+    /// Latest `exp` (unix seconds) of the jwts the session was used with.
+    fn token_expiry(&self) -> impl Future<Output = Result<Option<u64>, Error>> + Send;
+    fn extend_token_expiry(
+        &self,
+        expires_at: u64,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    /// Latest token expiry known to this node, `0` until a jwt is seen. It changes once the expiry
+    /// is extended on any node.
+    fn watch_token_expiry(&self) -> watch::Receiver<u64>;
+
+    fn ensure_principal(&self, authn: &Authentication) -> Result<(), Error> {
+        if *self.principal() == authn.principal() {
+            Ok(())
+        } else {
+            Err(Error::Forbidden(Error403::SessionPrincipalMismatch))
+        }
+    }
+
+    fn renew_token(
+        &self,
+        authn: &Authentication,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        let expires_at = authn.expires_at();
+        async move {
+            match expires_at {
+                Some(expires_at) => self.extend_token_expiry(expires_at).await,
+                None => Ok(()),
+            }
+        }
+    }
 
     fn ensure_started(
         &self,
@@ -49,12 +86,34 @@ pub trait GeneralSessionManager {
     // session
     fn create(
         &self,
-        upstream_url: Url,
+        upstream: UpstreamEndpoint,
+        principal: Principal,
     ) -> impl Future<Output = Result<Self::Session, Error>> + Send;
     fn find(
         &self,
         session_id: &str,
     ) -> impl Future<Output = Result<Option<Self::Session>, Error>> + Send;
+    fn list(&self) -> impl Future<Output = Result<Vec<SessionInfo>, Error>> + Send;
+
+    // counters
+    /// Increment `counter`, created with `ttl`, and return its value.
+    fn count(
+        &self,
+        counter: String,
+        ttl: Duration,
+    ) -> impl Future<Output = Result<u64, Error>> + Send;
+
+    // oauth clients
+    /// Keep a client registered at `/register` for `ttl`.
+    fn register_client(
+        &self,
+        client: RegisteredClient,
+        ttl: Duration,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    fn get_client(
+        &self,
+        client_id: &str,
+    ) -> impl Future<Output = Result<Option<RegisteredClient>, Error>> + Send;
 }
 
 pub trait GeneralAuthn {
@@ -84,10 +143,12 @@ pub trait GeneralAuthz {
         message: &ClientJsonRpcMessage,
     ) -> impl Future<Output = Result<AuthorizationResult, Error>>;
 
+    /// Responses to `tools/list`, `prompts/list` and `resources/list` are rewritten in place,
+    /// entries the target may not use are removed.
     fn authorize_server_message(
         &self,
         target: &Authentication,
-        message: &ServerJsonRpcMessage,
+        message: &mut ServerJsonRpcMessage,
     ) -> impl Future<Output = Result<AuthorizationResult, Error>>;
 }
 
@@ -96,5 +157,5 @@ pub trait GeneralResolver {
     fn resolve(
         &self,
         target: &http::request::Parts,
-    ) -> impl Future<Output = Result<Url, Error>> + Send;
+    ) -> impl Future<Output = Result<UpstreamEndpoint, Error>> + Send;
 }
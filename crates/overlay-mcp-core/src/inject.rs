@@ -0,0 +1,122 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use httpbuilder::{http_reference::HttpReference, modifier::HttpModifier};
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::{Map, Value};
+
+use crate::{
+    auth::JwtContextPointerType,
+    config::identity::{IdentityField, IdentityTokenConfig},
+    Authentication, Error, FatalError, IdentityConfig, Principal,
+};
+
+impl IdentityConfig {
+    /// Modifiers setting the identity of `authn` on upstream requests, a token sent in a header is
+    /// left to the transport which issues one for every request.
+    ///
+    /// Every identity target is removed first, a value the client sent itself and passthrough
+    /// copied must not reach the upstream when the claim does not resolve.
+    pub fn modifiers(&self, authn: &Authentication) -> Result<Vec<HttpModifier>, Error> {
+        let identity = authn.identity();
+        let mut modifiers = self
+            .fields
+            .iter()
+            .map(|field| &field.to)
+            .chain(self.token.as_ref().map(|token| &token.to))
+            .map(|target| HttpModifier::Remove {
+                remove: target.clone().into(),
+            })
+            .collect::<Vec<_>>();
+        for field in self.fields.iter() {
+            if let Some(value) = field.value(&identity)? {
+                modifiers.push(HttpModifier::Set {
+                    set: field.to.clone(),
+                    value,
+                });
+            }
+        }
+        if let Some(token) = self.token.as_ref().filter(|token| !token.is_per_request()) {
+            modifiers.push(HttpModifier::Set {
+                set: token.to.clone(),
+                value: token.issue(authn)?,
+            });
+        }
+        Ok(modifiers)
+    }
+}
+
+impl IdentityField {
+    fn value(&self, identity: &Value) -> Result<Option<String>, Error> {
+        let Ok(value) = self.path.resolve(identity) else {
+            return Ok(None);
+        };
+        match self.r#type {
+            JwtContextPointerType::String => Ok(Some(scalar_to_string(value))),
+            JwtContextPointerType::StringArray => {
+                let values =
+                    value
+                        .as_array()
+                        .ok_or(Error::Fatal(FatalError::UnexpectedClaimType(
+                            JwtContextPointerType::StringArray,
+                        )))?;
+                Ok(Some(
+                    values
+                        .iter()
+                        .map(scalar_to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ))
+            }
+        }
+    }
+}
+
+impl IdentityTokenConfig {
+    /// Header tokens are issued for every upstream request, a query token once per connection.
+    pub fn is_per_request(&self) -> bool {
+        matches!(self.to, HttpReference::Header(_))
+    }
+
+    pub fn issue(&self, authn: &Authentication) -> Result<String, Error> {
+        let identity = authn.identity();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut claims = Map::new();
+        for (name, path) in self.claims.iter() {
+            if let Ok(value) = path.resolve(&identity) {
+                claims.insert(name.clone(), value.clone());
+            }
+        }
+        let principal = authn.principal();
+        match (&principal, principal.subject()) {
+            (Principal::Jwt { .. }, Some(subject)) => {
+                claims.insert("sub".to_string(), Value::String(subject.to_string()));
+            }
+            (Principal::ApiKey { .. }, Some(subject)) => {
+                claims.insert(
+                    "sub".to_string(),
+                    Value::String(format!("apikey:{}", subject)),
+                );
+            }
+            _ => {}
+        }
+        claims.insert("iss".to_string(), Value::String(self.issuer.clone()));
+        if let Some(audience) = &self.audience {
+            claims.insert("aud".to_string(), Value::String(audience.clone()));
+        }
+        claims.insert("iat".to_string(), Value::from(now));
+        claims.insert("exp".to_string(), Value::from(now + self.ttl));
+
+        let key = EncodingKey::from_secret(self.secret.expose_secret().as_bytes());
+        Ok(jsonwebtoken::encode(&Header::default(), &claims, &key)?)
+    }
+}
+
+fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
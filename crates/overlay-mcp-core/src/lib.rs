@@ -1,15 +1,28 @@
+mod audit_log;
 mod config;
 mod errors;
+mod expiry;
 mod general;
 mod guard;
+mod inject;
 mod mcp;
+mod metric;
 mod models;
+mod oauth;
 mod stream;
+mod telemetry;
+mod transport;
 
+pub use audit_log::*;
 pub use config::*;
 pub use errors::*;
+pub use expiry::*;
 pub use general::*;
 pub use guard::*;
 pub use mcp::*;
+pub use metric::*;
 pub use models::*;
+pub use oauth::*;
 pub use stream::*;
+pub use telemetry::*;
+pub use transport::*;
@@ -23,3 +23,23 @@ impl MCP for MCP20241105 {
             .map(|(_, value)| value)
     }
 }
+
+pub struct MCP20250326;
+
+impl MCP20250326 {
+    pub const SESSION_ID_HEADER: &'static str = "mcp-session-id";
+}
+
+impl MCP for MCP20250326 {
+    fn version() -> &'static str {
+        "20250326"
+    }
+
+    fn pick_session_id(parts: &request::Parts) -> Option<Cow<'_, str>> {
+        parts
+            .headers
+            .get(Self::SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(Cow::Borrowed)
+    }
+}
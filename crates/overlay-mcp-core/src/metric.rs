@@ -0,0 +1,141 @@
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+
+use crate::AuthorizationResult;
+
+/// Live sessions held by a session manager, labeled `manager`.
+const SESSIONS_ACTIVE: &str = "mcp_sessions_active";
+/// Open upstream connections, labeled `transport`.
+const UPSTREAM_CONNECTIONS: &str = "mcp_upstream_connections";
+/// Upstream connections that could not be established.
+const UPSTREAM_CONNECT_FAILURES_TOTAL: &str = "mcp_upstream_connect_failures_total";
+/// JSON-RPC messages through upstream connections, labeled `direction` and `method`.
+const MESSAGES_TOTAL: &str = "mcp_messages_total";
+/// Time from a `tools/call` request until its response, labeled `tool` and `result`.
+const TOOL_CALL_DURATION_SECONDS: &str = "mcp_tool_call_duration_seconds";
+/// Authorization decisions, labeled `authorizer`, `stage` and `result`.
+const AUTHZ_DECISIONS_TOTAL: &str = "mcp_authz_decisions_total";
+/// Latency of OpenFGA check requests, labeled `kind` and `result`.
+const OPENFGA_CHECK_DURATION_SECONDS: &str = "openfga_check_duration_seconds";
+/// Raft cluster events, labeled `direction` and `event`.
+const RAFT_EVENTS_TOTAL: &str = "raft_events_total";
+
+/// Label of tools no upstream listed.
+const OTHER_TOOL: &str = "other";
+/// Most tool names remembered, tools listed beyond it are labeled `other`.
+const KNOWN_TOOLS_LIMIT: usize = 1024;
+
+/// Tool names listed in upstream `tools/list` responses on this node. Tool names come from
+/// clients, only listed ones become labels or counter keys so clients can not create unbounded
+/// series.
+static KNOWN_TOOLS: LazyLock<RwLock<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// Help texts of the MCP metrics, call once the recorder is installed.
+pub fn describe_metrics() {
+    describe_gauge!(SESSIONS_ACTIVE, "Live sessions held by the session manager");
+    describe_gauge!(UPSTREAM_CONNECTIONS, "Open upstream connections");
+    describe_counter!(
+        UPSTREAM_CONNECT_FAILURES_TOTAL,
+        "Upstream connections that could not be established"
+    );
+    describe_counter!(
+        MESSAGES_TOTAL,
+        "JSON-RPC messages sent to and received from upstream"
+    );
+    describe_histogram!(
+        TOOL_CALL_DURATION_SECONDS,
+        metrics::Unit::Seconds,
+        "Time from a tools/call request until its response"
+    );
+    describe_counter!(AUTHZ_DECISIONS_TOTAL, "Authorization decisions");
+    describe_histogram!(
+        OPENFGA_CHECK_DURATION_SECONDS,
+        metrics::Unit::Seconds,
+        "Latency of OpenFGA check requests"
+    );
+    describe_counter!(RAFT_EVENTS_TOTAL, "Raft events published and received");
+}
+
+pub fn record_active_sessions(manager: &'static str, count: usize) {
+    gauge!(SESSIONS_ACTIVE, "manager" => manager).set(count as f64);
+}
+
+pub fn record_upstream_connected(transport: &'static str) {
+    gauge!(UPSTREAM_CONNECTIONS, "transport" => transport).increment(1);
+}
+
+pub fn record_upstream_disconnected(transport: &'static str) {
+    gauge!(UPSTREAM_CONNECTIONS, "transport" => transport).decrement(1);
+}
+
+pub fn record_upstream_connect_failure() {
+    counter!(UPSTREAM_CONNECT_FAILURES_TOTAL).increment(1);
+}
+
+/// `direction` is `upstream` for client messages sent to upstream, `downstream` for messages
+/// received from it. Responses carry the method of their request.
+pub fn record_message(direction: &'static str, method: Option<&str>) {
+    let method = method.unwrap_or("unknown").to_string();
+    counter!(MESSAGES_TOTAL, "direction" => direction, "method" => method).increment(1);
+}
+
+/// Remember the tools of an upstream `tools/list` response.
+pub fn remember_tools<'a>(names: impl IntoIterator<Item = &'a str>) {
+    let mut known = KNOWN_TOOLS.write().unwrap();
+    for name in names {
+        if known.len() >= KNOWN_TOOLS_LIMIT {
+            break;
+        }
+        if !known.contains(name) {
+            known.insert(name.to_string());
+        }
+    }
+}
+
+/// `name` if an upstream listed the tool, `other` if not.
+pub fn known_tool(name: &str) -> &str {
+    if KNOWN_TOOLS.read().unwrap().contains(name) {
+        name
+    } else {
+        OTHER_TOOL
+    }
+}
+
+pub fn record_tool_call(tool: &str, elapsed: Duration, is_error: bool) {
+    let result = if is_error { "error" } else { "ok" };
+    let tool = known_tool(tool).to_string();
+    histogram!(TOOL_CALL_DURATION_SECONDS, "tool" => tool, "result" => result)
+        .record(elapsed.as_secs_f64());
+}
+
+/// `stage` is `enter`, `client_message` or `server_message`, a failed authorization is recorded
+/// as `error`.
+pub fn record_authz_decision<E>(
+    authorizer: &'static str,
+    stage: &'static str,
+    result: &Result<AuthorizationResult, E>,
+) {
+    let result = match result {
+        Ok(result) => result.as_str(),
+        Err(_) => "error",
+    };
+    counter!(AUTHZ_DECISIONS_TOTAL, "authorizer" => authorizer, "stage" => stage, "result" => result)
+        .increment(1);
+}
+
+/// `kind` is `check` or `batch_check`.
+pub fn record_openfga_check(kind: &'static str, elapsed: Duration, ok: bool) {
+    let result = if ok { "ok" } else { "error" };
+    histogram!(OPENFGA_CHECK_DURATION_SECONDS, "kind" => kind, "result" => result)
+        .record(elapsed.as_secs_f64());
+}
+
+/// `direction` is `publish` or `listen`.
+pub fn record_raft_event(direction: &'static str, event: &'static str) {
+    counter!(RAFT_EVENTS_TOTAL, "direction" => direction, "event" => event).increment(1);
+}
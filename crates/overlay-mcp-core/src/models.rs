@@ -1,20 +1,145 @@
 use httpbuilder::http_reference::HttpReference;
 use jsonwebtoken::TokenData;
+use rmcp::model::{ClientJsonRpcMessage, ErrorCode, ErrorData};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use url::Url;
 
-use crate::{Error, Error401, Error403};
+use crate::{
+    auth::ApikeyEntry,
+    upstream::{CommandUpstream, UpstreamTransportType},
+    Error, Error401, Error403,
+};
 
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Authentication {
     ApiKey {
         apikey: String,
         apikey_from: HttpReference,
+        /// Configured key the secret matched, unset when no keys are configured.
+        key: Option<Arc<ApikeyEntry>>,
     },
+    /// Verified jwt, or the claims of an introspected opaque token with a default header.
     Jwt {
         jwt: TokenData<serde_json::Value>,
     },
     NoAuth,
 }
 
+/// Identity a session is bound to, the raw api key is never stored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Principal {
+    ApiKey {
+        fingerprint: String,
+        // always serialized, raft stores the principal in a non self-describing format
+        #[serde(default)]
+        id: Option<String>,
+    },
+    Jwt {
+        issuer: Option<String>,
+        subject: Option<String>,
+    },
+    Anonymous,
+}
+
+impl Principal {
+    /// Jwt subject, or the id of the api key falling back to its fingerprint.
+    pub fn subject(&self) -> Option<&str> {
+        match self {
+            Principal::ApiKey { id: Some(id), .. } => Some(id),
+            Principal::ApiKey { fingerprint, .. } => Some(fingerprint),
+            Principal::Jwt { subject, .. } => subject.as_deref(),
+            Principal::Anonymous => None,
+        }
+    }
+
+    /// Jwt issuer, api keys and anonymous principals have none.
+    pub fn issuer(&self) -> Option<&str> {
+        match self {
+            Principal::Jwt { issuer, .. } => issuer.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl Authentication {
+    pub fn principal(&self) -> Principal {
+        match self {
+            Authentication::ApiKey { apikey, key, .. } => {
+                let digest = Sha256::digest(apikey.as_bytes());
+                Principal::ApiKey {
+                    fingerprint: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+                    id: key.as_ref().map(|key| key.id.clone()),
+                }
+            }
+            Authentication::Jwt { jwt } => {
+                let claim = |name: &str| {
+                    jwt.claims
+                        .get(name)
+                        .and_then(|value| value.as_str())
+                        .map(str::to_string)
+                };
+                Principal::Jwt {
+                    issuer: claim("iss"),
+                    subject: claim("sub"),
+                }
+            }
+            Authentication::NoAuth => Principal::Anonymous,
+        }
+    }
+
+    /// `exp` claim of the jwt, unix seconds.
+    pub fn expires_at(&self) -> Option<u64> {
+        match self {
+            Authentication::Jwt { jwt } => jwt.claims.get("exp").and_then(|exp| exp.as_u64()),
+            _ => None,
+        }
+    }
+
+    /// Whether the tool allowlist of the api key lets `tool` be listed and called.
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        match self {
+            Authentication::ApiKey { key: Some(key), .. } => {
+                key.tools.as_ref().is_none_or(|tools| tools.contains(tool))
+            }
+            _ => true,
+        }
+    }
+
+    /// Document the identity injection pointers are resolved against.
+    pub fn identity(&self) -> serde_json::Value {
+        match self {
+            Authentication::ApiKey {
+                apikey_from, key, ..
+            } => {
+                let Principal::ApiKey { fingerprint, .. } = self.principal() else {
+                    unreachable!("api key authentication always has api key principal")
+                };
+                let mut identity = serde_json::json!({
+                    "type": "apikey",
+                    "apikey": {
+                        "fingerprint": fingerprint,
+                        "from": apikey_from.to_string(),
+                    },
+                });
+                if let Some(key) = key {
+                    identity["apikey"]["id"] = serde_json::json!(key.id);
+                    identity["apikey"]["owner"] = serde_json::json!(key.owner);
+                }
+                identity
+            }
+            Authentication::Jwt { jwt } => serde_json::json!({
+                "type": "jwt",
+                "jwt": jwt.claims,
+            }),
+            Authentication::NoAuth => serde_json::json!({ "type": "anonymous" }),
+        }
+    }
+}
+
 pub enum AuthorizationResult {
     Allow,
     Deny,
@@ -22,6 +147,14 @@ pub enum AuthorizationResult {
 }
 
 impl AuthorizationResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthorizationResult::Allow => "allow",
+            AuthorizationResult::Deny => "deny",
+            AuthorizationResult::Unauthorized => "unauthorized",
+        }
+    }
+
     pub fn to_err_response(&self) -> Result<(), Error> {
         match self {
             AuthorizationResult::Allow => Ok(()),
@@ -32,3 +165,93 @@ impl AuthorizationResult {
         }
     }
 }
+
+/// JSON-RPC error code for client messages rejected by the authorization policy.
+pub const POLICY_DENIED_ERROR_CODE: ErrorCode = ErrorCode(-32003);
+
+/// JSON-RPC error returned to the client instead of forwarding a denied message.
+pub fn policy_denied_error(message: &ClientJsonRpcMessage) -> ErrorData {
+    let method = serde_json::to_value(message)
+        .ok()
+        .and_then(|value| value.get("method").cloned())
+        .unwrap_or(serde_json::Value::Null);
+    ErrorData {
+        code: POLICY_DENIED_ERROR_CODE,
+        message: "This method is not allowed".into(),
+        data: Some(serde_json::json!({
+            "reason": "policy_denied",
+            "method": method,
+        })),
+    }
+}
+
+/// JSON-RPC error code for client requests over the caller's rate limit.
+pub const RATE_LIMITED_ERROR_CODE: ErrorCode = ErrorCode(-32029);
+
+/// JSON-RPC error returned to the client instead of forwarding a rate limited request.
+pub fn rate_limited_error(bucket: &str, retry_after: u64) -> ErrorData {
+    ErrorData {
+        code: RATE_LIMITED_ERROR_CODE,
+        message: "Rate limit exceeded".into(),
+        data: Some(serde_json::json!({
+            "reason": "rate_limited",
+            "bucket": bucket,
+            "retry_after": retry_after,
+        })),
+    }
+}
+
+/// JSON-RPC error returned to the client instead of a server response it may not see.
+pub fn response_denied_error() -> ErrorData {
+    ErrorData {
+        code: POLICY_DENIED_ERROR_CODE,
+        message: "This response is not allowed".into(),
+        data: Some(serde_json::json!({ "reason": "policy_denied" })),
+    }
+}
+
+/// Upstream a session connects to, stored in raft session rows.
+///
+/// Stays externally tagged, bincode can not deserialize internally tagged enums. Commands are
+/// serialized without their env, [`crate::transport::passthrough`] takes it from the local config.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpstreamEndpoint {
+    Http {
+        url: Url,
+        transport: UpstreamTransportType,
+    },
+    Command(#[serde(with = "crate::upstream::without_env")] CommandUpstream),
+}
+
+impl UpstreamEndpoint {
+    /// Url of the upstream, or the program spawned for it. Safe to show, unlike the endpoint.
+    pub fn location(&self) -> String {
+        match self {
+            UpstreamEndpoint::Http { url, .. } => url.to_string(),
+            UpstreamEndpoint::Command(command) => command.program.clone(),
+        }
+    }
+}
+
+/// Live session as listed by the admin api.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub principal: Principal,
+    pub upstream: String,
+    /// Unix timestamp in seconds.
+    pub created_at: u64,
+    /// Whether the session is connected to upstream.
+    pub started: bool,
+    /// Raft node holding the upstream connection, none in standalone.
+    pub node: Option<u64>,
+}
+
+/// Seconds since the unix epoch.
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
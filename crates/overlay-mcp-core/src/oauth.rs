@@ -0,0 +1,87 @@
+use argon2::{
+    password_hash::{PasswordHasher, SaltString},
+    Argon2, PasswordHash, PasswordVerifier,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{unix_now, Error, Error401};
+
+/// OAuth client registered at `/register` (RFC 7591), kept by the session manager.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RegisteredClient {
+    pub client_id: String,
+    /// Argon2 PHC string of the secret, public clients have none.
+    pub client_secret_hash: Option<String>,
+    pub client_id_issued_at: u64,
+    /// Unix seconds, `0` if the secret never expires.
+    pub client_secret_expires_at: u64,
+    pub redirect_uris: Vec<String>,
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    pub grant_types: Vec<String>,
+    pub response_types: Vec<String>,
+    pub client_name: Option<String>,
+    pub client_uri: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEndpointAuthMethod {
+    None,
+    ClientSecretPost,
+    #[default]
+    ClientSecretBasic,
+}
+
+impl RegisteredClient {
+    /// Redirect uris are compared as registered, without normalization.
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+
+    pub fn allows_grant_type(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|grant| grant == grant_type)
+    }
+
+    /// Check the secret presented at the token endpoint, public clients need none.
+    pub async fn verify_secret(&self, secret: Option<&str>) -> Result<(), Error> {
+        let Some(hash) = &self.client_secret_hash else {
+            return Ok(());
+        };
+        if self.client_secret_expires_at != 0 && self.client_secret_expires_at <= unix_now() {
+            tracing::info!(client_id = self.client_id, "client secret expired");
+            return Err(Error401::InvalidClient.into());
+        }
+        let Some(secret) = secret else {
+            return Err(Error401::InvalidClient.into());
+        };
+        // argon2 is slow on purpose, keep it off the runtime threads
+        let (hash, secret) = (hash.clone(), secret.to_string());
+        let verified = tokio::task::spawn_blocking(move || {
+            PasswordHash::new(&hash).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(secret.as_bytes(), &hash)
+                    .is_ok()
+            })
+        })
+        .await
+        .unwrap_or(false);
+        match verified {
+            true => Ok(()),
+            false => Err(Error401::InvalidClient.into()),
+        }
+    }
+}
+
+/// Salted argon2 hash of a generated client secret.
+pub async fn hash_client_secret(secret: String) -> String {
+    let salt: [u8; 16] = rand::random();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::encode_b64(&salt).expect("16 bytes is a valid salt");
+        Argon2::default()
+            .hash_password(secret.as_bytes(), &salt)
+            .expect("argon2 hashes any secret with default params")
+            .to_string()
+    })
+    .await
+    .expect("argon2 hashing does not panic")
+}
@@ -1,9 +1,12 @@
 use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
-use tokio::sync::broadcast;
+use tokio::sync::broadcast::{self, error::RecvError};
 
-use crate::Error;
+use crate::{Error, TraceContext};
 
-pub struct Upstream(pub broadcast::Sender<ClientJsonRpcMessage>);
+/// Client message toward upstream, with the trace context it was sent from.
+pub type UpstreamMessage = (ClientJsonRpcMessage, TraceContext);
+
+pub struct Upstream(pub broadcast::Sender<UpstreamMessage>);
 
 pub struct Downstream(pub broadcast::Receiver<ServerJsonRpcMessage>);
 
@@ -11,23 +14,33 @@ pub struct BypassDownstream(pub broadcast::Sender<ServerJsonRpcMessage>);
 
 impl Upstream {
     pub async fn send(&self, msg: ClientJsonRpcMessage) -> Result<(), Error> {
-        self.0.send(msg).map_err(|_| Error::TokioBroadcastError)?;
+        self.0
+            .send((msg, TraceContext::current()))
+            .map_err(|_| Error::TokioBroadcastError)?;
         Ok(())
     }
 }
 
 impl Downstream {
+    /// Next server message, a receiver lagging behind skips the messages it missed.
     pub async fn recv(&mut self) -> Result<ServerJsonRpcMessage, Error> {
-        let msg = self
-            .0
-            .recv()
-            .await
-            .map_err(|_| Error::TokioBroadcastError)?;
-        Ok(msg)
+        loop {
+            match self.0.recv().await {
+                Ok(msg) => return Ok(msg),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "downstream lagged behind");
+                }
+                Err(RecvError::Closed) => return Err(Error::TokioBroadcastError),
+            }
+        }
     }
 }
 
 impl BypassDownstream {
+    pub fn subscribe(&self) -> Downstream {
+        Downstream(self.0.subscribe())
+    }
+
     pub async fn send(&self, msg: ServerJsonRpcMessage) -> Result<(), Error> {
         self.0.send(msg).map_err(|_| Error::TokioBroadcastError)?;
         Ok(())
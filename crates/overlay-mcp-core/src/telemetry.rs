@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use opentelemetry::global;
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use rmcp::model::{ClientJsonRpcMessage, JsonRpcMessage, ServerJsonRpcMessage};
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// W3C trace context of the span a client message was sent from.
+///
+/// Travels with the message to the task talking to upstream, which may live on another raft node.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraceContext(pub HashMap<String, String>);
+
+impl TraceContext {
+    pub fn current() -> Self {
+        let mut carrier = HashMap::new();
+        let context = Span::current().context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut carrier)
+        });
+        Self(carrier)
+    }
+
+    pub fn set_parent_of(&self, span: &Span) {
+        let context = global::get_text_map_propagator(|propagator| propagator.extract(&self.0));
+        span.set_parent(context);
+    }
+}
+
+/// Continue the trace of an incoming request carrying `traceparent`.
+pub fn set_parent_from_headers(span: &Span, headers: &http::HeaderMap) {
+    let context =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    span.set_parent(context);
+}
+
+/// Propagate the current span to an upstream request.
+pub fn inject_trace_headers(headers: &mut http::HeaderMap) {
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+/// Propagate the current span in `params._meta` of a request or notification, for transports
+/// without headers.
+pub fn inject_trace_meta(message: &mut serde_json::Value) {
+    let TraceContext(carrier) = TraceContext::current();
+    if carrier.is_empty() || message.get("method").is_none() {
+        return;
+    }
+    let Some(message) = message.as_object_mut() else {
+        return;
+    };
+    let params = message
+        .entry("params")
+        .or_insert_with(|| serde_json::Value::Object(Default::default()));
+    let Some(meta) = params.as_object_mut().map(|params| {
+        params
+            .entry("_meta")
+            .or_insert_with(|| serde_json::Value::Object(Default::default()))
+    }) else {
+        return;
+    };
+    if let Some(meta) = meta.as_object_mut() {
+        for (key, value) in carrier {
+            meta.insert(key, serde_json::Value::String(value));
+        }
+    }
+}
+
+/// Span of a single client message, `authz` is recorded once the decision is made.
+pub fn client_message_span(session_id: &str, message: &ClientJsonRpcMessage) -> Span {
+    let (method, tool) = client_message_method(message);
+    tracing::info_span!(
+        "mcp.message",
+        session_id,
+        rpc.method = method.as_deref(),
+        rpc.id = client_message_id(message).as_deref(),
+        mcp.tool = tool.as_deref(),
+        authz = tracing::field::Empty,
+    )
+}
+
+/// Span of a client message forwarded to upstream, until the upstream answered a request or the
+/// transport accepted a notification.
+pub fn upstream_span(session_id: &str, message: &ClientJsonRpcMessage) -> Span {
+    let (method, tool) = client_message_method(message);
+    tracing::info_span!(
+        "mcp.upstream",
+        session_id,
+        rpc.method = method.as_deref(),
+        rpc.id = client_message_id(message).as_deref(),
+        mcp.tool = tool.as_deref(),
+    )
+}
+
+/// JSON-RPC method of the message, and the tool name for `tools/call`.
+pub fn client_message_method(message: &ClientJsonRpcMessage) -> (Option<String>, Option<String>) {
+    let Ok(value) = serde_json::to_value(message) else {
+        return (None, None);
+    };
+    let method = value
+        .get("method")
+        .and_then(|method| method.as_str())
+        .map(str::to_string);
+    let tool = match method.as_deref() {
+        Some("tools/call") => value
+            .pointer("/params/name")
+            .and_then(|name| name.as_str())
+            .map(str::to_string),
+        _ => None,
+    };
+    (method, tool)
+}
+
+/// JSON-RPC method of a server request or notification.
+pub fn server_message_method(message: &ServerJsonRpcMessage) -> Option<String> {
+    match message {
+        JsonRpcMessage::Request(_) | JsonRpcMessage::Notification(_) => {
+            serde_json::to_value(message)
+                .ok()?
+                .get("method")?
+                .as_str()
+                .map(str::to_string)
+        }
+        _ => None,
+    }
+}
+
+fn client_message_id(message: &ClientJsonRpcMessage) -> Option<String> {
+    match message {
+        JsonRpcMessage::Request(request) => Some(request.id.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,1155 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    process::Stdio,
+    sync::{Arc, LazyLock, Mutex, RwLock},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{stream::BoxStream, Sink, Stream, StreamExt};
+use httpbuilder::{builder::HttpBuilder, errors::HttpBuilderError, http_reference::HttpReference};
+use reqwest::{
+    header::{HeaderName, ACCEPT, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use rmcp::{
+    model::{
+        CallToolResult, ClientJsonRpcMessage, ClientNotification, ClientRequest, ErrorData,
+        JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+        JsonRpcVersion2_0, RequestId, ServerJsonRpcMessage, ServerResult,
+    },
+    transport::sse::SseTransportError,
+};
+use serde::Deserialize;
+use sse_stream::{Sse, SseStream};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{ChildStdin, ChildStdout, Command},
+    sync::mpsc,
+    task::JoinSet,
+    time::timeout,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, Span};
+use url::Url;
+
+use crate::{
+    client_message_method,
+    identity::IdentityTokenConfig,
+    inject_trace_headers, inject_trace_meta, record_message, record_tool_call,
+    record_upstream_connect_failure, record_upstream_connected, record_upstream_disconnected,
+    remember_tools,
+    server_message_method,
+    upstream::{CommandUpstream, UpstreamTransportType},
+    AuditScope, Authentication, BaseModifiers, Error, Error503, IdentityConfig, UpstreamEndpoint,
+    MCP20250326,
+};
+
+const EVENT_STREAM_MIME_TYPE: &str = "text/event-stream";
+const JSON_MIME_TYPE: &str = "application/json";
+/// Wait before opening the legacy SSE event stream again after it failed.
+const SSE_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// Requests awaiting a response, the oldest is forgotten beyond it.
+const PENDING_REQUESTS_LIMIT: usize = 1024;
+/// How long the transport detected for an `auto` upstream is reused before probing again.
+const DETECTION_TTL: Duration = Duration::from_secs(600);
+
+// transport detected for `auto` upstreams by url without query, with when it was detected
+static DETECTED_TRANSPORTS: LazyLock<Mutex<HashMap<Url, (UpstreamTransportType, Instant)>>> =
+    LazyLock::new(Default::default);
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("SSE transport error: {0}")]
+    Sse(#[from] SseTransportError),
+
+    #[error("Transport closed")]
+    Closed,
+}
+
+/// Client side connection from overlay to an upstream MCP server.
+///
+/// Keeps the `mcp.upstream` span of every forwarded request until its response arrives.
+pub struct UpstreamConnection {
+    transport: UpstreamTransport,
+    pending: HashMap<RequestId, PendingRequest>,
+    audit: AuditScope,
+}
+
+/// Request forwarded to upstream, awaiting its response.
+struct PendingRequest {
+    // `mcp.upstream` span the request was sent in, ends once the response arrives
+    _span: Span,
+    method: Option<String>,
+    tool: Option<String>,
+    // redacted tool arguments, kept only while auditing
+    arguments: Option<serde_json::Value>,
+    started: Instant,
+}
+
+enum UpstreamTransport {
+    Sse(SseTransport),
+    StreamableHttp(StreamableHttpTransport),
+    Stdio(StdioTransport),
+}
+
+/// Http client for an upstream, issuing the identity token sent in a header for every request
+/// so a long lived upstream connection never carries an expired one.
+#[derive(Clone, Default)]
+pub struct UpstreamClient {
+    client: reqwest::Client,
+    identity_token: Option<Arc<IdentityToken>>,
+}
+
+struct IdentityToken {
+    config: IdentityTokenConfig,
+    authn: Authentication,
+    header: HeaderName,
+}
+
+impl UpstreamClient {
+    fn request(&self, method: Method, url: Url) -> reqwest::RequestBuilder {
+        let builder = self.client.request(method, url);
+        let Some(token) = &self.identity_token else {
+            return builder;
+        };
+        match token.config.issue(&token.authn) {
+            Ok(value) => builder.header(token.header.clone(), value),
+            Err(err) => {
+                tracing::error!(error = %err, "failed to issue identity token");
+                builder
+            }
+        }
+    }
+
+    fn get(&self, url: Url) -> reqwest::RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    fn post(&self, url: Url) -> reqwest::RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    fn delete(&self, url: Url) -> reqwest::RequestBuilder {
+        self.request(Method::DELETE, url)
+    }
+}
+
+/// Build the upstream endpoint and http client carrying passthrough components and caller identity of `original_request`.
+///
+/// Copied query parameters are appended to the endpoint url, headers become default headers of the client.
+/// Commands are spawned as configured in `command`, the local config, with its env.
+pub fn passthrough(
+    endpoint: &UpstreamEndpoint,
+    modifiers: &BaseModifiers,
+    identity: &IdentityConfig,
+    command: Option<&CommandUpstream>,
+    original_request: &http::request::Parts,
+) -> Result<(UpstreamEndpoint, UpstreamClient), Error> {
+    let (url, transport) = match endpoint {
+        UpstreamEndpoint::Http { url, transport } => (url, transport),
+        UpstreamEndpoint::Command(session_command) => {
+            let command = command
+                .filter(|command| command.same_command(session_command))
+                .ok_or(Error503::NoUpstreamMcpServer)?;
+            return Ok((
+                UpstreamEndpoint::Command(command.clone()),
+                UpstreamClient::default(),
+            ));
+        }
+    };
+    let authn = original_request
+        .extensions
+        .get::<Authentication>()
+        .unwrap_or(&Authentication::NoAuth);
+    let identity_token = match &identity.token {
+        Some(config) if config.is_per_request() => {
+            let HttpReference::Header(name) = &config.to else {
+                unreachable!("per request tokens are sent in a header");
+            };
+            let header = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| HttpBuilderError::InvalidHeaderName(name.clone()))?;
+            // fail the connection now rather than every request later
+            config.issue(authn)?;
+            Some(Arc::new(IdentityToken {
+                config: config.clone(),
+                authn: authn.clone(),
+                header,
+            }))
+        }
+        _ => None,
+    };
+    let identity = identity.modifiers(authn)?;
+    if modifiers.modifiers.is_empty() && identity.is_empty() {
+        let client = UpstreamClient {
+            client: reqwest::Client::new(),
+            identity_token,
+        };
+        return Ok((endpoint.clone(), client));
+    }
+    let dst = url
+        .as_str()
+        .parse::<http::Uri>()
+        .map_err(|err| HttpBuilderError::InvalidUri(err.into()))?;
+    let builder = HttpBuilder::new(original_request, dst)?
+        .apply(&modifiers.modifiers)?
+        .apply(&identity)?;
+    let url = Url::parse(&builder.uri()?.to_string())?;
+    let client = UpstreamClient {
+        client: reqwest::Client::builder()
+            .default_headers(builder.headers)
+            .build()?,
+        identity_token,
+    };
+    Ok((
+        UpstreamEndpoint::Http {
+            url,
+            transport: *transport,
+        },
+        client,
+    ))
+}
+
+impl UpstreamConnection {
+    /// Connect to the upstream, everything spawned for the connection lives until
+    /// `cancel_token` is cancelled or the connection is dropped. Tool calls are audited for
+    /// the session of `audit`.
+    pub async fn connect(
+        endpoint: &UpstreamEndpoint,
+        client: UpstreamClient,
+        audit: AuditScope,
+        cancel_token: CancellationToken,
+    ) -> Result<Self, Error> {
+        let transport = UpstreamTransport::connect(endpoint, client, cancel_token)
+            .await
+            .inspect_err(|_| record_upstream_connect_failure())?;
+        record_upstream_connected(transport.as_str());
+        Ok(Self {
+            transport,
+            pending: HashMap::new(),
+            audit,
+        })
+    }
+}
+
+impl Stream for UpstreamConnection {
+    type Item = ServerJsonRpcMessage;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let poll = this.transport.poll_next_unpin(cx);
+        if let Poll::Ready(Some(message)) = &poll {
+            let (id, is_error) = match message {
+                JsonRpcMessage::Response(JsonRpcResponse { id, result, .. }) => (
+                    Some(id),
+                    matches!(
+                        result,
+                        ServerResult::CallToolResult(CallToolResult {
+                            is_error: Some(true),
+                            ..
+                        })
+                    ),
+                ),
+                JsonRpcMessage::Error(error) => (Some(&error.id), true),
+                _ => (None, false),
+            };
+            match id.and_then(|id| this.pending.remove(id)) {
+                Some(request) => {
+                    record_message("downstream", request.method.as_deref());
+                    if let JsonRpcMessage::Response(JsonRpcResponse {
+                        result: ServerResult::ListToolsResult(list),
+                        ..
+                    }) = message
+                    {
+                        remember_tools(list.tools.iter().map(|tool| tool.name.as_ref()));
+                    }
+                    if let Some(tool) = &request.tool {
+                        let elapsed = request.started.elapsed();
+                        record_tool_call(tool, elapsed, is_error);
+                        if let (Some(arguments), Some(id)) = (&request.arguments, id) {
+                            let id = serde_json::to_value(id).unwrap_or_default();
+                            this.audit
+                                .tool_called(&id, tool, arguments, is_error, elapsed);
+                        }
+                    }
+                }
+                None => record_message("downstream", server_message_method(message).as_deref()),
+            }
+        }
+        poll
+    }
+}
+
+impl Drop for UpstreamConnection {
+    fn drop(&mut self) {
+        record_upstream_disconnected(self.transport.as_str());
+    }
+}
+
+impl Sink<ClientJsonRpcMessage> for UpstreamConnection {
+    type Error = TransportError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().transport).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: ClientJsonRpcMessage) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        // a cancelled request may never be answered
+        if let JsonRpcMessage::Notification(JsonRpcNotification {
+            notification: ClientNotification::CancelledNotification(cancelled),
+            ..
+        }) = &item
+        {
+            this.pending.remove(&cancelled.params.request_id);
+        }
+        let (method, tool) = client_message_method(&item);
+        record_message("upstream", method.as_deref());
+        if let JsonRpcMessage::Request(JsonRpcRequest { id, .. }) = &item {
+            if this.pending.len() >= PENDING_REQUESTS_LIMIT {
+                let oldest = this
+                    .pending
+                    .iter()
+                    .min_by_key(|(_, pending)| pending.started)
+                    .map(|(id, _)| id.clone());
+                if let Some(oldest) = oldest {
+                    tracing::warn!(id = ?oldest, "request never answered, forgotten");
+                    this.pending.remove(&oldest);
+                }
+            }
+            this.pending.insert(
+                id.clone(),
+                PendingRequest {
+                    _span: Span::current(),
+                    method,
+                    tool,
+                    arguments: this.audit.tool_call(&item).map(|(_, arguments)| arguments),
+                    started: Instant::now(),
+                },
+            );
+        }
+        Pin::new(&mut this.transport).start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().transport).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().transport).poll_close(cx)
+    }
+}
+
+impl UpstreamTransport {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sse(_) => "sse",
+            Self::StreamableHttp(_) => "streamable-http",
+            Self::Stdio(_) => "stdio",
+        }
+    }
+
+    async fn connect(
+        endpoint: &UpstreamEndpoint,
+        client: UpstreamClient,
+        cancel_token: CancellationToken,
+    ) -> Result<Self, Error> {
+        let (url, transport) = match endpoint {
+            UpstreamEndpoint::Http { url, transport } => (url, transport),
+            UpstreamEndpoint::Command(command) => {
+                let transport = StdioTransport::new(command, cancel_token)?;
+                return Ok(Self::Stdio(transport));
+            }
+        };
+        match transport {
+            UpstreamTransportType::Sse => {
+                let transport = SseTransport::connect(url.clone(), client, cancel_token).await?;
+                Ok(Self::Sse(transport))
+            }
+            UpstreamTransportType::StreamableHttp => Ok(Self::StreamableHttp(
+                StreamableHttpTransport::new(url.clone(), client, cancel_token),
+            )),
+            UpstreamTransportType::Auto => match detected_transport(url) {
+                Some(UpstreamTransportType::StreamableHttp) => Ok(Self::StreamableHttp(
+                    StreamableHttpTransport::new(url.clone(), client, cancel_token),
+                )),
+                Some(_) => {
+                    // probe again next time, the upstream may have moved to streamable http
+                    let transport = SseTransport::connect(url.clone(), client, cancel_token)
+                        .await
+                        .inspect_err(|_| forget_transport(url))?;
+                    Ok(Self::Sse(transport))
+                }
+                None => {
+                    let handshake = timeout(
+                        Duration::from_secs(5),
+                        SseTransport::connect(url.clone(), client.clone(), cancel_token.clone()),
+                    )
+                    .await;
+                    match handshake {
+                        Ok(Ok(transport)) => {
+                            remember_transport(url, UpstreamTransportType::Sse);
+                            return Ok(Self::Sse(transport));
+                        }
+                        Ok(Err(err)) => {
+                            tracing::info!(url = %url, error = %err, "sse handshake failed, fallback to streamable http");
+                            // a network error says nothing about the transport, probe again
+                            if rejects_sse(&err) {
+                                remember_transport(url, UpstreamTransportType::StreamableHttp);
+                            }
+                        }
+                        Err(_) => {
+                            tracing::info!(url = %url, "sse handshake timeout, fallback to streamable http");
+                        }
+                    }
+                    Ok(Self::StreamableHttp(StreamableHttpTransport::new(
+                        url.clone(),
+                        client,
+                        cancel_token,
+                    )))
+                }
+            },
+        }
+    }
+}
+
+// the upstream answered the sse handshake at the http level with something other than an event
+// stream, a streamable http upstream rejects the GET or answers it with a plain response
+fn rejects_sse(err: &SseTransportError) -> bool {
+    match err {
+        SseTransportError::Reqwest(err) => matches!(
+            err.status(),
+            Some(StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED)
+        ),
+        SseTransportError::UnexpectedContentType(_) => true,
+        _ => false,
+    }
+}
+
+fn detection_key(url: &Url) -> Url {
+    let mut key = url.clone();
+    // passthrough may add per caller query parameters
+    key.set_query(None);
+    key
+}
+
+fn detected_transport(url: &Url) -> Option<UpstreamTransportType> {
+    let detected = DETECTED_TRANSPORTS.lock().unwrap();
+    detected
+        .get(&detection_key(url))
+        .filter(|(_, detected_at)| detected_at.elapsed() < DETECTION_TTL)
+        .map(|(transport, _)| *transport)
+}
+
+fn remember_transport(url: &Url, transport: UpstreamTransportType) {
+    let mut detected = DETECTED_TRANSPORTS.lock().unwrap();
+    detected.retain(|_, (_, detected_at)| detected_at.elapsed() < DETECTION_TTL);
+    detected.insert(detection_key(url), (transport, Instant::now()));
+}
+
+fn forget_transport(url: &Url) {
+    DETECTED_TRANSPORTS
+        .lock()
+        .unwrap()
+        .remove(&detection_key(url));
+}
+
+impl Stream for UpstreamTransport {
+    type Item = ServerJsonRpcMessage;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            Self::Sse(SseTransport(transport))
+            | Self::StreamableHttp(StreamableHttpTransport(transport))
+            | Self::Stdio(StdioTransport(transport)) => transport.poll_next_unpin(cx),
+        }
+    }
+}
+
+impl Sink<ClientJsonRpcMessage> for UpstreamTransport {
+    type Error = TransportError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            Self::Sse(SseTransport(transport))
+            | Self::StreamableHttp(StreamableHttpTransport(transport))
+            | Self::Stdio(StdioTransport(transport)) => Pin::new(transport).poll_ready(cx),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: ClientJsonRpcMessage) -> Result<(), Self::Error> {
+        match self.get_mut() {
+            Self::Sse(SseTransport(transport))
+            | Self::StreamableHttp(StreamableHttpTransport(transport))
+            | Self::Stdio(StdioTransport(transport)) => Pin::new(transport).start_send(item),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            Self::Sse(SseTransport(transport))
+            | Self::StreamableHttp(StreamableHttpTransport(transport))
+            | Self::Stdio(StdioTransport(transport)) => Pin::new(transport).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            Self::Sse(SseTransport(transport))
+            | Self::StreamableHttp(StreamableHttpTransport(transport))
+            | Self::Stdio(StdioTransport(transport)) => Pin::new(transport).poll_close(cx),
+        }
+    }
+}
+
+/// MCP 2024-11-05 HTTP with SSE client transport.
+///
+/// Server messages arrive on a GET event stream whose first `endpoint` event names the url
+/// messages are POSTed to, `initialize` and `notifications/initialized` one at a time and the
+/// others concurrently, each with its own request headers.
+pub struct SseTransport(ChannelTransport);
+
+/// MCP 2025-03-26 Streamable HTTP client transport.
+///
+/// Messages are POSTed as they come, concurrently once the session is initialized; responses
+/// arrive either as a JSON body or an SSE stream, and server initiated messages arrive on a GET
+/// stream opened after `notifications/initialized`.
+pub struct StreamableHttpTransport(ChannelTransport);
+
+/// Stdio client transport, messages are newline delimited json on stdin/stdout.
+pub struct StdioTransport(ChannelTransport);
+
+/// Transport backed by a background task, talking to it over unbounded channels.
+struct ChannelTransport {
+    outgoing: mpsc::UnboundedSender<(ClientJsonRpcMessage, Span)>,
+    incoming: mpsc::UnboundedReceiver<ServerJsonRpcMessage>,
+}
+
+struct SseInner {
+    client: UpstreamClient,
+    url: Url,
+    post_url: Url,
+    last_event_id: Mutex<Option<String>>,
+    incoming: mpsc::UnboundedSender<ServerJsonRpcMessage>,
+    cancel_token: CancellationToken,
+}
+
+struct StreamableHttpInner {
+    client: UpstreamClient,
+    url: Url,
+    session_id: RwLock<Option<String>>,
+    incoming: mpsc::UnboundedSender<ServerJsonRpcMessage>,
+    cancel_token: CancellationToken,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ServerPayload {
+    Batch(Vec<ServerJsonRpcMessage>),
+    Single(ServerJsonRpcMessage),
+}
+
+impl SseTransport {
+    /// Open the event stream and wait for the `endpoint` event, the stream is opened again
+    /// when it fails until `cancel_token` is cancelled.
+    pub async fn connect(
+        url: Url,
+        client: UpstreamClient,
+        cancel_token: CancellationToken,
+    ) -> Result<Self, SseTransportError> {
+        let mut events = open_event_stream(&client, &url, None).await?;
+        let mut last_event_id = None;
+        let post_url = loop {
+            let event = events
+                .next()
+                .await
+                .ok_or(SseTransportError::UnexpectedEndOfStream)??;
+            if event.id.is_some() {
+                last_event_id = event.id;
+            }
+            if event.event.as_deref() == Some("endpoint") {
+                break url.join(&event.data.unwrap_or_default())?;
+            }
+        };
+        tracing::info!(url = %url, post_url = %post_url, "sse transport connected");
+
+        let (outgoing_send, outgoing_recv) = mpsc::unbounded_channel();
+        let (incoming_send, incoming_recv) = mpsc::unbounded_channel();
+        let inner = Arc::new(SseInner {
+            client,
+            url,
+            post_url,
+            last_event_id: Mutex::new(last_event_id),
+            incoming: incoming_send,
+            cancel_token: cancel_token.child_token(),
+        });
+        tokio::spawn(inner.clone().read_events(events));
+        tokio::spawn(inner.run(outgoing_recv));
+        Ok(Self(ChannelTransport {
+            outgoing: outgoing_send,
+            incoming: incoming_recv,
+        }))
+    }
+}
+
+async fn open_event_stream(
+    client: &UpstreamClient,
+    url: &Url,
+    last_event_id: Option<String>,
+) -> Result<BoxStream<'static, Result<Sse, sse_stream::Error>>, SseTransportError> {
+    let mut request = client
+        .get(url.clone())
+        .header(ACCEPT, EVENT_STREAM_MIME_TYPE);
+    if let Some(last_event_id) = last_event_id {
+        request = request.header("Last-Event-ID", last_event_id);
+    }
+    let response = request.send().await?.error_for_status()?;
+    match response.headers().get(CONTENT_TYPE) {
+        Some(content_type)
+            if content_type
+                .as_bytes()
+                .starts_with(EVENT_STREAM_MIME_TYPE.as_bytes()) => {}
+        content_type => {
+            return Err(SseTransportError::UnexpectedContentType(
+                content_type.cloned(),
+            ))
+        }
+    }
+    Ok(SseStream::from_byte_stream(response.bytes_stream()).boxed())
+}
+
+impl SseInner {
+    async fn run(
+        self: Arc<Self>,
+        mut outgoing: mpsc::UnboundedReceiver<(ClientJsonRpcMessage, Span)>,
+    ) {
+        let mut posts = JoinSet::new();
+        loop {
+            tokio::select! {
+                _ = self.cancel_token.cancelled() => {
+                    break;
+                }
+                Some(_) = posts.join_next(), if !posts.is_empty() => {}
+                msg = outgoing.recv() => {
+                    match msg {
+                        // the upstream must see initialize and initialized before anything else
+                        Some((msg, span)) if is_lifecycle_message(&msg) => {
+                            self.clone().post(msg).instrument(span).await
+                        }
+                        Some((msg, span)) => {
+                            posts.spawn(self.clone().post(msg).instrument(span));
+                        }
+                        None => {
+                            tracing::info!(url = %self.url, "sse transport dropped");
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.cancel_token.cancel();
+    }
+
+    async fn post(self: Arc<Self>, message: ClientJsonRpcMessage) {
+        let request_id = match &message {
+            JsonRpcMessage::Request(request) => Some(request.id.clone()),
+            _ => None,
+        };
+        let mut trace_headers = http::HeaderMap::new();
+        inject_trace_headers(&mut trace_headers);
+        let result = self
+            .client
+            .post(self.post_url.clone())
+            .headers(trace_headers)
+            .json(&message)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(err) = result {
+            tracing::error!(url = %self.post_url, error = %err, "sse post failed");
+            if let Some(id) = request_id {
+                let error = ServerJsonRpcMessage::Error(JsonRpcError {
+                    jsonrpc: JsonRpcVersion2_0,
+                    id,
+                    error: ErrorData::internal_error("Upstream request failed", None),
+                });
+                if self.incoming.send(error).is_err() {
+                    tracing::debug!("sse incoming channel closed");
+                }
+            }
+        }
+    }
+
+    async fn read_events(
+        self: Arc<Self>,
+        mut events: BoxStream<'static, Result<Sse, sse_stream::Error>>,
+    ) {
+        loop {
+            let event = tokio::select! {
+                _ = self.cancel_token.cancelled() => break,
+                event = events.next() => event,
+            };
+            match event {
+                Some(Ok(event)) => {
+                    if event.id.is_some() {
+                        *self.last_event_id.lock().unwrap() = event.id;
+                    }
+                    let Some(data) = event.data else {
+                        continue;
+                    };
+                    match serde_json::from_str::<ServerJsonRpcMessage>(&data) {
+                        Ok(message) => {
+                            if self.incoming.send(message).is_err() {
+                                break;
+                            }
+                        }
+                        Err(err) => {
+                            tracing::error!(error = %err, "failed to parse json rpc message");
+                        }
+                    }
+                }
+                Some(Err(err)) => {
+                    tracing::error!(url = %self.url, error = %err, "sse event stream error");
+                    match self.reopen().await {
+                        Some(reopened) => events = reopened,
+                        None => break,
+                    }
+                }
+                None => {
+                    tracing::info!(url = %self.url, "sse event stream ended");
+                    break;
+                }
+            }
+        }
+        self.cancel_token.cancel();
+    }
+
+    /// Open the event stream again, retrying until the transport is cancelled.
+    async fn reopen(&self) -> Option<BoxStream<'static, Result<Sse, sse_stream::Error>>> {
+        loop {
+            tokio::select! {
+                _ = self.cancel_token.cancelled() => return None,
+                _ = tokio::time::sleep(SSE_RETRY_INTERVAL) => {}
+            }
+            let last_event_id = self.last_event_id.lock().unwrap().clone();
+            match open_event_stream(&self.client, &self.url, last_event_id).await {
+                Ok(events) => return Some(events),
+                Err(err) => {
+                    tracing::warn!(url = %self.url, error = %err, "failed to reopen sse event stream");
+                }
+            }
+        }
+    }
+}
+
+impl StreamableHttpTransport {
+    pub fn new(url: Url, client: UpstreamClient, cancel_token: CancellationToken) -> Self {
+        let (outgoing_send, outgoing_recv) = mpsc::unbounded_channel();
+        let (incoming_send, incoming_recv) = mpsc::unbounded_channel();
+        let inner = Arc::new(StreamableHttpInner {
+            client,
+            url,
+            session_id: RwLock::new(None),
+            incoming: incoming_send,
+            cancel_token: cancel_token.child_token(),
+        });
+        tokio::spawn(inner.run(outgoing_recv));
+        Self(ChannelTransport {
+            outgoing: outgoing_send,
+            incoming: incoming_recv,
+        })
+    }
+}
+
+impl StdioTransport {
+    /// The child process is killed when `cancel_token` is cancelled or the transport is
+    /// dropped, stderr is forwarded to tracing.
+    pub fn new(command: &CommandUpstream, cancel_token: CancellationToken) -> Result<Self, Error> {
+        let mut process = Command::new(&command.program);
+        process
+            .args(&command.args)
+            .envs(
+                command
+                    .env
+                    .iter()
+                    .map(|(name, value)| (name, value.expose_secret())),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &command.cwd {
+            process.current_dir(cwd);
+        }
+        let mut child = process.spawn()?;
+        let pid = child.id();
+        tracing::info!(program = command.program, pid, "upstream process spawned");
+
+        let stdin = child.stdin.take().expect("stdin must be piped");
+        let stdout = child.stdout.take().expect("stdout must be piped");
+        let stderr = child.stderr.take().expect("stderr must be piped");
+        let (outgoing_send, outgoing_recv) = mpsc::unbounded_channel();
+        let (incoming_send, incoming_recv) = mpsc::unbounded_channel();
+        let stop_ct = cancel_token.child_token();
+
+        tokio::spawn(stdio_write(stdin, outgoing_recv, stop_ct.clone()));
+        tokio::spawn(stdio_read(stdout, incoming_send, stop_ct.clone()));
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::info!(pid, "upstream stderr: {}", line);
+            }
+        });
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = stop_ct.cancelled() => {
+                    if let Err(err) = child.kill().await {
+                        tracing::error!(pid, error = %err, "failed to kill upstream process");
+                    }
+                    tracing::info!(pid, "upstream process killed");
+                }
+                status = child.wait() => {
+                    match status {
+                        Ok(status) => tracing::info!(pid, %status, "upstream process exited"),
+                        Err(err) => tracing::error!(pid, error = %err, "failed to wait upstream process"),
+                    }
+                    stop_ct.cancel();
+                }
+            }
+        });
+
+        Ok(Self(ChannelTransport {
+            outgoing: outgoing_send,
+            incoming: incoming_recv,
+        }))
+    }
+}
+
+impl Stream for ChannelTransport {
+    type Item = ServerJsonRpcMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_recv(cx)
+    }
+}
+
+impl Sink<ClientJsonRpcMessage> for ChannelTransport {
+    type Error = TransportError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.outgoing.is_closed() {
+            return Poll::Ready(Err(TransportError::Closed));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: ClientJsonRpcMessage) -> Result<(), Self::Error> {
+        self.outgoing
+            .send((item, Span::current()))
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl StreamableHttpInner {
+    async fn run(
+        self: Arc<Self>,
+        mut outgoing: mpsc::UnboundedReceiver<(ClientJsonRpcMessage, Span)>,
+    ) {
+        // a slow request must not hold back the ones after it
+        let mut posts = JoinSet::new();
+        loop {
+            tokio::select! {
+                _ = self.cancel_token.cancelled() => {
+                    break;
+                }
+                Some(_) = posts.join_next(), if !posts.is_empty() => {}
+                msg = outgoing.recv() => {
+                    match msg {
+                        // later posts need the session id of the initialize response
+                        Some((msg, span)) if is_lifecycle_message(&msg) => {
+                            self.clone().post(msg).instrument(span).await
+                        }
+                        Some((msg, span)) => {
+                            posts.spawn(self.clone().post(msg).instrument(span));
+                        }
+                        None => {
+                            tracing::info!(url = %self.url, "streamable http transport dropped");
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.terminate().await;
+        self.cancel_token.cancel();
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.session_id
+            .read()
+            .expect("session id lock poisoned")
+            .clone()
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.session_id() {
+            Some(session_id) => builder.header(MCP20250326::SESSION_ID_HEADER, session_id),
+            None => builder,
+        }
+    }
+
+    fn deliver(&self, message: ServerJsonRpcMessage) {
+        if self.incoming.send(message).is_err() {
+            tracing::debug!("streamable http incoming channel closed");
+        }
+    }
+
+    /// Answers the request that could not be delivered, so the client is not left waiting.
+    fn fail(&self, request_id: Option<RequestId>, message: &'static str) {
+        if let Some(id) = request_id {
+            self.deliver(ServerJsonRpcMessage::Error(JsonRpcError {
+                jsonrpc: JsonRpcVersion2_0,
+                id,
+                error: ErrorData::internal_error(message, None),
+            }));
+        }
+    }
+
+    async fn post(self: Arc<Self>, message: ClientJsonRpcMessage) {
+        let request_id = match &message {
+            JsonRpcMessage::Request(request) => Some(request.id.clone()),
+            _ => None,
+        };
+        let is_initialized = matches!(
+            &message,
+            JsonRpcMessage::Notification(JsonRpcNotification {
+                notification: ClientNotification::InitializedNotification(_),
+                ..
+            })
+        );
+        let mut trace_headers = http::HeaderMap::new();
+        inject_trace_headers(&mut trace_headers);
+        let request = self.request(
+            self.client
+                .post(self.url.clone())
+                .header(
+                    ACCEPT,
+                    format!("{}, {}", JSON_MIME_TYPE, EVENT_STREAM_MIME_TYPE),
+                )
+                .headers(trace_headers)
+                .json(&message),
+        );
+        let response = match request.send().await {
+            // the upstream dropped the session, ending the connection lets the client start over
+            Ok(response)
+                if response.status() == StatusCode::NOT_FOUND && self.session_id().is_some() =>
+            {
+                tracing::warn!(url = %self.url, "upstream session expired");
+                self.session_id
+                    .write()
+                    .expect("session id lock poisoned")
+                    .take();
+                self.fail(request_id, "Upstream session expired");
+                self.cancel_token.cancel();
+                return;
+            }
+            response => response.and_then(|response| response.error_for_status()),
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::error!(url = %self.url, error = %err, "streamable http post failed");
+                self.fail(request_id, "Upstream request failed");
+                return;
+            }
+        };
+
+        if let Some(session_id) = response
+            .headers()
+            .get(MCP20250326::SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            self.session_id
+                .write()
+                .expect("session id lock poisoned")
+                .replace(session_id.to_string());
+        }
+        if is_initialized {
+            tokio::spawn(self.clone().listen());
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if content_type.starts_with(EVENT_STREAM_MIME_TYPE) {
+            tokio::spawn(self.clone().read_event_stream(response));
+        } else if content_type.starts_with(JSON_MIME_TYPE) {
+            match response.json::<ServerPayload>().await {
+                Ok(ServerPayload::Single(message)) => self.deliver(message),
+                Ok(ServerPayload::Batch(messages)) => messages
+                    .into_iter()
+                    .for_each(|message| self.deliver(message)),
+                Err(err) => {
+                    tracing::error!(url = %self.url, error = %err, "failed to parse streamable http response");
+                }
+            }
+        }
+    }
+
+    async fn listen(self: Arc<Self>) {
+        let request = self.request(
+            self.client
+                .get(self.url.clone())
+                .header(ACCEPT, EVENT_STREAM_MIME_TYPE),
+        );
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                self.read_event_stream(response).await;
+            }
+            Ok(response) => {
+                tracing::info!(url = %self.url, status = %response.status(), "upstream does not offer server stream");
+            }
+            Err(err) => {
+                tracing::error!(url = %self.url, error = %err, "failed to open server stream");
+            }
+        }
+    }
+
+    async fn read_event_stream(self: Arc<Self>, response: reqwest::Response) {
+        let mut event_stream = SseStream::from_byte_stream(response.bytes_stream());
+        loop {
+            tokio::select! {
+                _ = self.cancel_token.cancelled() => {
+                    break;
+                }
+                event = event_stream.next() => {
+                    match event {
+                        Some(Ok(event)) => {
+                            let Some(data) = event.data else {
+                                continue;
+                            };
+                            match serde_json::from_str::<ServerJsonRpcMessage>(&data) {
+                                Ok(message) => self.deliver(message),
+                                Err(err) => {
+                                    tracing::error!(error = %err, "failed to parse json rpc message");
+                                }
+                            }
+                        }
+                        Some(Err(err)) => {
+                            tracing::error!(error = %err, "streamable http event stream error");
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+
+    async fn terminate(&self) {
+        let Some(session_id) = self.session_id() else {
+            return;
+        };
+        let result = self
+            .client
+            .delete(self.url.clone())
+            .header(MCP20250326::SESSION_ID_HEADER, session_id)
+            .send()
+            .await;
+        if let Err(err) = result {
+            tracing::error!(url = %self.url, error = %err, "failed to terminate upstream session");
+        }
+    }
+}
+
+fn is_lifecycle_message(message: &ClientJsonRpcMessage) -> bool {
+    matches!(
+        message,
+        JsonRpcMessage::Request(JsonRpcRequest {
+            request: ClientRequest::InitializeRequest(_),
+            ..
+        }) | JsonRpcMessage::Notification(JsonRpcNotification {
+            notification: ClientNotification::InitializedNotification(_),
+            ..
+        })
+    )
+}
+
+async fn stdio_write(
+    mut stdin: ChildStdin,
+    mut outgoing: mpsc::UnboundedReceiver<(ClientJsonRpcMessage, Span)>,
+    stop_ct: CancellationToken,
+) {
+    loop {
+        let (message, span) = tokio::select! {
+            _ = stop_ct.cancelled() => break,
+            message = outgoing.recv() => match message {
+                Some(message) => message,
+                None => {
+                    tracing::info!("stdio transport dropped");
+                    stop_ct.cancel();
+                    break;
+                }
+            },
+        };
+        let mut message = serde_json::to_value(&message).expect("failed to serialize message");
+        // no headers on stdio, the trace context travels in `params._meta`
+        span.in_scope(|| inject_trace_meta(&mut message));
+        let mut line = serde_json::to_vec(&message).expect("failed to serialize message");
+        line.push(b'\n');
+        if let Err(err) = stdin.write_all(&line).await {
+            tracing::error!(error = %err, "failed to write upstream stdin");
+            stop_ct.cancel();
+            break;
+        }
+        if let Err(err) = stdin.flush().await {
+            tracing::error!(error = %err, "failed to flush upstream stdin");
+        }
+    }
+}
+
+async fn stdio_read(
+    stdout: ChildStdout,
+    incoming: mpsc::UnboundedSender<ServerJsonRpcMessage>,
+    stop_ct: CancellationToken,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        let line = tokio::select! {
+            _ = stop_ct.cancelled() => break,
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::error!(error = %err, "failed to read upstream stdout");
+                    break;
+                }
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ServerJsonRpcMessage>(&line) {
+            Ok(message) => {
+                if incoming.send(message).is_err() {
+                    break;
+                }
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "failed to parse json rpc message");
+            }
+        }
+    }
+}
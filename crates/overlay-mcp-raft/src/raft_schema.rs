@@ -1,3 +1,4 @@
+use overlay_mcp_core::{record_raft_event, TraceContext};
 use rmcp::{
     model::{ClientJsonRpcMessage, ServerJsonRpcMessage},
     serde_json,
@@ -7,6 +8,12 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize, hiqlite::EnumIter, hiqlite::ToPrimitive)]
 pub enum RaftSchema {
     Session,
+    // ids of the sessions each node holds, keyed by node id, the cache can not be iterated
+    SessionIndex,
+    // rate limit counts of every node, keyed by counter and node id
+    Counter,
+    // oauth clients registered at `/register`
+    Client,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +30,8 @@ pub enum RaftSchemaEvent {
     // If main session is received this event, it will notify to server
     // client -[MCP Protocol]> raft node(with sub session) -[Raft Cluster Event]> raft node(with main session) -[MCP Protocol]> server
     NotifyToMainSession(EventNotifyToMainSession),
+    // Token expiry of the session extended, streams of every node paused for a renewal resume
+    TokenExtended(String, u64),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +39,8 @@ pub struct EventNotifyToMainSession {
     pub session_id: String,
     // serialized ClientJsonRpcMessage
     pub raw_json: String,
+    // trace context of the sub session node
+    pub trace: TraceContext,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,10 +51,31 @@ pub struct EventNotifyToSubSession {
 }
 
 impl RaftSchemaEvent {
-    pub fn notify_to_main_session(session_id: String, event: &ClientJsonRpcMessage) -> Self {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DeleteSession(_) => "delete_session",
+            Self::NewMainSession(..) => "new_main_session",
+            Self::NotifyToSubSession(_) => "notify_to_sub_session",
+            Self::NotifyToMainSession(_) => "notify_to_main_session",
+            Self::TokenExtended(..) => "token_extended",
+        }
+    }
+
+    /// Notify every raft node of this event.
+    pub async fn publish(&self, raft_client: &hiqlite::Client) -> Result<(), hiqlite::Error> {
+        record_raft_event("publish", self.as_str());
+        raft_client.notify(self).await
+    }
+
+    pub fn notify_to_main_session(
+        session_id: String,
+        event: &ClientJsonRpcMessage,
+        trace: TraceContext,
+    ) -> Self {
         Self::NotifyToMainSession(EventNotifyToMainSession {
             session_id,
             raw_json: serde_json::to_string(event).unwrap(),
+            trace,
         })
     }
 
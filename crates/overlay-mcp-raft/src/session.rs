@@ -2,35 +2,41 @@ use std::{borrow::Cow, sync::Arc, time::Duration};
 
 use futures::{SinkExt, StreamExt};
 use overlay_mcp_core::{
-    BypassDownstream, Downstream, Error, FatalError, GeneralSession, SessionGuard, StreamGuard,
-    Upstream,
-};
-use rmcp::{
-    model::{ClientJsonRpcMessage, ServerJsonRpcMessage},
-    transport::{IntoTransport, SseTransport},
-    RoleClient,
+    passthrough, raise_token_expiry, sleep_until_millis, unix_now_millis, upstream_span, Activity,
+    BypassDownstream, Downstream, Error, FatalError, GeneralSession, Principal, SessionGuard,
+    StreamGuard, Upstream, UpstreamClient, UpstreamConnection, UpstreamEndpoint, UpstreamMessage,
 };
+use rmcp::model::ServerJsonRpcMessage;
 use serde::{Deserialize, Serialize};
 use tokio::{
     sync::{
         broadcast::{self, error::RecvError},
-        Mutex, Notify,
+        watch, Mutex, Notify,
     },
     time::timeout,
 };
-use tokio_util::sync::CancellationToken;
-use url::Url;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tracing::Instrument;
 use uuid::Uuid;
 
 use crate::{RaftManagerInner, RaftSchema, RaftSchemaEvent};
 
+/// How often a node other than the main node checks whether the session is over.
+const EXPIRY_POLL: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub struct RaftSession {
     pub(crate) parent: Arc<RaftManagerInner>,
 
     pub(crate) session_id: String,
-    pub(crate) upstream_url: Url,
+    pub(crate) upstream_endpoint: UpstreamEndpoint,
+    pub(crate) principal: Principal,
     pub(crate) subsession_id: String,
+    pub(crate) created_at: u64,
+    // messages seen by this node, the main node stores it in the session row
+    pub(crate) last_active: Arc<Activity>,
+    // latest token expiry written to the session row by this node or announced by others
+    pub(crate) token_expires_at: Arc<watch::Sender<u64>>,
 
     pub(crate) channels: Arc<RaftSessionChannels>,
     pub(crate) cancel_token: CancellationToken,
@@ -39,8 +45,16 @@ pub struct RaftSession {
 #[derive(Clone, Deserialize, Serialize)]
 pub struct RaftSessionData {
     pub(crate) session_id: String,
-    pub(crate) upstream_url: Url,
+    pub(crate) upstream: UpstreamEndpoint,
+    pub(crate) principal: Principal,
+    pub(crate) created_at: u64,
+    // unix milliseconds
+    pub(crate) last_active: u64,
+    // unix seconds, latest jwt expiry of any node
+    pub(crate) token_expires_at: Option<u64>,
     pub(crate) main_subsession_id: Option<String>,
+    // node of the main session, holding the upstream connection
+    pub(crate) main_node_id: Option<u64>,
 }
 
 pub struct RaftSessionChannels {
@@ -48,24 +62,30 @@ pub struct RaftSessionChannels {
     pub(crate) downstream: Mutex<Option<Downstream>>,
     pub(crate) bypass_downstream: Mutex<Option<BypassDownstream>>,
     connection: Mutex<RaftSessionConnection>,
+    // tells the downstream of the main session why the session expired
+    expiry_send: broadcast::Sender<ServerJsonRpcMessage>,
     pub(crate) stream_notify: Notify,
 }
 
 enum RaftSessionConnection {
     Stopped {
-        clt_recv: broadcast::Receiver<ClientJsonRpcMessage>,
+        clt_recv: broadcast::Receiver<UpstreamMessage>,
         svr_send: broadcast::Sender<ServerJsonRpcMessage>,
     },
     Started(CancellationToken),
 }
 
 impl GeneralSession for RaftSession {
-    fn session_id(&self) -> Cow<str> {
+    fn session_id(&self) -> Cow<'_, str> {
         Cow::Borrowed(&self.session_id)
     }
 
-    fn upstream_url(&self) -> Cow<Url> {
-        Cow::Borrowed(&self.upstream_url)
+    fn upstream(&self) -> Cow<'_, UpstreamEndpoint> {
+        Cow::Borrowed(&self.upstream_endpoint)
+    }
+
+    fn principal(&self) -> Cow<'_, Principal> {
+        Cow::Borrowed(&self.principal)
     }
 
     async fn guard_upstream(&self) -> Result<StreamGuard<Upstream>, Error> {
@@ -195,7 +215,7 @@ impl GeneralSession for RaftSession {
         )
         .await
         .map_err(|_| Error::Fatal(FatalError::Timeout))
-        .and_then(|result| result.map_err(Error::HiqliteError))?;
+        .and_then(|result| result.map_err(Error::from))?;
 
         let mut session_data = self
             .parent
@@ -205,19 +225,12 @@ impl GeneralSession for RaftSession {
             .ok_or_else(|| Error::AlreadyClosedSession(self.session_id.clone()))?;
         if session_data.main_subsession_id.is_none() {
             session_data.main_subsession_id = Some(self.subsession_id.clone());
+            session_data.main_node_id = Some(self.parent.node_id);
         }
-        self.parent
-            .raft_client
-            .put(
-                RaftSchema::Session,
-                self.session_id.clone(),
-                &session_data,
-                None,
-            )
-            .await?;
+        self.parent.put_session(&session_data).await?;
         drop(lock);
 
-        if session_data.main_subsession_id.as_ref() == Some(&self.subsession_id) {
+        if session_data.main_subsession_id.as_ref() != Some(&self.subsession_id) {
             tracing::info!(
                 session_id = self.session_id,
                 subsession_id = self.subsession_id,
@@ -230,7 +243,14 @@ impl GeneralSession for RaftSession {
             subsession_id = self.subsession_id,
             "start main session"
         );
-        self.start_main_session(&reqwest::Client::new()).await
+        let (endpoint, client) = passthrough(
+            &self.upstream_endpoint,
+            &self.parent.passthrough,
+            &self.parent.identity,
+            self.parent.command.as_ref(),
+            original_request,
+        )?;
+        self.start_main_session(&endpoint, client).await
     }
 
     async fn stop(&self) -> Result<(), Error> {
@@ -244,7 +264,7 @@ impl GeneralSession for RaftSession {
         )
         .await
         .map_err(|_| Error::Fatal(FatalError::Timeout))
-        .and_then(|result| result.map_err(Error::HiqliteError))?;
+        .and_then(|result| result.map_err(Error::from))?;
 
         let mut session_data = self
             .parent
@@ -254,15 +274,9 @@ impl GeneralSession for RaftSession {
             .ok_or_else(|| Error::AlreadyClosedSession(self.session_id.clone()))?;
         if session_data.main_subsession_id.as_ref() == Some(&self.subsession_id) {
             session_data.main_subsession_id = None;
-            self.parent
-                .raft_client
-                .put(
-                    RaftSchema::Session,
-                    self.session_id.clone(),
-                    &session_data,
-                    None,
-                )
-                .await?;
+            session_data.main_node_id = None;
+            session_data.last_active = session_data.last_active.max(self.last_active.get());
+            self.parent.put_session(&session_data).await?;
         }
         drop(lock);
 
@@ -300,45 +314,200 @@ impl GeneralSession for RaftSession {
             .raft_client
             .delete(RaftSchema::Session, self.session_id.clone())
             .await?;
+        RaftSchemaEvent::DeleteSession(self.session_id.clone())
+            .publish(&self.parent.raft_client)
+            .await?;
         self.parent
+            .audit
+            .session_closed(&self.session_id, &self.principal);
+        Ok(())
+    }
+
+    fn closed(&self) -> WaitForCancellationFutureOwned {
+        self.cancel_token.clone().cancelled_owned()
+    }
+
+    async fn token_expiry(&self) -> Result<Option<u64>, Error> {
+        Ok(self
+            .parent
             .raft_client
-            .notify(&RaftSchemaEvent::DeleteSession(self.session_id.clone()))
-            .await?;
+            .get::<_, _, RaftSessionData>(RaftSchema::Session, &self.session_id)
+            .await?
+            .and_then(|session_data| session_data.token_expires_at))
+    }
+
+    async fn extend_token_expiry(&self, expires_at: u64) -> Result<(), Error> {
+        // the row is written only when a later token is seen
+        if *self.token_expires_at.borrow() >= expires_at {
+            return Ok(());
+        }
+        let lock = timeout(
+            Duration::from_secs(5),
+            self.parent.raft_client.lock(self.session_id.clone()),
+        )
+        .await
+        .map_err(|_| Error::Fatal(FatalError::Timeout))
+        .and_then(|result| result.map_err(Error::from))?;
+
+        let mut session_data = self
+            .parent
+            .raft_client
+            .get::<_, _, RaftSessionData>(RaftSchema::Session, &self.session_id)
+            .await?
+            .ok_or_else(|| Error::AlreadyClosedSession(self.session_id.clone()))?;
+        let extended = session_data.token_expires_at < Some(expires_at);
+        if extended {
+            session_data.token_expires_at = Some(expires_at);
+            self.parent.put_session(&session_data).await?;
+        }
+        drop(lock);
+        raise_token_expiry(&self.token_expires_at, expires_at);
+        // streams paused on other nodes resume
+        if extended {
+            RaftSchemaEvent::TokenExtended(self.session_id.clone(), expires_at)
+                .publish(&self.parent.raft_client)
+                .await?;
+        }
         Ok(())
     }
+
+    fn watch_token_expiry(&self) -> watch::Receiver<u64> {
+        self.token_expires_at.subscribe()
+    }
 }
 
 impl RaftSession {
     pub(crate) fn new(
         parent: Arc<RaftManagerInner>,
-        session_id: String,
-        upstream_url: Url,
+        session_data: &RaftSessionData,
         cancel_token: CancellationToken,
     ) -> Self {
-        let (clt_send, clt_recv) = broadcast::channel::<ClientJsonRpcMessage>(16);
+        let (clt_send, clt_recv) = broadcast::channel::<UpstreamMessage>(16);
         let (svr_send, svr_recv) = broadcast::channel::<ServerJsonRpcMessage>(16);
 
         let upstream = Upstream(clt_send);
         let downstream = Downstream(svr_recv);
         let bypass_downstream = BypassDownstream(svr_send.clone());
 
-        RaftSession {
+        let session = RaftSession {
             parent,
-            session_id,
+            session_id: session_data.session_id.clone(),
             subsession_id: Uuid::new_v4().to_string(),
-            upstream_url,
+            upstream_endpoint: session_data.upstream.clone(),
+            principal: session_data.principal.clone(),
+            created_at: session_data.created_at,
+            last_active: Arc::new(Activity::new(session_data.last_active)),
+            token_expires_at: Arc::new(watch::Sender::new(
+                session_data.token_expires_at.unwrap_or_default(),
+            )),
             channels: Arc::new(RaftSessionChannels {
                 upstream: Mutex::new(Some(upstream)),
                 downstream: Mutex::new(Some(downstream)),
                 bypass_downstream: Mutex::new(Some(bypass_downstream)),
+                expiry_send: svr_send.clone(),
                 connection: Mutex::new(RaftSessionConnection::Stopped { clt_recv, svr_send }),
                 stream_notify: Notify::new(),
             }),
             cancel_token,
+        };
+        if !session.parent.limits.is_unlimited() {
+            tokio::spawn(session.clone().expire());
         }
+        session
     }
 
-    async fn start_main_session(&self, client: &reqwest::Client) -> Result<(), Error> {
+    // every node holding the session watches it, the main node decides while there is one
+    async fn expire(self) {
+        let limits = self.parent.limits;
+        let mut next_check = limits
+            .deadline(self.created_at, self.last_active.get())
+            .map(|(deadline, _)| deadline);
+        while let Some(deadline) = next_check {
+            tokio::select! {
+                _ = sleep_until_millis(deadline) => {}
+                _ = self.cancel_token.cancelled() => return,
+            }
+            next_check = match self.enforce_limits().await {
+                Ok(next_check) => next_check,
+                Err(e) => {
+                    tracing::error!(
+                        session_id = self.session_id,
+                        "failed to expire session: {:?}",
+                        e
+                    );
+                    Some(unix_now_millis() + EXPIRY_POLL.as_millis() as u64)
+                }
+            }
+        }
+    }
+
+    /// Close the session if it is over, returns when to check again.
+    async fn enforce_limits(&self) -> Result<Option<u64>, Error> {
+        let limits = self.parent.limits;
+        let lock = timeout(
+            Duration::from_secs(5),
+            self.parent.raft_client.lock(self.session_id.clone()),
+        )
+        .await
+        .map_err(|_| Error::Fatal(FatalError::Timeout))
+        .and_then(|result| result.map_err(Error::from))?;
+
+        let Some(mut session_data) = self
+            .parent
+            .raft_client
+            .get::<_, _, RaftSessionData>(RaftSchema::Session, &self.session_id)
+            .await?
+        else {
+            // the row expired, its main node is gone
+            tracing::info!(session_id = self.session_id, "session row expired");
+            self.parent.drop_session(&self.session_id).await;
+            self.cancel_token.cancel();
+            // every node holding the session records it, nobody else closes it
+            self.parent
+                .audit
+                .session_closed(&self.session_id, &self.principal);
+            return Ok(None);
+        };
+        let now = unix_now_millis();
+        let is_main = session_data.main_subsession_id.as_ref() == Some(&self.subsession_id);
+        if !is_main && session_data.main_subsession_id.is_some() {
+            let poll = limits.idle_timeout.unwrap_or(EXPIRY_POLL);
+            return Ok(Some(now + poll.as_millis() as u64));
+        }
+
+        let last_active = session_data.last_active.max(self.last_active.get());
+        if let Some(expiry) = limits.expired(session_data.created_at, last_active, now) {
+            tracing::info!(
+                session_id = self.session_id,
+                reason = expiry.as_str(),
+                "session expired"
+            );
+            let notification = expiry.notification();
+            if is_main {
+                let _ = self.channels.expiry_send.send(notification.clone());
+            }
+            RaftSchemaEvent::notify_to_sub_session(self.session_id.clone(), &notification)
+                .publish(&self.parent.raft_client)
+                .await?;
+            self.close().await?;
+            drop(lock);
+            return Ok(None);
+        }
+        if is_main && last_active > session_data.last_active {
+            session_data.last_active = last_active;
+            self.parent.put_session(&session_data).await?;
+        }
+        drop(lock);
+        Ok(limits
+            .deadline(session_data.created_at, last_active)
+            .map(|(deadline, _)| deadline))
+    }
+
+    async fn start_main_session(
+        &self,
+        endpoint: &UpstreamEndpoint,
+        client: UpstreamClient,
+    ) -> Result<(), Error> {
         if self.cancel_token.is_cancelled() {
             return Err(Error::AlreadyClosedSession(self.session_id.clone()));
         }
@@ -354,12 +523,13 @@ impl RaftSession {
         // let remote_event = self.parent.event_send.subscribe();
         let raft_client = self.parent.raft_client.clone();
         let remote_event_recv = self.parent.event_send.subscribe();
+        let audit = self.parent.audit.scope(&self.session_id, &self.principal);
         let transport =
-            SseTransport::start_with_client(self.upstream_url.clone(), client.clone()).await?;
-        let (transport_sink, transport_stream) =
-            IntoTransport::<RoleClient, _, _>::into_transport(transport);
+            UpstreamConnection::connect(endpoint, client, audit, stop_ct.clone()).await?;
+        let (transport_sink, transport_stream) = transport.split();
         let returning_chan = self.channels.clone();
         let my_session_id = self.session_id.clone();
+        let last_active = self.last_active.clone();
         tokio::spawn(async move {
             let stop_ct = stop_ct;
             let transport_sink = transport_sink;
@@ -376,8 +546,12 @@ impl RaftSession {
                     msg = remote_event_recv.recv() => {
                         match msg {
                             Ok(RaftSchemaEvent::NotifyToMainSession(event)) if event.session_id == my_session_id => {
-                                tracing::info!("my session event: {:?}", event);
-                                match transport_sink.send(event.to_client_json_rpc_message()).await {
+                                tracing::debug!("my session event: {:?}", event);
+                                last_active.touch();
+                                let msg = event.to_client_json_rpc_message();
+                                let span = upstream_span(&my_session_id, &msg);
+                                event.trace.set_parent_of(&span);
+                                match transport_sink.send(msg).instrument(span).await {
                                     Ok(_) => {}
                                     Err(e) => {
                                         tracing::error!("send error: {:?}", e);
@@ -385,7 +559,7 @@ impl RaftSession {
                                 }
                             }
                             Ok(msg) => {
-                                tracing::info!("other session event: {:?}", msg);
+                                tracing::debug!("other session event: {:?}", msg);
                             }
                             Err(e) => {
                                 tracing::error!("error listening for client message: {:?}", e);
@@ -395,9 +569,10 @@ impl RaftSession {
                     msg = transport_stream.next() => {
                         match msg {
                             Some(msg) => {
-                                tracing::info!("to client message: {:?}", msg);
+                                tracing::debug!("to client message: {:?}", msg);
+                                last_active.touch();
                                 let event = RaftSchemaEvent::notify_to_sub_session(my_session_id.clone(), &msg);
-                                match raft_client.notify(&event).await {
+                                match event.publish(&raft_client).await {
                                     Ok(_) => {}
                                     Err(e) => {
                                         tracing::error!("send error: {:?}", e);
@@ -413,9 +588,12 @@ impl RaftSession {
                     }
                     msg = recv.recv() => {
                         match msg {
-                            Ok(msg) => {
-                                tracing::info!("to server message: {:?}", msg);
-                                match transport_sink.send(msg).await {
+                            Ok((msg, trace)) => {
+                                tracing::debug!("to server message: {:?}", msg);
+                                last_active.touch();
+                                let span = upstream_span(&my_session_id, &msg);
+                                trace.set_parent_of(&span);
+                                match transport_sink.send(msg).instrument(span).await {
                                     Ok(_) => {}
                                     Err(e) => {
                                         tracing::error!("send error: {:?}", e);
@@ -458,6 +636,7 @@ impl RaftSession {
         let raft_client = self.parent.raft_client.clone();
         let remote_event_recv = self.parent.event_send.subscribe();
         let returning_chan = self.channels.clone();
+        let last_active = self.last_active.clone();
         tokio::spawn(async move {
             let stop_ct = stop_ct;
             let mut remote_event_recv = remote_event_recv;
@@ -467,10 +646,13 @@ impl RaftSession {
             let my_session_id = session_id.clone();
             loop {
                 tokio::select! {
+                    // forward pending events, the expiry notice, before the session is dropped
+                    biased;
                     msg = remote_event_recv.recv() => {
                         match msg {
                             Ok(RaftSchemaEvent::NotifyToSubSession(event)) if event.session_id == my_session_id => {
-                                tracing::info!("to client message: {:?}", event);
+                                tracing::debug!("to client message: {:?}", event);
+                                last_active.touch();
                                 match send.send(event.to_server_json_rpc_message()) {
                                     Ok(_) => {}
                                     Err(e) => {
@@ -485,13 +667,14 @@ impl RaftSession {
                         }
                     }
                     msg = recv.recv() => {
-                        tracing::info!("to main session message: {:?}", msg);
+                        tracing::debug!("to main session message: {:?}", msg);
                         match msg {
-                            Ok(msg) => {
-                                let event = RaftSchemaEvent::notify_to_main_session(my_session_id.clone(), &msg);
-                                match raft_client.notify(&event).await {
+                            Ok((msg, trace)) => {
+                                last_active.touch();
+                                let event = RaftSchemaEvent::notify_to_main_session(my_session_id.clone(), &msg, trace);
+                                match event.publish(&raft_client).await {
                                     Ok(_) => {
-                                        tracing::info!("to main session message: {} {:?}", my_session_id, msg);
+                                        tracing::debug!("to main session message: {} {:?}", my_session_id, msg);
                                     }
                                     Err(e) => {
                                         tracing::error!("send error: {:?}", e);
@@ -527,7 +710,7 @@ impl RaftSessionConnection {
         &mut self,
         ct: &CancellationToken,
     ) -> Option<(
-        broadcast::Receiver<ClientJsonRpcMessage>,
+        broadcast::Receiver<UpstreamMessage>,
         broadcast::Sender<ServerJsonRpcMessage>,
         CancellationToken,
     )> {
@@ -549,7 +732,7 @@ impl RaftSessionConnection {
 
     pub fn restore(
         &mut self,
-        clt_recv: broadcast::Receiver<ClientJsonRpcMessage>,
+        clt_recv: broadcast::Receiver<UpstreamMessage>,
         svr_send: broadcast::Sender<ServerJsonRpcMessage>,
     ) {
         if let RaftSessionConnection::Stopped { .. } = self {
@@ -1,12 +1,20 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+    time::Duration,
+};
 
 use overlay_mcp_core::{
-    server::RaftConfig, BaseModifiers, Config, Error, FatalError, GeneralSession,
-    GeneralSessionManager,
+    raise_token_expiry, record_active_sessions, record_raft_event, server::RaftConfig, unix_now,
+    unix_now_millis, upstream::CommandUpstream, Audit, BaseModifiers, Error, FatalError,
+    GeneralSession, GeneralSessionManager, IdentityConfig, Principal, RegisteredClient,
+    SessionInfo, SessionLimits, UpstreamEndpoint,
+};
+use tokio::{
+    sync::{broadcast, Mutex, RwLock},
+    time::Instant,
 };
-use tokio::sync::{broadcast, RwLock};
 use tokio_util::sync::CancellationToken;
-use url::Url;
 use uuid::Uuid;
 
 use crate::{RaftSchema, RaftSchemaEvent, RaftSession, RaftSessionData};
@@ -16,12 +24,39 @@ pub struct RaftManager {
     inner: Arc<RaftManagerInner>,
 }
 
+/// How long a session row outlives its deadline, the main node refreshes it meanwhile.
+const EXPIRY_GRACE: Duration = Duration::from_secs(30);
+
+/// How often a node shares its counts and session ids with the cluster.
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the session ids of a node outlive it, unchanged ids are shared again in between.
+const INDEX_TTL: Duration = Duration::from_secs(30);
+
+/// Count of this node, shared with the cluster once changed.
+pub(crate) struct LocalCount {
+    count: u64,
+    expires_at: Instant,
+    synced: bool,
+}
+
 pub(crate) struct RaftManagerInner {
+    pub(crate) node_id: u64,
     pub(crate) raft_client: hiqlite::Client,
     pub(crate) event_send: broadcast::Sender<RaftSchemaEvent>,
     pub(crate) sessions: RwLock<HashMap<String, RaftSession>>,
+    // ids of the other nodes, each shares its own counts
+    pub(crate) peers: Vec<u64>,
+    pub(crate) counters: Mutex<HashMap<String, LocalCount>>,
+    // session ids last shared by this node, and when
+    pub(crate) shared_index: Mutex<Option<(BTreeSet<String>, Instant)>>,
     pub(crate) cancel_token: CancellationToken,
     pub(crate) passthrough: BaseModifiers,
+    pub(crate) identity: IdentityConfig,
+    // command upstream of the local config, with its env
+    pub(crate) command: Option<CommandUpstream>,
+    pub(crate) limits: SessionLimits,
+    pub(crate) audit: Audit,
 }
 
 impl RaftManager {
@@ -29,6 +64,10 @@ impl RaftManager {
         cancel_token: CancellationToken,
         config: &RaftConfig,
         passthrough: BaseModifiers,
+        identity: IdentityConfig,
+        command: Option<CommandUpstream>,
+        limits: SessionLimits,
+        audit: Audit,
     ) -> Result<Self, Error> {
         let node_id = match (&config.id, &config.index) {
             (Some(id), None) => *id,
@@ -52,11 +91,18 @@ impl RaftManager {
                 .ok_or(Error::Fatal(FatalError::RaftUnresolvedNodeId(
                     "node not found",
                 )))?;
+        let peers = config
+            .nodes
+            .iter()
+            .map(|node| node.id)
+            .filter(|id| *id != node_id)
+            .collect();
         let config = hiqlite::NodeConfig {
             node_id,
             raft_config: config.cluster.clone(),
             secret_raft: config.secret.clone(),
             secret_api: config.secret.clone(),
+            data_dir: config.data_dir.clone().into(),
             nodes: config
                 .nodes
                 .iter()
@@ -73,11 +119,19 @@ impl RaftManager {
         let client = hiqlite::start_node_with_cache::<RaftSchema>(config).await?;
         let (send, _) = broadcast::channel(16);
         let inner = Arc::new(RaftManagerInner {
+            node_id,
             raft_client: client,
             event_send: send.clone(),
             sessions: RwLock::new(HashMap::new()),
+            peers,
+            counters: Mutex::new(HashMap::new()),
+            shared_index: Mutex::new(None),
             cancel_token,
             passthrough,
+            identity,
+            command,
+            limits,
+            audit,
         });
         let event_clt = inner.raft_client.clone();
         let event_cancel = inner.cancel_token.clone();
@@ -89,12 +143,18 @@ impl RaftManager {
                         break;
                     }
                     event = event_clt.listen::<RaftSchemaEvent>() => {
+                        if let Ok(event) = &event {
+                            record_raft_event("listen", event.as_str());
+                        }
                         match event {
                             Ok(RaftSchemaEvent::DeleteSession(id)) => {
                                 tracing::debug!("delete session: {:?}", id);
-                                let deleted_session = event_inner.sessions.write().await.remove(&id);
-                                if let Some(session) = deleted_session {
-                                    session.cancel_token.cancel();
+                                event_inner.drop_session(&id).await;
+                            }
+                            Ok(RaftSchemaEvent::TokenExtended(id, expires_at)) => {
+                                let session = event_inner.sessions.read().await.get(&id).cloned();
+                                if let Some(session) = session {
+                                    raise_token_expiry(&session.token_expires_at, expires_at);
                                 }
                             }
                             Ok(event) => {
@@ -114,6 +174,22 @@ impl RaftManager {
                 }
             }
         });
+        let sync_cancel = inner.cancel_token.clone();
+        let sync_inner = inner.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SYNC_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = sync_cancel.cancelled() => {
+                        break;
+                    }
+                    _ = interval.tick() => {
+                        sync_inner.sync_counters().await;
+                        sync_inner.sync_session_index().await;
+                    }
+                }
+            }
+        });
         Ok(Self { inner })
     }
 }
@@ -121,35 +197,39 @@ impl RaftManager {
 impl GeneralSessionManager for RaftManager {
     type Session = RaftSession;
 
-    async fn create(&self, upstream_url: Url) -> Result<Self::Session, Error> {
+    async fn create(
+        &self,
+        upstream: UpstreamEndpoint,
+        principal: Principal,
+    ) -> Result<Self::Session, Error> {
         let session_data = RaftSessionData {
             session_id: Uuid::new_v4().to_string(),
-            upstream_url,
+            upstream,
+            principal,
+            created_at: unix_now(),
+            last_active: unix_now_millis(),
+            token_expires_at: None,
             main_subsession_id: None,
+            main_node_id: None,
         };
 
         let session = RaftSession::new(
             self.inner.clone(),
-            session_data.session_id.clone(),
-            session_data.upstream_url.clone(),
+            &session_data,
             self.inner.cancel_token.child_token(),
         );
 
-        self.inner
-            .raft_client
-            .put(
-                RaftSchema::Session,
-                session_data.session_id.clone(),
-                &session_data,
-                None,
-            )
-            .await?;
+        self.inner.put_session(&session_data).await?;
 
-        self.inner
-            .sessions
-            .write()
-            .await
-            .insert(session.session_id().into_owned(), session.clone());
+        let mut sessions = self.inner.sessions.write().await;
+        sessions.insert(session.session_id().into_owned(), session.clone());
+        record_active_sessions("raft", sessions.len());
+        drop(sessions);
+        self.inner.audit.session_opened(
+            &session_data.session_id,
+            &session_data.principal,
+            &session_data.upstream,
+        );
 
         Ok(session)
     }
@@ -178,13 +258,213 @@ impl GeneralSessionManager for RaftManager {
                 .or_insert_with(|| {
                     RaftSession::new(
                         self.inner.clone(),
-                        session_data.session_id.clone(),
-                        session_data.upstream_url.clone(),
+                        &session_data,
                         self.inner.cancel_token.child_token(),
                     )
                 })
                 .clone();
+            record_active_sessions("raft", lock.len());
             Ok(Some(session))
         }
     }
+
+    async fn list(&self) -> Result<Vec<SessionInfo>, Error> {
+        let mut infos = Vec::new();
+        for session_id in self.inner.session_index().await? {
+            let session_data = self
+                .inner
+                .raft_client
+                .get::<_, _, RaftSessionData>(RaftSchema::Session, session_id.as_str())
+                .await?;
+            // closed or expired, the index of its node catches up
+            let Some(session_data) = session_data else {
+                continue;
+            };
+            infos.push(SessionInfo {
+                session_id: session_data.session_id,
+                principal: session_data.principal,
+                upstream: session_data.upstream.location(),
+                created_at: session_data.created_at,
+                started: session_data.main_subsession_id.is_some(),
+                node: session_data.main_node_id,
+            });
+        }
+        Ok(infos)
+    }
+
+    /// Counts on this node without a raft write, the counts of other nodes lag behind by up to
+    /// [`SYNC_INTERVAL`].
+    async fn count(&self, counter: String, ttl: Duration) -> Result<u64, Error> {
+        let now = Instant::now();
+        let mut total = {
+            let mut counters = self.inner.counters.lock().await;
+            let local = counters.entry(counter.clone()).or_insert(LocalCount {
+                count: 0,
+                expires_at: now + ttl,
+                synced: true,
+            });
+            if local.expires_at <= now {
+                local.count = 0;
+                local.expires_at = now + ttl;
+            }
+            local.count += 1;
+            local.synced = false;
+            local.count
+        };
+        for peer in &self.inner.peers {
+            total += self
+                .inner
+                .raft_client
+                .get::<_, _, u64>(RaftSchema::Counter, node_counter(&counter, *peer))
+                .await?
+                .unwrap_or_default();
+        }
+        Ok(total)
+    }
+
+    async fn register_client(&self, client: RegisteredClient, ttl: Duration) -> Result<(), Error> {
+        let client_id = client.client_id.clone();
+        self.inner
+            .raft_client
+            .put(
+                RaftSchema::Client,
+                client_id,
+                &client,
+                Some(ttl.as_secs().max(1) as i64),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_client(&self, client_id: &str) -> Result<Option<RegisteredClient>, Error> {
+        Ok(self
+            .inner
+            .raft_client
+            .get(RaftSchema::Client, client_id)
+            .await?)
+    }
+}
+
+impl RaftManagerInner {
+    /// Store the session row, it expires on its own once the session is over.
+    pub(crate) async fn put_session(&self, session_data: &RaftSessionData) -> Result<(), Error> {
+        let ttl = self.limits.ttl(
+            session_data.created_at,
+            session_data.last_active,
+            EXPIRY_GRACE,
+        );
+        self.raft_client
+            .put(
+                RaftSchema::Session,
+                session_data.session_id.clone(),
+                session_data,
+                ttl,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Forget the session on this node only.
+    pub(crate) async fn drop_session(&self, session_id: &str) {
+        let mut sessions = self.sessions.write().await;
+        let dropped_session = sessions.remove(session_id);
+        record_active_sessions("raft", sessions.len());
+        drop(sessions);
+        if let Some(session) = dropped_session {
+            session.cancel_token.cancel();
+        }
+    }
+
+    /// Share the counts changed since the last sync and forget expired ones.
+    async fn sync_counters(&self) {
+        let now = Instant::now();
+        let changed = {
+            let mut counters = self.counters.lock().await;
+            counters.retain(|_, local| local.expires_at > now);
+            counters
+                .iter_mut()
+                .filter(|(_, local)| !local.synced)
+                .map(|(counter, local)| {
+                    local.synced = true;
+                    (counter.clone(), local.count, local.expires_at - now)
+                })
+                .collect::<Vec<_>>()
+        };
+        for (counter, count, ttl) in changed {
+            let shared = self
+                .raft_client
+                .put(
+                    RaftSchema::Counter,
+                    node_counter(&counter, self.node_id),
+                    &count,
+                    Some(ttl.as_secs().max(1) as i64),
+                )
+                .await;
+            if let Err(err) = shared {
+                tracing::error!("failed to share counter {}: {:?}", counter, err);
+            }
+        }
+    }
+
+    /// Share the ids of the sessions this node holds once changed, or before they expire.
+    async fn sync_session_index(&self) {
+        let session_ids = self
+            .sessions
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let now = Instant::now();
+        let mut shared_index = self.shared_index.lock().await;
+        if let Some((shared, shared_at)) = &*shared_index {
+            if *shared == session_ids && now < *shared_at + INDEX_TTL / 2 {
+                return;
+            }
+        }
+        let shared = self
+            .raft_client
+            .put(
+                RaftSchema::SessionIndex,
+                node_index(self.node_id),
+                &session_ids,
+                Some(INDEX_TTL.as_secs() as i64),
+            )
+            .await;
+        match shared {
+            Ok(()) => *shared_index = Some((session_ids, now)),
+            Err(err) => tracing::error!("failed to share session index: {:?}", err),
+        }
+    }
+
+    /// Ids of the sessions held by any node, those of other nodes lag behind by up to
+    /// [`SYNC_INTERVAL`].
+    pub(crate) async fn session_index(&self) -> Result<BTreeSet<String>, Error> {
+        let mut session_ids = self
+            .sessions
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        for peer in &self.peers {
+            session_ids.extend(
+                self.raft_client
+                    .get::<_, _, BTreeSet<String>>(RaftSchema::SessionIndex, node_index(*peer))
+                    .await?
+                    .unwrap_or_default(),
+            );
+        }
+        Ok(session_ids)
+    }
+}
+
+/// Row of the session ids held by `node_id`.
+fn node_index(node_id: u64) -> String {
+    format!("sessions@{}", node_id)
+}
+
+/// Row of the count `node_id` has for `counter`.
+fn node_counter(counter: &str, node_id: u64) -> String {
+    format!("{}@{}", counter, node_id)
 }
@@ -0,0 +1,17 @@
+use axum::http;
+use overlay_mcp_core::{upstream::CommandUpstream, Error, GeneralResolver, UpstreamEndpoint};
+
+#[derive(Clone)]
+pub struct CommandResolver(pub(crate) UpstreamEndpoint);
+
+impl CommandResolver {
+    pub fn new(config: &CommandUpstream) -> Self {
+        Self(UpstreamEndpoint::Command(config.clone()))
+    }
+}
+
+impl GeneralResolver for CommandResolver {
+    async fn resolve(&self, _target: &http::request::Parts) -> Result<UpstreamEndpoint, Error> {
+        Ok(self.0.clone())
+    }
+}
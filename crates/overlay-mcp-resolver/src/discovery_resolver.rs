@@ -8,8 +8,8 @@ use std::{
 
 use axum::http;
 use overlay_mcp_core::{
-    upstream::DiscoveryUpstream,
-    Error, Error503, GeneralResolver,
+    upstream::{DiscoveryUpstream, UpstreamTransportType},
+    Error, Error503, GeneralResolver, UpstreamEndpoint,
 };
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
@@ -25,6 +25,7 @@ impl DiscoveryResolver {
             resolver: resolver.clone(),
             token: cancel_token.clone(),
             discovery: config.discovery.clone(),
+            transport: config.transport,
             counter: AtomicUsize::new(0),
             found_urls: vec![],
         }));
@@ -93,17 +94,21 @@ pub struct InnerDiscoveryResolver {
     #[allow(dead_code)]
     token: CancellationToken,
     discovery: Url,
+    transport: UpstreamTransportType,
     counter: AtomicUsize,
     found_urls: Vec<Url>,
 }
 
 impl GeneralResolver for DiscoveryResolver {
-    async fn resolve(&self, _target: &http::request::Parts) -> Result<Url, Error> {
+    async fn resolve(&self, _target: &http::request::Parts) -> Result<UpstreamEndpoint, Error> {
         let resolver = self.0.read().await;
         if resolver.found_urls.is_empty() {
             return Err(Error::ServiceUnavailable(Error503::NoUpstreamMcpServer));
         }
         let index = resolver.counter.fetch_add(1, Ordering::SeqCst) % resolver.found_urls.len();
-        Ok(resolver.found_urls[index].clone())
+        Ok(UpstreamEndpoint::Http {
+            url: resolver.found_urls[index].clone(),
+            transport: resolver.transport,
+        })
     }
 }
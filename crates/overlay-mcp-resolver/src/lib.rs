@@ -1,18 +1,20 @@
+mod command_resolver;
 mod discovery_resolver;
 mod static_resolver;
 
 use axum::http;
+pub use command_resolver::*;
 pub use discovery_resolver::*;
-use overlay_mcp_core::{Config, Error, GeneralResolver, UpstreamConfig};
+use overlay_mcp_core::{Config, Error, GeneralResolver, UpstreamConfig, UpstreamEndpoint};
 pub use static_resolver::*;
 
 use tokio_util::sync::CancellationToken;
-use url::Url;
 
 #[derive(Clone)]
 pub enum Resolver {
     Static(StaticResolver),
     Discovery(DiscoveryResolver),
+    Command(CommandResolver),
 }
 
 impl Resolver {
@@ -24,15 +26,19 @@ impl Resolver {
             UpstreamConfig::Discovery(headless_discovery_upstream) => {
                 DiscoveryResolver::new(ct, headless_discovery_upstream).map(Self::Discovery)
             }
+            UpstreamConfig::Command(command_upstream) => {
+                Ok(Self::Command(CommandResolver::new(command_upstream)))
+            }
         }
     }
 }
 
 impl GeneralResolver for Resolver {
-    async fn resolve(&self, target: &http::request::Parts) -> Result<Url, Error> {
+    async fn resolve(&self, target: &http::request::Parts) -> Result<UpstreamEndpoint, Error> {
         match self {
             Self::Static(resolver) => resolver.resolve(target).await,
             Self::Discovery(resolver) => resolver.resolve(target).await,
+            Self::Command(resolver) => resolver.resolve(target).await,
         }
     }
 }
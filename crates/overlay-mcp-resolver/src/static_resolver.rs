@@ -4,9 +4,10 @@ use std::sync::{
 };
 
 use axum::http;
-use overlay_mcp_core::{upstream::StaticUpstream, Error, Error503, GeneralResolver};
+use overlay_mcp_core::{
+    upstream::StaticUpstream, Error, Error503, GeneralResolver, UpstreamEndpoint,
+};
 use tokio::sync::RwLock;
-use url::Url;
 
 #[derive(Clone)]
 pub struct StaticResolver(pub(crate) Arc<RwLock<InnerStaticResolver>>);
@@ -15,23 +16,30 @@ impl StaticResolver {
     pub fn new(config: &StaticUpstream) -> Self {
         Self(Arc::new(RwLock::new(InnerStaticResolver {
             counter: AtomicUsize::new(0),
-            urls: config.urls.clone(),
+            endpoints: config
+                .urls
+                .iter()
+                .map(|url| UpstreamEndpoint::Http {
+                    url: url.url().clone(),
+                    transport: url.transport().unwrap_or(config.transport),
+                })
+                .collect(),
         })))
     }
 }
 
 pub struct InnerStaticResolver {
     counter: AtomicUsize,
-    urls: Vec<Url>,
+    endpoints: Vec<UpstreamEndpoint>,
 }
 
 impl GeneralResolver for StaticResolver {
-    async fn resolve(&self, _target: &http::request::Parts) -> Result<Url, Error> {
+    async fn resolve(&self, _target: &http::request::Parts) -> Result<UpstreamEndpoint, Error> {
         let resolver = self.0.read().await;
-        if resolver.urls.is_empty() {
+        if resolver.endpoints.is_empty() {
             return Err(Error::ServiceUnavailable(Error503::NoUpstreamMcpServer));
         }
-        let index = resolver.counter.fetch_add(1, Ordering::SeqCst) % resolver.urls.len();
-        Ok(resolver.urls[index].clone())
+        let index = resolver.counter.fetch_add(1, Ordering::SeqCst) % resolver.endpoints.len();
+        Ok(resolver.endpoints[index].clone())
     }
 }
@@ -1,11 +1,13 @@
+use std::time::Duration;
+
 use overlay_mcp_core::{
-    server::ClusterConfig, BypassDownstream, Config, Downstream, Error, GeneralSession,
-    GeneralSessionManager, SessionGuard, StreamGuard, Upstream,
+    server::ClusterConfig, Audit, BypassDownstream, Config, Downstream, Error, GeneralSession,
+    GeneralSessionManager, Principal, RegisteredClient, SessionGuard, SessionInfo, SessionLimits,
+    StreamGuard, Upstream, UpstreamConfig, UpstreamEndpoint,
 };
 use overlay_mcp_raft::{RaftManager, RaftSession};
 use overlay_mcp_standalone::{StandaloneManager, StandaloneSession};
-use tokio_util::sync::CancellationToken;
-use url::Url;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
 
 #[derive(Clone)]
 pub enum SessionManager {
@@ -14,23 +16,41 @@ pub enum SessionManager {
 }
 
 #[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Session {
     Standalone(StandaloneSession),
     Raft(RaftSession),
 }
 
 impl SessionManager {
-    pub async fn new(cancel_token: CancellationToken, config: &Config) -> Result<Self, Error> {
+    /// Sessions opened and closed, and tool calls of their upstreams, are written to `audit`.
+    pub async fn new(
+        cancel_token: CancellationToken,
+        config: &Config,
+        audit: Audit,
+    ) -> Result<Self, Error> {
+        let command = match &config.upstream {
+            UpstreamConfig::Command(command) => Some(command.clone()),
+            _ => None,
+        };
         match &config.server.cluster {
             ClusterConfig::None => Ok(Self::Standalone(StandaloneManager::new(
                 cancel_token,
                 config.application.passthrough.clone(),
+                config.application.identity.clone(),
+                command,
+                SessionLimits::new(&config.server),
+                audit,
             ))),
             ClusterConfig::Raft(raft_config) => {
                 let raft_manager = RaftManager::new(
                     cancel_token,
                     raft_config,
                     config.application.passthrough.clone(),
+                    config.application.identity.clone(),
+                    command,
+                    SessionLimits::new(&config.server),
+                    audit,
                 )
                 .await?;
                 Ok(Self::Raft(raft_manager))
@@ -42,13 +62,20 @@ impl SessionManager {
 impl GeneralSessionManager for SessionManager {
     type Session = Session;
 
-    async fn create(&self, upstream_url: Url) -> Result<Self::Session, Error> {
+    async fn create(
+        &self,
+        upstream: UpstreamEndpoint,
+        principal: Principal,
+    ) -> Result<Self::Session, Error> {
         match self {
             Self::Standalone(standalone_manager) => standalone_manager
-                .create(upstream_url)
+                .create(upstream, principal)
                 .await
                 .map(Session::Standalone),
-            Self::Raft(raft_manager) => raft_manager.create(upstream_url).await.map(Session::Raft),
+            Self::Raft(raft_manager) => raft_manager
+                .create(upstream, principal)
+                .await
+                .map(Session::Raft),
         }
     }
 
@@ -64,20 +91,55 @@ impl GeneralSessionManager for SessionManager {
                 .map(|session| session.map(Session::Raft)),
         }
     }
+
+    async fn list(&self) -> Result<Vec<SessionInfo>, Error> {
+        match self {
+            Self::Standalone(standalone_manager) => standalone_manager.list().await,
+            Self::Raft(raft_manager) => raft_manager.list().await,
+        }
+    }
+
+    async fn count(&self, counter: String, ttl: Duration) -> Result<u64, Error> {
+        match self {
+            Self::Standalone(standalone_manager) => standalone_manager.count(counter, ttl).await,
+            Self::Raft(raft_manager) => raft_manager.count(counter, ttl).await,
+        }
+    }
+
+    async fn register_client(&self, client: RegisteredClient, ttl: Duration) -> Result<(), Error> {
+        match self {
+            Self::Standalone(manager) => manager.register_client(client, ttl).await,
+            Self::Raft(manager) => manager.register_client(client, ttl).await,
+        }
+    }
+
+    async fn get_client(&self, client_id: &str) -> Result<Option<RegisteredClient>, Error> {
+        match self {
+            Self::Standalone(manager) => manager.get_client(client_id).await,
+            Self::Raft(manager) => manager.get_client(client_id).await,
+        }
+    }
 }
 
 impl GeneralSession for Session {
-    fn session_id(&self) -> std::borrow::Cow<str> {
+    fn session_id(&self) -> std::borrow::Cow<'_, str> {
         match self {
             Self::Standalone(session) => session.session_id(),
             Self::Raft(session) => session.session_id(),
         }
     }
 
-    fn upstream_url(&self) -> std::borrow::Cow<Url> {
+    fn upstream(&self) -> std::borrow::Cow<'_, UpstreamEndpoint> {
         match self {
-            Self::Standalone(session) => session.upstream_url(),
-            Self::Raft(session) => session.upstream_url(),
+            Self::Standalone(session) => session.upstream(),
+            Self::Raft(session) => session.upstream(),
+        }
+    }
+
+    fn principal(&self) -> std::borrow::Cow<'_, Principal> {
+        match self {
+            Self::Standalone(session) => session.principal(),
+            Self::Raft(session) => session.principal(),
         }
     }
 
@@ -136,4 +198,32 @@ impl GeneralSession for Session {
             Self::Raft(session) => session.close().await,
         }
     }
+
+    fn closed(&self) -> WaitForCancellationFutureOwned {
+        match self {
+            Self::Standalone(session) => session.closed(),
+            Self::Raft(session) => session.closed(),
+        }
+    }
+
+    async fn token_expiry(&self) -> Result<Option<u64>, Error> {
+        match self {
+            Self::Standalone(session) => session.token_expiry().await,
+            Self::Raft(session) => session.token_expiry().await,
+        }
+    }
+
+    async fn extend_token_expiry(&self, expires_at: u64) -> Result<(), Error> {
+        match self {
+            Self::Standalone(session) => session.extend_token_expiry(expires_at).await,
+            Self::Raft(session) => session.extend_token_expiry(expires_at).await,
+        }
+    }
+
+    fn watch_token_expiry(&self) -> tokio::sync::watch::Receiver<u64> {
+        match self {
+            Self::Standalone(session) => session.watch_token_expiry(),
+            Self::Raft(session) => session.watch_token_expiry(),
+        }
+    }
 }
@@ -2,37 +2,34 @@ use std::{borrow::Cow, sync::Arc, time::Duration};
 
 use futures::{SinkExt, StreamExt};
 use overlay_mcp_core::{
-    BypassDownstream, Downstream, Error, FatalError, GeneralSession, SessionGuard, StreamGuard,
-    Upstream,
-};
-use rmcp::{
-    model::{ClientJsonRpcMessage, ServerJsonRpcMessage},
-    transport::{IntoTransport, SseTransport},
-    RoleClient,
+    passthrough, raise_token_expiry, record_active_sessions, sleep_until_millis, unix_now,
+    unix_now_millis, upstream_span, Activity, BypassDownstream, Downstream, Error, FatalError,
+    GeneralSession, Principal, SessionGuard, SessionLimits, StreamGuard, Upstream,
+    UpstreamConnection, UpstreamEndpoint, UpstreamMessage,
 };
+use rmcp::model::ServerJsonRpcMessage;
 use tokio::{
     sync::{
         broadcast::{self, error::RecvError},
-        Mutex, Notify,
+        watch, Mutex, Notify,
     },
     time::timeout,
 };
 
-use tokio_util::sync::CancellationToken;
-use url::Url;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tracing::Instrument;
 
 use crate::StandaloneManagerInner;
 
 #[derive(Clone)]
 pub struct StandaloneSession {
-    #[allow(dead_code)]
     pub(crate) parent: Arc<StandaloneManagerInner>,
     pub(crate) inner: Arc<StandaloneSessionInner>,
 }
 
 enum StandaloneSessionConnection {
     Stopped {
-        clt_recv: broadcast::Receiver<ClientJsonRpcMessage>,
+        clt_recv: broadcast::Receiver<UpstreamMessage>,
         svr_send: broadcast::Sender<ServerJsonRpcMessage>,
     },
     Started(CancellationToken),
@@ -40,7 +37,14 @@ enum StandaloneSessionConnection {
 
 pub struct StandaloneSessionInner {
     pub(crate) session_id: String,
-    pub(crate) upstream_url: Url,
+    pub(crate) upstream_endpoint: UpstreamEndpoint,
+    pub(crate) principal: Principal,
+    pub(crate) created_at: u64,
+    pub(crate) last_active: Activity,
+    // unix seconds, 0 until a jwt is seen
+    token_expires_at: watch::Sender<u64>,
+    // tells the downstream why the session expired
+    expiry_send: broadcast::Sender<ServerJsonRpcMessage>,
 
     pub(crate) upstream: Mutex<Option<Upstream>>,
     pub(crate) downstream: Mutex<Option<Downstream>>,
@@ -53,12 +57,16 @@ pub struct StandaloneSessionInner {
 }
 
 impl GeneralSession for StandaloneSession {
-    fn session_id(&self) -> Cow<str> {
+    fn session_id(&self) -> Cow<'_, str> {
         Cow::Borrowed(&self.inner.session_id)
     }
 
-    fn upstream_url(&self) -> Cow<Url> {
-        Cow::Borrowed(&self.inner.upstream_url)
+    fn upstream(&self) -> Cow<'_, UpstreamEndpoint> {
+        Cow::Borrowed(&self.inner.upstream_endpoint)
+    }
+
+    fn principal(&self) -> Cow<'_, Principal> {
+        Cow::Borrowed(&self.inner.principal)
     }
 
     async fn guard_upstream(&self) -> Result<StreamGuard<Upstream>, Error> {
@@ -190,14 +198,23 @@ impl GeneralSession for StandaloneSession {
             .take(&self.inner.cancel_token)
             .ok_or(Error::AlreadyStartedSession(self.inner.session_id.clone()))?;
 
-        let transport = SseTransport::start_with_client(
-            self.inner.upstream_url.clone(),
-            reqwest::Client::new(),
-        )
-        .await?;
-        let (transport_sink, transport_stream) =
-            IntoTransport::<RoleClient, _, _>::into_transport(transport);
+        let (endpoint, client) = passthrough(
+            &self.inner.upstream_endpoint,
+            &self.parent.passthrough,
+            &self.parent.identity,
+            self.parent.command.as_ref(),
+            original_request,
+        )?;
+        let audit = self
+            .parent
+            .audit
+            .scope(&self.inner.session_id, &self.inner.principal);
+        let transport =
+            UpstreamConnection::connect(&endpoint, client, audit, stop_ct.clone()).await?;
+        let (transport_sink, transport_stream) = transport.split();
         let returning_chan = self.inner.connection.clone();
+        let session_id = self.inner.session_id.clone();
+        let inner = self.inner.clone();
         tokio::spawn(async move {
             let stop_ct = stop_ct;
             let transport_sink = transport_sink;
@@ -211,7 +228,8 @@ impl GeneralSession for StandaloneSession {
                     msg = transport_stream.next() => {
                         match msg {
                             Some(msg) => {
-                                tracing::info!("to client message: {:?}", msg);
+                                tracing::debug!("to client message: {:?}", msg);
+                                inner.last_active.touch();
                                 match send.send(msg) {
                                     Ok(_) => {}
                                     Err(e) => {
@@ -227,9 +245,12 @@ impl GeneralSession for StandaloneSession {
                     }
                     msg = recv.recv() => {
                         match msg {
-                            Ok(msg) => {
-                                tracing::info!("to server message: {:?}", msg);
-                                match transport_sink.send(msg).await {
+                            Ok((msg, trace)) => {
+                                tracing::debug!("to server message: {:?}", msg);
+                                inner.last_active.touch();
+                                let span = upstream_span(&session_id, &msg);
+                                trace.set_parent_of(&span);
+                                match transport_sink.send(msg).instrument(span).await {
                                     Ok(_) => {}
                                     Err(e) => {
                                         tracing::error!("send error: {:?}", e);
@@ -298,16 +319,35 @@ impl GeneralSession for StandaloneSession {
         // remove session from parent handled by cancel_token
         Ok(())
     }
+
+    fn closed(&self) -> WaitForCancellationFutureOwned {
+        self.inner.cancel_token.clone().cancelled_owned()
+    }
+
+    async fn token_expiry(&self) -> Result<Option<u64>, Error> {
+        let expires_at = *self.inner.token_expires_at.borrow();
+        Ok((expires_at != 0).then_some(expires_at))
+    }
+
+    async fn extend_token_expiry(&self, expires_at: u64) -> Result<(), Error> {
+        raise_token_expiry(&self.inner.token_expires_at, expires_at);
+        Ok(())
+    }
+
+    fn watch_token_expiry(&self) -> watch::Receiver<u64> {
+        self.inner.token_expires_at.subscribe()
+    }
 }
 
 impl StandaloneSession {
     pub(crate) fn new(
         parent: Arc<StandaloneManagerInner>,
         session_id: String,
-        upstream_url: Url,
+        upstream_endpoint: UpstreamEndpoint,
+        principal: Principal,
         cancel_token: CancellationToken,
     ) -> Self {
-        let (clt_send, clt_recv) = broadcast::channel::<ClientJsonRpcMessage>(16);
+        let (clt_send, clt_recv) = broadcast::channel::<UpstreamMessage>(16);
         let (svr_send, svr_recv) = broadcast::channel::<ServerJsonRpcMessage>(16);
 
         let upstream = Upstream(clt_send);
@@ -318,16 +358,29 @@ impl StandaloneSession {
         let stop_parent = parent.clone();
         let stop_ct = cancel_token.clone();
         let stop_session_id = session_id.clone();
+        let stop_principal = principal.clone();
         tokio::spawn(async move {
             stop_ct.cancelled().await;
-            stop_parent.sessions.write().await.remove(&stop_session_id);
+            let mut sessions = stop_parent.sessions.write().await;
+            sessions.remove(&stop_session_id);
+            record_active_sessions("standalone", sessions.len());
+            drop(sessions);
+            stop_parent
+                .audit
+                .session_closed(&stop_session_id, &stop_principal);
         });
 
-        StandaloneSession {
+        let limits = parent.limits;
+        let session = StandaloneSession {
             parent,
             inner: Arc::new(StandaloneSessionInner {
                 session_id,
-                upstream_url,
+                upstream_endpoint,
+                principal,
+                created_at: unix_now(),
+                last_active: Activity::default(),
+                token_expires_at: watch::Sender::new(0),
+                expiry_send: svr_send.clone(),
                 upstream: Mutex::new(Some(upstream)),
                 downstream: Mutex::new(Some(downstream)),
                 bypass_downstream: Mutex::new(Some(bypass_downstream)),
@@ -338,7 +391,32 @@ impl StandaloneSession {
                 stream_notify: Notify::new(),
                 cancel_token,
             }),
+        };
+        if !limits.is_unlimited() {
+            tokio::spawn(expire(session.inner.clone(), limits));
         }
+        session
+    }
+}
+
+// close the session once idle or too old, the downstream is told why first
+async fn expire(inner: Arc<StandaloneSessionInner>, limits: SessionLimits) {
+    while let Some((deadline, expiry)) = limits.deadline(inner.created_at, inner.last_active.get())
+    {
+        if deadline > unix_now_millis() {
+            tokio::select! {
+                _ = sleep_until_millis(deadline) => continue,
+                _ = inner.cancel_token.cancelled() => return,
+            }
+        }
+        tracing::info!(
+            session_id = inner.session_id,
+            reason = expiry.as_str(),
+            "session expired"
+        );
+        let _ = inner.expiry_send.send(expiry.notification());
+        inner.cancel_token.cancel();
+        return;
     }
 }
 impl StandaloneSessionConnection {
@@ -350,7 +428,7 @@ impl StandaloneSessionConnection {
         &mut self,
         ct: &CancellationToken,
     ) -> Option<(
-        broadcast::Receiver<ClientJsonRpcMessage>,
+        broadcast::Receiver<UpstreamMessage>,
         broadcast::Sender<ServerJsonRpcMessage>,
         CancellationToken,
     )> {
@@ -372,7 +450,7 @@ impl StandaloneSessionConnection {
 
     pub fn restore(
         &mut self,
-        clt_recv: broadcast::Receiver<ClientJsonRpcMessage>,
+        clt_recv: broadcast::Receiver<UpstreamMessage>,
         svr_send: broadcast::Sender<ServerJsonRpcMessage>,
     ) {
         if let StandaloneSessionConnection::Stopped { .. } = self {
@@ -1,13 +1,22 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use overlay_mcp_core::{BaseModifiers, Error, GeneralSessionManager};
-use tokio::sync::RwLock;
+use overlay_mcp_core::{
+    record_active_sessions, upstream::CommandUpstream, Audit, BaseModifiers, Error, GeneralSession,
+    GeneralSessionManager, IdentityConfig, Principal, RegisteredClient, SessionInfo, SessionLimits,
+    UpstreamEndpoint,
+};
+use tokio::{
+    sync::{Mutex, RwLock},
+    time::Instant,
+};
 use tokio_util::sync::CancellationToken;
-use url::Url;
 use uuid::Uuid;
 
 use crate::StandaloneSession;
 
+/// Expired counters are dropped once there are this many.
+const COUNTERS_PRUNE_LEN: usize = 4096;
+
 #[derive(Clone)]
 pub struct StandaloneManager {
     inner: Arc<StandaloneManagerInner>,
@@ -15,16 +24,38 @@ pub struct StandaloneManager {
 
 pub(crate) struct StandaloneManagerInner {
     pub(crate) passthrough: BaseModifiers,
+    pub(crate) identity: IdentityConfig,
+    // command upstream of the local config, with its env
+    pub(crate) command: Option<CommandUpstream>,
+    pub(crate) limits: SessionLimits,
+    pub(crate) audit: Audit,
     pub(crate) sessions: RwLock<HashMap<String, StandaloneSession>>,
+    // value and expiry of every counter
+    pub(crate) counters: Mutex<HashMap<String, (u64, Instant)>>,
+    // registered oauth clients and their expiry
+    pub(crate) clients: Mutex<HashMap<String, (RegisteredClient, Instant)>>,
     pub(crate) cancel_token: CancellationToken,
 }
 
 impl StandaloneManager {
-    pub fn new(cancel_token: CancellationToken, passthrough: BaseModifiers) -> Self {
+    pub fn new(
+        cancel_token: CancellationToken,
+        passthrough: BaseModifiers,
+        identity: IdentityConfig,
+        command: Option<CommandUpstream>,
+        limits: SessionLimits,
+        audit: Audit,
+    ) -> Self {
         Self {
             inner: Arc::new(StandaloneManagerInner {
                 passthrough,
+                identity,
+                command,
+                limits,
+                audit,
                 sessions: RwLock::new(HashMap::new()),
+                counters: Mutex::new(HashMap::new()),
+                clients: Mutex::new(HashMap::new()),
                 cancel_token,
             }),
         }
@@ -34,21 +65,30 @@ impl StandaloneManager {
 impl GeneralSessionManager for StandaloneManager {
     type Session = StandaloneSession;
 
-    async fn create(&self, upstream_url: Url) -> Result<Self::Session, Error> {
+    async fn create(
+        &self,
+        upstream: UpstreamEndpoint,
+        principal: Principal,
+    ) -> Result<Self::Session, Error> {
         let session_id = Uuid::new_v4().to_string();
         let close_token = self.inner.cancel_token.child_token();
         let session = StandaloneSession::new(
             self.inner.clone(),
             session_id.clone(),
-            upstream_url,
+            upstream,
+            principal,
             close_token,
         );
 
-        self.inner
-            .sessions
-            .write()
-            .await
-            .insert(session_id, session.clone());
+        let mut sessions = self.inner.sessions.write().await;
+        sessions.insert(session_id, session.clone());
+        record_active_sessions("standalone", sessions.len());
+        drop(sessions);
+        self.inner.audit.session_opened(
+            &session.inner.session_id,
+            &session.inner.principal,
+            &session.inner.upstream_endpoint,
+        );
 
         Ok(session)
     }
@@ -64,4 +104,58 @@ impl GeneralSessionManager for StandaloneManager {
             Ok(None)
         }
     }
+
+    async fn list(&self) -> Result<Vec<SessionInfo>, Error> {
+        let sessions = self
+            .inner
+            .sessions
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut infos = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            infos.push(SessionInfo {
+                session_id: session.inner.session_id.clone(),
+                principal: session.inner.principal.clone(),
+                upstream: session.inner.upstream_endpoint.location(),
+                created_at: session.inner.created_at,
+                started: session.is_started().await,
+                node: None,
+            });
+        }
+        Ok(infos)
+    }
+
+    async fn count(&self, counter: String, ttl: Duration) -> Result<u64, Error> {
+        let now = Instant::now();
+        let mut counters = self.inner.counters.lock().await;
+        if counters.len() >= COUNTERS_PRUNE_LEN {
+            counters.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        let (count, expires_at) = counters.entry(counter).or_insert((0, now + ttl));
+        if *expires_at <= now {
+            *count = 0;
+            *expires_at = now + ttl;
+        }
+        *count += 1;
+        Ok(*count)
+    }
+
+    async fn register_client(&self, client: RegisteredClient, ttl: Duration) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut clients = self.inner.clients.lock().await;
+        clients.retain(|_, (_, expires_at)| *expires_at > now);
+        clients.insert(client.client_id.clone(), (client, now + ttl));
+        Ok(())
+    }
+
+    async fn get_client(&self, client_id: &str) -> Result<Option<RegisteredClient>, Error> {
+        let clients = self.inner.clients.lock().await;
+        Ok(clients
+            .get(client_id)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(client, _)| client.clone()))
+    }
 }
@@ -1,3 +1,4 @@
 pub mod middlewares;
 pub mod router;
+pub mod telemetry;
 pub mod utils;
\ No newline at end of file
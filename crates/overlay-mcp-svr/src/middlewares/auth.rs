@@ -1,12 +1,11 @@
-
 use axum::{
     body::Body,
-    extract::{FromRequestParts, Request},
-    response::Response,
+    extract::{FromRef, FromRequestParts, Request},
+    response::{IntoResponse, Response},
 };
 use http::{request::Parts, StatusCode};
 use overlay_mcp_auth::{Authn, Authz};
-use overlay_mcp_core::{Authentication, GeneralAuthn};
+use overlay_mcp_core::{Authentication, Config, Error, Error400, Error401, Error403, GeneralAuthn};
 use tower::{Layer, Service};
 
 pub struct HttpAuthentication(pub Authentication);
@@ -25,13 +24,54 @@ where
                 .body(Body::empty())
                 .unwrap()
         })?;
-        let authn = authn.authenticate(parts).await.map_err(|err| {
-            tracing::error!(error = ?err, "http authentication failed");
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap()
+        let authn = authn.authenticate(parts).await.map_err(|err| match err {
+            // rejected bearer tokens are challenged again (RFC 6750 3.1)
+            Error::BadRequest(Error400::InvalidToken(reason)) => {
+                tracing::info!(reason, "bearer token rejected");
+                Error::Unauthorized(Error401::InvalidToken(reason)).into_response()
+            }
+            err => {
+                tracing::warn!(error = ?err, "http authentication failed");
+                err.into_response()
+            }
         })?;
+        // sessions started by this request inject the caller identity into upstream requests
+        parts.extensions.insert(authn.clone());
+        Ok(Self(authn))
+    }
+}
+
+/// Caller of the session admin api, authenticated like any client and listed in
+/// `application.admin`.
+pub struct AdminAuthentication(pub Authentication);
+
+impl<S> FromRequestParts<S> for AdminAuthentication
+where
+    S: Send + Sync,
+    Config: FromRef<S>,
+{
+    type Rejection = Response<Body>;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let HttpAuthentication(authn) =
+            HttpAuthentication::from_request_parts(parts, state).await?;
+        let config = Config::from_ref(state);
+        let Some(admin) = &config.application.admin else {
+            return Err(Error::Forbidden(Error403::AdminRequired).into_response());
+        };
+        let is_admin = match &authn {
+            Authentication::ApiKey { apikey, key, .. } => {
+                admin.is_admin_apikey(apikey, key.as_ref().map(|key| key.id.as_str()))
+            }
+            Authentication::Jwt { jwt } => admin.is_admin_claims(&jwt.claims),
+            Authentication::NoAuth => {
+                return Err(Error::Unauthorized(Error401::AuthenticationFailed).into_response());
+            }
+        };
+        if !is_admin {
+            tracing::warn!(principal = ?authn.principal(), "admin api denied");
+            return Err(Error::Forbidden(Error403::AdminRequired).into_response());
+        }
         Ok(Self(authn))
     }
 }
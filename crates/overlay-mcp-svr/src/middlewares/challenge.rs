@@ -0,0 +1,115 @@
+use axum::{extract::Request, response::Response};
+use futures::future::BoxFuture;
+use http::{header, HeaderValue, StatusCode};
+use overlay_mcp_auth::Authn;
+use overlay_mcp_core::{Config, GeneralAuthn};
+use tower::{Layer, Service};
+
+/// Adds the `WWW-Authenticate: Bearer` challenge (RFC 6750 3) pointing to the protected
+/// resource metadata (RFC 9728 5.1) to 401 and 403 responses of protected resources.
+#[derive(Clone)]
+pub struct BearerChallengeLayer {
+    // hostname without trailing slash
+    base: String,
+    scope: String,
+}
+
+#[derive(Clone)]
+pub struct BearerChallengeMiddleware<S> {
+    inner: S,
+    base: String,
+    scope: String,
+}
+
+impl BearerChallengeLayer {
+    pub fn new(config: &Config, authn: &Authn) -> Self {
+        let mut hostname = config.server.hostname.clone();
+        hostname.set_path("/");
+        hostname.set_query(None);
+        hostname.set_fragment(None);
+        let scope = authn
+            .scopes()
+            .into_iter()
+            .map(|scope| scope.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Self {
+            base: hostname.as_str().trim_end_matches('/').to_string(),
+            scope,
+        }
+    }
+}
+
+impl<S> Layer<S> for BearerChallengeLayer {
+    type Service = BearerChallengeMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BearerChallengeMiddleware {
+            inner,
+            base: self.base.clone(),
+            scope: self.scope.clone(),
+        }
+    }
+}
+
+impl<S> Service<Request> for BearerChallengeMiddleware<S>
+where
+    S: Service<Request, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let bearer = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_whitespace().next())
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("bearer"));
+        let resource_metadata = format!(
+            "{}/.well-known/oauth-protected-resource{}",
+            self.base,
+            req.uri().path()
+        );
+        let scope = self.scope.clone();
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = future.await?;
+            if response.headers().contains_key(header::WWW_AUTHENTICATE) {
+                return Ok(response);
+            }
+            let mut challenge = format!("Bearer resource_metadata=\"{}\"", resource_metadata);
+            match (response.status(), bearer) {
+                // requests without a token get no error code (RFC 6750 3.1)
+                (StatusCode::UNAUTHORIZED, false) => {}
+                (StatusCode::UNAUTHORIZED, true) => challenge.push_str(
+                    ", error=\"invalid_token\", error_description=\"The access token is invalid or expired\"",
+                ),
+                (StatusCode::FORBIDDEN, true) => {
+                    challenge.push_str(
+                        ", error=\"insufficient_scope\", error_description=\"The access token does not grant access to this resource\"",
+                    );
+                    if !scope.is_empty() {
+                        challenge.push_str(&format!(", scope=\"{}\"", scope));
+                    }
+                }
+                _ => return Ok(response),
+            }
+            if let Ok(challenge) = HeaderValue::from_str(&challenge) {
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, challenge);
+            }
+            Ok(response)
+        })
+    }
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+}
@@ -1,10 +1,16 @@
 mod auth;
+mod challenge;
+mod problem;
+mod ratelimit;
 mod reqwest;
 mod resolver;
 mod session;
 mod trace;
 
 pub use auth::*;
+pub use challenge::*;
+pub use problem::*;
+pub use ratelimit::*;
 pub use reqwest::*;
 pub use resolver::*;
 pub use session::*;
@@ -0,0 +1,61 @@
+use axum::{extract::Request, response::Response};
+use futures::future::BoxFuture;
+use http::header;
+use overlay_mcp_core::Problem;
+use tower::{Layer, Service};
+use tower_http::request_id::RequestId;
+
+/// Completes the problem details of error responses with the id of the request, set by
+/// `SetRequestIdLayer` in front of it.
+#[derive(Clone, Default)]
+pub struct ProblemLayer;
+
+#[derive(Clone)]
+pub struct ProblemMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Layer<S> for ProblemLayer {
+    type Service = ProblemMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProblemMiddleware { inner }
+    }
+}
+
+impl<S> Service<Request> for ProblemMiddleware<S>
+where
+    S: Service<Request, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let request_id = req
+            .extensions()
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .map(str::to_string);
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = future.await?;
+            if let (Some(mut problem), Some(request_id)) =
+                (response.extensions_mut().remove::<Problem>(), request_id)
+            {
+                problem.request_id = Some(request_id);
+                response.headers_mut().remove(header::CONTENT_LENGTH);
+                *response.body_mut() = problem.body();
+            }
+            Ok(response)
+        })
+    }
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+}
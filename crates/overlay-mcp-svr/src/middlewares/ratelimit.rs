@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{FromRef, FromRequestParts},
+    response::{IntoResponse, Response},
+};
+use axum_client_ip::ClientIp;
+use http::{request::Parts, StatusCode};
+use overlay_mcp_core::{
+    known_tool, ratelimit::RateLimit, unix_now, Authentication, Config, Error, Error429,
+    GeneralSessionManager, RateLimitConfig,
+};
+use overlay_mcp_session_manager::SessionManager;
+use rmcp::model::{CallToolRequest, ClientJsonRpcMessage, ClientRequest, JsonRpcMessage};
+
+use super::HttpAuthentication;
+
+/// Rate limits of the caller, see `application.rate_limit`.
+pub struct HttpRateLimit {
+    // limits and the key of the caller, none if not limited
+    limits: Option<(RateLimitConfig, String)>,
+    session_manager: SessionManager,
+    authn: Authentication,
+}
+
+/// Bucket a client message exceeded.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimited {
+    pub bucket: &'static str,
+    pub retry_after: u64,
+}
+
+impl<S> FromRequestParts<S> for HttpRateLimit
+where
+    S: Send + Sync,
+    Config: FromRef<S>,
+{
+    type Rejection = Response<Body>;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session_manager = parts
+            .extensions
+            .get::<SessionManager>()
+            .cloned()
+            .ok_or_else(|| {
+                tracing::error!("session manager middleware not found");
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::empty())
+                    .unwrap()
+            })?;
+        // authenticated once, by the extractor running first
+        let authn = match parts.extensions.get::<Authentication>() {
+            Some(authn) => authn.clone(),
+            None => {
+                HttpAuthentication::from_request_parts(parts, state)
+                    .await?
+                    .0
+            }
+        };
+        let config = Config::from_ref(state);
+        let limits = match config.application.rate_limit {
+            Some(limits) => {
+                let ip = ClientIp::from_request_parts(parts, state)
+                    .await
+                    .ok()
+                    .map(|ClientIp(ip)| ip);
+                limits.key(&authn, ip).map(|key| (limits, key))
+            }
+            None => None,
+        };
+        Ok(Self {
+            limits,
+            session_manager,
+            authn,
+        })
+    }
+}
+
+impl HttpRateLimit {
+    /// Count a new session, fails with 429 over the limit.
+    pub async fn check_session(&self) -> Result<(), Error> {
+        let Some((limits, key)) = &self.limits else {
+            return Ok(());
+        };
+        if let Some(limited) = self.hit("sessions", key, limits.sessions).await? {
+            return Err(limited.into());
+        }
+        if let Some(max_sessions) = limits.max_sessions {
+            let principal = self.authn.principal();
+            let open = self
+                .session_manager
+                .list()
+                .await?
+                .iter()
+                .filter(|session| session.principal == principal)
+                .count();
+            if open >= max_sessions {
+                tracing::warn!(key, open, "too many open sessions");
+                return Err(Error::TooManyRequests(Error429::TooManySessions));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a client message, `tools/call` requests also count for their tool.
+    pub async fn check_message(
+        &self,
+        message: &ClientJsonRpcMessage,
+    ) -> Result<Option<RateLimited>, Error> {
+        let Some((limits, key)) = &self.limits else {
+            return Ok(None);
+        };
+        if let Some(limited) = self.hit("messages", key, limits.messages).await? {
+            return Ok(Some(limited));
+        }
+        if let JsonRpcMessage::Request(request) = message {
+            if let ClientRequest::CallToolRequest(CallToolRequest { params, .. }) = &request.request
+            {
+                // clients choose tool names, only configured or listed tools get their own counter
+                let tool = if limits.tool.contains_key(params.name.as_ref()) {
+                    params.name.as_ref()
+                } else {
+                    known_tool(&params.name)
+                };
+                let key = format!("{}:{}", key, tool);
+                return self.hit("tools", &key, limits.tool(&params.name)).await;
+            }
+        }
+        Ok(None)
+    }
+
+    async fn hit(
+        &self,
+        bucket: &'static str,
+        key: &str,
+        limit: Option<RateLimit>,
+    ) -> Result<Option<RateLimited>, Error> {
+        let Some(limit) = limit else {
+            return Ok(None);
+        };
+        let window = limit.window.max(1);
+        let now = unix_now();
+        let index = now / window;
+        let count = self
+            .session_manager
+            .count(
+                format!("ratelimit:{}:{}:{}", bucket, key, index),
+                Duration::from_secs(window),
+            )
+            .await?;
+        if count <= limit.limit {
+            return Ok(None);
+        }
+        tracing::warn!(bucket, key, count, "rate limit exceeded");
+        Ok(Some(RateLimited {
+            bucket,
+            retry_after: (index + 1) * window - now,
+        }))
+    }
+}
+
+impl From<RateLimited> for Error {
+    fn from(limited: RateLimited) -> Self {
+        Error::TooManyRequests(Error429::RateLimited {
+            bucket: limited.bucket,
+            retry_after: limited.retry_after,
+        })
+    }
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        Error::from(self).into_response()
+    }
+}
@@ -1,10 +1,10 @@
 use axum::{
     body::Body,
     extract::{FromRequestParts, OptionalFromRequestParts, Request},
-    response::Response,
+    response::{IntoResponse, Response},
 };
-use http::{request::Parts, StatusCode};
-use overlay_mcp_core::{MCP};
+use http::request::Parts;
+use overlay_mcp_core::{Error, Error400, MCP};
 use overlay_mcp_session_manager::SessionManager;
 
 use std::{fmt::Display, marker::PhantomData};
@@ -36,10 +36,7 @@ where
 
     async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
         let Some(session_id) = Spec::pick_session_id(parts) else {
-            return Err(Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Body::from("no session id"))
-                .unwrap());
+            return Err(Error::BadRequest(Error400::SessionIdRequired).into_response());
         };
         Ok(Self {
             session_id: session_id.to_string(),
@@ -1,17 +1,19 @@
+use http::Request;
+use overlay_mcp_core::set_parent_from_headers;
 use tower_http::{
     classify::{ServerErrorsAsFailures, SharedClassifier},
-    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    trace::{DefaultMakeSpan, DefaultOnResponse, MakeSpan, TraceLayer},
     LatencyUnit,
 };
-use tracing::Level;
+use tracing::{Level, Span};
 
-pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>> {
+pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, TraceParentMakeSpan> {
     TraceLayer::new_for_http()
-        .make_span_with(
+        .make_span_with(TraceParentMakeSpan(
             DefaultMakeSpan::new()
                 .level(Level::INFO)
                 .include_headers(true),
-        )
+        ))
         .on_response(
             DefaultOnResponse::new()
                 .level(Level::INFO)
@@ -19,3 +21,15 @@ pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>> {
                 .include_headers(true),
         )
 }
+
+/// Request span continuing the trace of an incoming `traceparent` header.
+#[derive(Clone, Debug)]
+pub struct TraceParentMakeSpan(DefaultMakeSpan);
+
+impl<B> MakeSpan<B> for TraceParentMakeSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let span = self.0.make_span(request);
+        set_parent_from_headers(&span, request.headers());
+        span
+    }
+}
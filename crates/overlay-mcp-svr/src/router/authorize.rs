@@ -1,44 +1,165 @@
 use axum::{
-    extract::Query,
+    extract::{Query, RawQuery},
     response::{IntoResponse, Redirect},
     Extension,
 };
-use oauth2::{CsrfToken, PkceCodeChallenge, RedirectUrl};
+use oauth2::{CsrfToken, PkceCodeChallenge, RedirectUrl, Scope};
 use overlay_mcp_auth::Authn;
-use overlay_mcp_core::{Error, Error400, GeneralAuthn};
+use overlay_mcp_core::{Error, Error400, GeneralAuthn, GeneralSessionManager};
+use overlay_mcp_session_manager::SessionManager;
 use serde::Deserialize;
+use url::Url;
 
-#[allow(dead_code)]
+// `resource` may be repeated (RFC 8707), it is read from the raw query
 #[derive(Debug, Deserialize)]
 pub struct Params {
     response_type: String,
     client_id: String,
-    code_challenge: String,
-    code_challenge_method: String,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
     redirect_uri: String,
+    state: Option<String>,
+    scope: Option<String>,
+}
+
+/// Error returned to the client's redirect uri (RFC 6749 4.1.2.1).
+struct AuthorizeError {
+    error: &'static str,
+    description: &'static str,
+}
+
+impl AuthorizeError {
+    fn invalid_request(description: &'static str) -> Self {
+        Self {
+            error: "invalid_request",
+            description,
+        }
+    }
 }
 
 pub async fn handler(
     Extension(authenticater): Extension<Authn>,
+    Extension(session_manager): Extension<SessionManager>,
     Query(query): Query<Params>,
+    RawQuery(raw_query): RawQuery,
 ) -> Result<impl IntoResponse, Error> {
-    let code_challenge = serde_json::from_value::<PkceCodeChallenge>(serde_json::json!(
-        {
-            "code_challenge": query.code_challenge,
-            "code_challenge_method": query.code_challenge_method,
-        }
-    ))
-    .expect("must be success");
+    // unknown clients and redirect uris are not redirected to (RFC 6749 4.1.2.1)
+    let client = session_manager
+        .get_client(&query.client_id)
+        .await?
+        .ok_or(Error400::UnknownClient)?;
+    if !client.allows_redirect_uri(&query.redirect_uri) {
+        return Err(Error400::InvalidRedirectUri.into());
+    }
     let redirect_uri =
-        RedirectUrl::new(query.redirect_uri).map_err(Error400::InvalidUrl)?;
+        RedirectUrl::new(query.redirect_uri.clone()).map_err(Error400::InvalidUrl)?;
 
-    let (auth_url, _csrf_token) = authenticater
+    if query.response_type != "code" {
+        return Ok(error_redirect(
+            &redirect_uri,
+            query.state.as_deref(),
+            AuthorizeError {
+                error: "unsupported_response_type",
+                description: "only `code` is supported",
+            },
+        ));
+    }
+    let code_challenge = match pkce_challenge(&query) {
+        Ok(code_challenge) => code_challenge,
+        Err(err) => return Ok(error_redirect(&redirect_uri, query.state.as_deref(), err)),
+    };
+    let resources = url::form_urlencoded::parse(raw_query.unwrap_or_default().as_bytes())
+        .filter(|(key, _)| key == "resource")
+        .map(|(_, value)| value.into_owned())
+        .collect::<Vec<_>>();
+
+    let mut scopes = authenticater.scopes();
+    for scope in query
+        .scope
+        .iter()
+        .flat_map(|scope| scope.split_whitespace())
+    {
+        let scope = Scope::new(scope.to_string());
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    let state = query.state.clone();
+    let oauth_client = authenticater
         .create_oauth_client()
-        .set_redirect_uri(redirect_uri)
-        .authorize_url(CsrfToken::new_random)
+        .set_redirect_uri(redirect_uri);
+    let mut request = oauth_client
+        .authorize_url(|| match &state {
+            Some(state) => CsrfToken::new(state.clone()),
+            None => CsrfToken::new_random(),
+        })
         .set_pkce_challenge(code_challenge)
-        .add_scopes(authenticater.scopes())
-        .url();
+        .add_scopes(scopes);
+    for resource in &resources {
+        request = request.add_extra_param("resource", resource);
+    }
+    let (mut auth_url, _) = request.url();
+    if query.state.is_none() {
+        // no state is sent back to a client which sent none
+        let pairs = auth_url
+            .query_pairs()
+            .filter(|(key, _)| key != "state")
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect::<Vec<_>>();
+        auth_url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
 
     Ok(Redirect::to(auth_url.as_str()))
 }
+
+// only S256 is advertised in `/.well-known/oauth-authorization-server`
+fn pkce_challenge(query: &Params) -> Result<PkceCodeChallenge, AuthorizeError> {
+    let code_challenge = query
+        .code_challenge
+        .as_deref()
+        .ok_or(AuthorizeError::invalid_request("code_challenge required"))?;
+    match query.code_challenge_method.as_deref() {
+        Some("S256") => {}
+        None => {
+            return Err(AuthorizeError::invalid_request(
+                "code_challenge_method required",
+            ))
+        }
+        Some(_) => {
+            return Err(AuthorizeError::invalid_request(
+                "transform algorithm not supported",
+            ))
+        }
+    }
+    // base64url encoded sha256 digest (RFC 7636 4.2)
+    let well_formed = code_challenge.len() == 43
+        && code_challenge
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if !well_formed {
+        return Err(AuthorizeError::invalid_request("malformed code_challenge"));
+    }
+    serde_json::from_value::<PkceCodeChallenge>(serde_json::json!({
+        "code_challenge": code_challenge,
+        "code_challenge_method": "S256",
+    }))
+    .map_err(|_| AuthorizeError::invalid_request("malformed code_challenge"))
+}
+
+fn error_redirect(
+    redirect_uri: &RedirectUrl,
+    state: Option<&str>,
+    err: AuthorizeError,
+) -> Redirect {
+    let mut url: Url = redirect_uri.url().clone();
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs
+            .append_pair("error", err.error)
+            .append_pair("error_description", err.description);
+        if let Some(state) = state {
+            pairs.append_pair("state", state);
+        }
+    }
+    Redirect::to(url.as_str())
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashSet, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    response::{sse::Event, IntoResponse, Response, Sse},
+    Extension, Json,
+};
+use futures::StreamExt;
+use futures_util::Stream;
+use http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use overlay_mcp_auth::Authz;
+use overlay_mcp_core::{
+    client_message_span, policy_denied_error, rate_limited_error, Authentication,
+    AuthorizationResult, Config, Downstream, Error, Error400, Error404, FatalError, GeneralAuthz,
+    GeneralResolver, GeneralSession, GeneralSessionManager, MCP20250326,
+};
+use overlay_mcp_resolver::Resolver;
+use overlay_mcp_session_manager::{Session, SessionManager};
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientRequest, JsonRpcError, JsonRpcMessage, JsonRpcRequest,
+    JsonRpcVersion2_0, RequestId, ServerJsonRpcMessage,
+};
+use serde::Deserialize;
+use tracing::Instrument;
+
+use crate::{
+    middlewares::{HttpAuthentication, HttpRateLimit, HttpSessionId},
+    utils::{authorize_server_message, session_stream, JsonRequest},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Payload {
+    Batch(Vec<ClientJsonRpcMessage>),
+    Single(ClientJsonRpcMessage),
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn post_handler(
+    HttpAuthentication(authn): HttpAuthentication,
+    session_id: Option<HttpSessionId<MCP20250326>>,
+    rate_limit: HttpRateLimit,
+    Extension(resolver): Extension<Resolver>,
+    Extension(session_manager): Extension<SessionManager>,
+    Extension(authz): Extension<Authz>,
+    State(config): State<Config>,
+    req: JsonRequest<Payload>,
+) -> Result<Response, Error> {
+    let (is_batch, messages) = match req.json {
+        Payload::Batch(messages) => (true, messages),
+        Payload::Single(message) => (false, vec![message]),
+    };
+
+    authz.authorize_enter(&authn).await?.to_err_response()?;
+    // a session created here is closed again if the request is not authorized
+    let created = session_id.is_none();
+    let session = match session_id {
+        Some(session_id) => find_session(&session_manager, &session_id, &authn).await?,
+        None if messages.iter().any(is_initialize_request) => {
+            tracing::info!("mcp initialize without session id");
+            rate_limit.check_session().await?;
+            let upstream = resolver.resolve(&req.parts).await?;
+            session_manager.create(upstream, authn.principal()).await?
+        }
+        None => return Err(Error::BadRequest(Error400::SessionIdRequired)),
+    };
+    session.ensure_started(&req.parts).await?;
+    session.renew_token(&authn).await?;
+
+    let mut pending = HashSet::new();
+    let mut rejected = Vec::new();
+    let mut forwarding = Vec::new();
+    for message in messages {
+        let span = client_message_span(&session.session_id(), &message);
+        let result = match authz
+            .authorize_client_message(&authn, &message)
+            .instrument(span.clone())
+            .await
+        {
+            Ok(result) => result,
+            Err(err) => {
+                close_created(&session, created).await;
+                return Err(err);
+            }
+        };
+        span.record("authz", result.as_str());
+        match result {
+            AuthorizationResult::Allow => {
+                if let Some(limited) = rate_limit.check_message(&message).await? {
+                    span.in_scope(|| {
+                        tracing::warn!(bucket = limited.bucket, "client message rate limited")
+                    });
+                    if let JsonRpcMessage::Request(request) = &message {
+                        rejected.push(ServerJsonRpcMessage::Error(JsonRpcError {
+                            jsonrpc: JsonRpcVersion2_0,
+                            id: request.id.clone(),
+                            error: rate_limited_error(limited.bucket, limited.retry_after),
+                        }));
+                    }
+                    continue;
+                }
+                if let JsonRpcMessage::Request(request) = &message {
+                    pending.insert(request.id.clone());
+                }
+                forwarding.push((message, span));
+            }
+            AuthorizationResult::Deny => {
+                span.in_scope(|| tracing::warn!("client message denied by policy"));
+                if let JsonRpcMessage::Request(request) = &message {
+                    rejected.push(ServerJsonRpcMessage::Error(JsonRpcError {
+                        jsonrpc: JsonRpcVersion2_0,
+                        id: request.id.clone(),
+                        error: policy_denied_error(&message),
+                    }));
+                }
+            }
+            result @ AuthorizationResult::Unauthorized => {
+                close_created(&session, created).await;
+                result.to_err_response()?;
+            }
+        }
+    }
+
+    // subscribe before forwarding, responses must not be missed
+    let downstream = session.guard_bypass_downstream().await?.subscribe();
+    if !forwarding.is_empty() {
+        let send = session.guard_upstream().await?;
+        for (message, span) in forwarding {
+            send.send(message).instrument(span).await?;
+        }
+    }
+
+    let mut response = if pending.is_empty() && rejected.is_empty() {
+        StatusCode::ACCEPTED.into_response()
+    } else if accepts_event_stream(&req.parts.headers) {
+        let stream = response_stream(authz.clone(), authn.clone(), downstream, pending, rejected)
+            .map(|message| {
+                let data = serde_json::to_string(&message).expect("failed to serialize message");
+                Ok::<_, Error>(Event::default().event("message").data(&data))
+            });
+        Sse::new(stream).into_response()
+    } else {
+        // unlike an event stream the client sees nothing until every response arrived
+        let mut messages = tokio::time::timeout(
+            Duration::from_secs(config.server.response_timeout),
+            response_stream(authz.clone(), authn.clone(), downstream, pending, rejected)
+                .collect::<Vec<_>>(),
+        )
+        .await
+        .map_err(|_| Error::Fatal(FatalError::Timeout))?;
+        match (is_batch, messages.len()) {
+            (false, 1) => Json(messages.remove(0)).into_response(),
+            _ => Json(messages).into_response(),
+        }
+    };
+    insert_session_id(response.headers_mut(), &session);
+    Ok(response)
+}
+
+pub async fn get_handler(
+    HttpAuthentication(authn): HttpAuthentication,
+    session_id: HttpSessionId<MCP20250326>,
+    Extension(session_manager): Extension<SessionManager>,
+    Extension(authz): Extension<Authz>,
+    State(config): State<Config>,
+    req: Request<Body>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Error>>>, Error> {
+    let (parts, _) = req.into_parts();
+
+    authz.authorize_enter(&authn).await?.to_err_response()?;
+    let session = find_session(&session_manager, &session_id, &authn).await?;
+    session.ensure_started(&parts).await?;
+    session.renew_token(&authn).await?;
+    let downstream_guard = session.guard_downstream().await?;
+    let messages = session_stream(session, downstream_guard, config.server.token_expiry);
+
+    let recv_stream = async_stream::stream! {
+        for await mut message in messages {
+            if !authorize_server_message(&authz, &authn, &mut message).await {
+                continue;
+            }
+            // responses are delivered on the stream of the POST that carried the request
+            if let JsonRpcMessage::Response(_) | JsonRpcMessage::Error(_) = message {
+                continue;
+            }
+            let data = serde_json::to_string(&message).expect("failed to serialize message");
+            yield Ok(Event::default().event("message").data(&data));
+        }
+    };
+    Ok(Sse::new(recv_stream))
+}
+
+pub async fn delete_handler(
+    HttpAuthentication(authn): HttpAuthentication,
+    session_id: HttpSessionId<MCP20250326>,
+    Extension(session_manager): Extension<SessionManager>,
+    Extension(authz): Extension<Authz>,
+) -> Result<StatusCode, Error> {
+    authz.authorize_enter(&authn).await?.to_err_response()?;
+    let session = find_session(&session_manager, &session_id, &authn).await?;
+    session.close().await?;
+    tracing::info!(session_id = session_id.as_str(), "mcp session deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn find_session(
+    session_manager: &SessionManager,
+    session_id: &HttpSessionId<MCP20250326>,
+    authn: &Authentication,
+) -> Result<Session, Error> {
+    let session = session_manager
+        .find(session_id.as_str())
+        .await?
+        .ok_or(Error::NotFound(Error404::SessionNotFound {
+            session_id: session_id.to_string(),
+        }))?;
+    session.ensure_principal(authn)?;
+    Ok(session)
+}
+
+/// Close the session the rejected request created, its id is never handed out.
+async fn close_created(session: &Session, created: bool) {
+    if !created {
+        return;
+    }
+    if let Err(err) = session.close().await {
+        tracing::error!(session_id = %session.session_id(), "failed to close session: {:?}", err);
+    }
+}
+
+fn response_stream(
+    authz: Authz,
+    authn: Authentication,
+    mut downstream: Downstream,
+    mut pending: HashSet<RequestId>,
+    immediate: Vec<ServerJsonRpcMessage>,
+) -> impl Stream<Item = ServerJsonRpcMessage> {
+    async_stream::stream! {
+        for message in immediate {
+            yield message;
+        }
+        while !pending.is_empty() {
+            // lagged messages are skipped by `recv`, it fails only once the session is gone
+            let mut message = match downstream.recv().await {
+                Ok(message) => message,
+                Err(_) => break,
+            };
+            let id = match &message {
+                JsonRpcMessage::Response(resp) => resp.id.clone(),
+                JsonRpcMessage::Error(err) => err.id.clone(),
+                _ => continue,
+            };
+            if pending.remove(&id) && authorize_server_message(&authz, &authn, &mut message).await {
+                yield message;
+            }
+        }
+    }
+}
+
+fn is_initialize_request(message: &ClientJsonRpcMessage) -> bool {
+    matches!(
+        message,
+        JsonRpcMessage::Request(JsonRpcRequest {
+            request: ClientRequest::InitializeRequest(_),
+            ..
+        })
+    )
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|value| value.trim().parse::<mime::Mime>().ok())
+        .any(|mime| mime.essence_str() == mime::TEXT_EVENT_STREAM.essence_str())
+}
+
+fn insert_session_id(headers: &mut HeaderMap, session: &Session) {
+    match HeaderValue::from_str(&session.session_id()) {
+        Ok(value) => {
+            headers.insert(
+                HeaderName::from_static(MCP20250326::SESSION_ID_HEADER),
+                value,
+            );
+        }
+        Err(err) => {
+            tracing::error!(error = ?err, "session id is not a valid header value");
+        }
+    }
+}
@@ -2,73 +2,95 @@ use axum::Extension;
 use http::StatusCode;
 use overlay_mcp_auth::Authz;
 use overlay_mcp_core::{
-    AuthorizationResult, Error, Error404, GeneralAuthz, GeneralSession, GeneralSessionManager,
-    MCP20241105,
+    client_message_span, policy_denied_error, rate_limited_error, AuthorizationResult, Error,
+    Error404, GeneralAuthz, GeneralSession, GeneralSessionManager, MCP20241105,
 };
 use overlay_mcp_session_manager::SessionManager;
 use rmcp::model::{
-    ClientJsonRpcMessage, ErrorCode, ErrorData, JsonRpcError, JsonRpcMessage, JsonRpcVersion2_0,
-    NumberOrString, ServerJsonRpcMessage,
+    ClientJsonRpcMessage, JsonRpcError, JsonRpcMessage, JsonRpcVersion2_0, ServerJsonRpcMessage,
 };
 
+use tracing::{Instrument, Span};
+
 use crate::{
-    middlewares::{HttpAuthentication, HttpSessionId},
+    middlewares::{HttpAuthentication, HttpRateLimit, HttpSessionId},
     utils::JsonRequest,
 };
 
 pub async fn handler(
     HttpAuthentication(authn): HttpAuthentication,
     session_id: HttpSessionId<MCP20241105>,
+    rate_limit: HttpRateLimit,
     Extension(session_manager): Extension<SessionManager>,
     Extension(authz): Extension<Authz>,
     req: JsonRequest<ClientJsonRpcMessage>,
 ) -> Result<StatusCode, Error> {
-    let result = authz.authorize_client_message(&authn, &req.json).await?;
-    let Some(session) = session_manager.find(session_id.as_str()).await? else {
-        return Err(Error::NotFound(Error404::SessionNotFound {
-            session_id: session_id.to_string(),
-        }));
-    };
-    session.ensure_started(&req.parts).await?;
-    match result {
-        AuthorizationResult::Allow => {}
-        AuthorizationResult::Deny => {
-            let bypass = session.guard_bypass_downstream().await?;
-            let id = extract_json_rpc_id(&req.json).unwrap_or(NumberOrString::Number(0));
-            tracing::error!("unauthorized");
-            bypass
-                .send(ServerJsonRpcMessage::Error(JsonRpcError {
-                    jsonrpc: JsonRpcVersion2_0,
-                    id,
-                    error: ErrorData {
-                        code: ErrorCode::INVALID_REQUEST,
-                        message: "This method is not allowed".into(),
-                        data: None,
-                    },
-                }))
-                .await?;
+    let span = client_message_span(session_id.as_str(), &req.json);
+    async move {
+        let result = authz.authorize_client_message(&authn, &req.json).await?;
+        Span::current().record("authz", result.as_str());
+        let Some(session) = session_manager.find(session_id.as_str()).await? else {
+            return Err(Error::NotFound(Error404::SessionNotFound {
+                session_id: session_id.to_string(),
+            }));
+        };
+        session.ensure_principal(&authn)?;
+        session.ensure_started(&req.parts).await?;
+        session.renew_token(&authn).await?;
+        match result {
+            AuthorizationResult::Allow => {}
+            AuthorizationResult::Deny => {
+                tracing::warn!(
+                    session_id = session_id.as_str(),
+                    "client message denied by policy"
+                );
+                // denied message must never reach upstream, only requests expect an answer
+                if let JsonRpcMessage::Request(request) = &req.json {
+                    let bypass = session.guard_bypass_downstream().await?;
+                    bypass
+                        .send(ServerJsonRpcMessage::Error(JsonRpcError {
+                            jsonrpc: JsonRpcVersion2_0,
+                            id: request.id.clone(),
+                            error: policy_denied_error(&req.json),
+                        }))
+                        .await?;
+                }
+                return Ok(StatusCode::ACCEPTED);
+            }
+            result @ AuthorizationResult::Unauthorized => {
+                result.to_err_response()?;
+            }
         }
-        result @ AuthorizationResult::Unauthorized => {
-            result.to_err_response()?;
+        if let Some(limited) = rate_limit.check_message(&req.json).await? {
+            // limited message never reaches upstream, only requests expect an answer
+            if let JsonRpcMessage::Request(request) = &req.json {
+                let bypass = session.guard_bypass_downstream().await?;
+                bypass
+                    .send(ServerJsonRpcMessage::Error(JsonRpcError {
+                        jsonrpc: JsonRpcVersion2_0,
+                        id: request.id.clone(),
+                        error: rate_limited_error(limited.bucket, limited.retry_after),
+                    }))
+                    .await?;
+            } else {
+                tracing::warn!(
+                    session_id = session_id.as_str(),
+                    bucket = limited.bucket,
+                    "client notification dropped by rate limit"
+                );
+            }
+            return Ok(StatusCode::ACCEPTED);
         }
-    }
-    let send = session.guard_upstream().await?;
-    tracing::info!(
-        "upstream \n{}",
-        serde_json::to_string_pretty(&req.json).unwrap()
-    );
-
-    send.send(req.json).await?;
-    Ok(StatusCode::ACCEPTED)
-}
+        let send = session.guard_upstream().await?;
+        // messages carry tool arguments, never log them at info
+        tracing::debug!(
+            "upstream \n{}",
+            serde_json::to_string_pretty(&req.json).unwrap()
+        );
 
-fn extract_json_rpc_id<Req, Res, Noti>(
-    message: &JsonRpcMessage<Req, Res, Noti>,
-) -> Option<NumberOrString> {
-    match message {
-        JsonRpcMessage::Request(req) => Some(req.id.clone()),
-        JsonRpcMessage::Response(resp) => Some(resp.id.clone()),
-        JsonRpcMessage::Notification(_) => None,
-        JsonRpcMessage::Error(err) => Some(err.id.clone()),
+        send.send(req.json).await?;
+        Ok(StatusCode::ACCEPTED)
     }
+    .instrument(span)
+    .await
 }
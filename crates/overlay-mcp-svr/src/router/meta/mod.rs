@@ -1,7 +1,18 @@
-use axum::{routing::get, Router};
+use std::sync::OnceLock;
+
+use axum::{
+    routing::{delete, get},
+    Router,
+};
 use axum_health::Health;
-use axum_prometheus::PrometheusMetricLayer;
-use overlay_mcp_core::Config;
+use axum_prometheus::{utils::SECONDS_DURATION_BUCKETS, PrometheusMetricLayerBuilder};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use overlay_mcp_core::{describe_metrics, Config};
+
+pub mod sessions;
+
+// the recorder is process wide, every router renders the same one
+static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
 
 pub fn router(config: &Config) -> Router<Config> {
     let mut router = Router::new();
@@ -14,7 +25,9 @@ pub fn router(config: &Config) -> Router<Config> {
 
     if config.application.prometheus {
         tracing::info!("Enable Prometheus metrics");
-        let (prometheus_layer, prometheus_metrics) = PrometheusMetricLayer::pair();
+        let (prometheus_layer, prometheus_metrics) = PrometheusMetricLayerBuilder::new()
+            .with_metrics_from_fn(|| PROMETHEUS_HANDLE.get_or_init(install_recorder).clone())
+            .build_pair();
         router = router
             .route(
                 "/metrics",
@@ -22,5 +35,25 @@ pub fn router(config: &Config) -> Router<Config> {
             )
             .layer(prometheus_layer);
     }
+
+    if config.application.admin.is_some() {
+        router = router
+            .route(
+                "/sessions",
+                get(sessions::list_handler).delete(sessions::delete_all_handler),
+            )
+            .route("/sessions/{session_id}", delete(sessions::delete_handler));
+    }
     router
 }
+
+// every histogram, the http and MCP latencies, is rendered with the same buckets
+fn install_recorder() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(SECONDS_DURATION_BUCKETS)
+        .expect("histogram buckets must not be empty")
+        .install_recorder()
+        .expect("failed to install prometheus recorder");
+    describe_metrics();
+    handle
+}
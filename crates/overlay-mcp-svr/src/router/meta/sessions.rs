@@ -0,0 +1,88 @@
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use http::StatusCode;
+use overlay_mcp_core::{
+    Error, Error400, Error404, GeneralSession, GeneralSessionManager, Principal, SessionInfo,
+};
+use overlay_mcp_session_manager::SessionManager;
+use serde::{Deserialize, Serialize};
+
+use crate::middlewares::AdminAuthentication;
+
+#[derive(Debug, Deserialize)]
+pub struct SubjectQuery {
+    /// Jwt subject or api key fingerprint of the session principal.
+    subject: Option<String>,
+    /// Jwt issuer of the session principal, subjects are only unique per issuer.
+    issuer: Option<String>,
+}
+
+impl SubjectQuery {
+    fn matches(&self, subject: &str, principal: &Principal) -> bool {
+        principal.subject() == Some(subject) && principal.issuer() == self.issuer.as_deref()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Closed {
+    closed: Vec<String>,
+}
+
+pub async fn list_handler(
+    AdminAuthentication(_): AdminAuthentication,
+    Extension(session_manager): Extension<SessionManager>,
+    Query(query): Query<SubjectQuery>,
+) -> Result<Json<Vec<SessionInfo>>, Error> {
+    let mut sessions = session_manager.list().await?;
+    if let Some(subject) = &query.subject {
+        sessions.retain(|session| query.matches(subject, &session.principal));
+    }
+    Ok(Json(sessions))
+}
+
+pub async fn delete_handler(
+    AdminAuthentication(authn): AdminAuthentication,
+    Extension(session_manager): Extension<SessionManager>,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, Error> {
+    let session = session_manager
+        .find(&session_id)
+        .await?
+        .ok_or(Error::NotFound(Error404::SessionNotFound {
+            session_id: session_id.clone(),
+        }))?;
+    session.close().await?;
+    tracing::info!(session_id, admin = ?authn.principal(), "session closed by admin");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Close every session of `subject`, a subject is required so a typo never closes everything.
+pub async fn delete_all_handler(
+    AdminAuthentication(authn): AdminAuthentication,
+    Extension(session_manager): Extension<SessionManager>,
+    Query(query): Query<SubjectQuery>,
+) -> Result<Json<Closed>, Error> {
+    let subject = query
+        .subject
+        .clone()
+        .ok_or(Error::BadRequest(Error400::SubjectRequired))?;
+    let mut closed = Vec::new();
+    for info in session_manager.list().await? {
+        if !query.matches(&subject, &info.principal) {
+            continue;
+        }
+        let Some(session) = session_manager.find(&info.session_id).await? else {
+            continue;
+        };
+        match session.close().await {
+            Ok(()) => closed.push(info.session_id),
+            // closed meanwhile
+            Err(Error::AlreadyClosedSession(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    tracing::info!(subject, issuer = ?query.issuer, ?closed, admin = ?authn.principal(), "sessions closed by admin");
+    Ok(Json(Closed { closed }))
+}
@@ -3,20 +3,25 @@ use axum::{
     Extension, Router,
 };
 use overlay_mcp_auth::{Authn, Authz};
-use overlay_mcp_core::{Config, Error};
+use overlay_mcp_core::{Audit, Config, Error};
 use overlay_mcp_resolver::Resolver;
 use overlay_mcp_session_manager::SessionManager;
 use tokio_util::sync::CancellationToken;
-use tower_http::cors::CorsLayer;
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+};
 
 use crate::middlewares::{
-    trace_layer, AuthLayer, ReqwestLayer, ResolverLayer, SessionManagerLayer,
+    trace_layer, AuthLayer, BearerChallengeLayer, ProblemLayer, ReqwestLayer, ResolverLayer,
+    SessionManagerLayer,
 };
 
 pub mod meta;
 pub mod well_known;
 
 pub mod authorize;
+pub mod mcp;
 pub mod message;
 pub mod register;
 pub mod sse;
@@ -24,15 +29,26 @@ pub mod token;
 
 pub async fn router(cancel: CancellationToken, config: Config) -> Result<Router, Error> {
     let authn = Authn::new(&config).await?;
-    let authz = Authz::new(&config).await?;
+    let audit = Audit::new(config.application.audit.as_ref())?;
+    let authz = Authz::new(&config, audit.clone()).await?;
+
+    // protected resources challenge callers to authenticate with the oauth proxy
+    let protected = Router::new()
+        .route("/sse", get(sse::handler))
+        .route("/message", post(message::handler))
+        .route(
+            "/mcp",
+            get(mcp::get_handler)
+                .post(mcp::post_handler)
+                .delete(mcp::delete_handler),
+        )
+        .layer(BearerChallengeLayer::new(&config, &authn));
 
-    let router = Router::new()
+    let mut router = Router::new()
         .route("/authorize", get(authorize::handler))
         .route("/register", post(register::handler))
         .route("/token", post(token::handler))
-        .route("/sse", get(sse::handler))
-        .route("/message", post(message::handler))
-        // TODO: .route("/mcp", get(mcp::handler).post(mcp::handler)) // for MCP 20250326 spec
+        .merge(protected)
         .nest("/.well-known", well_known::router(&config))
         .nest("/.meta", meta::router(&config))
         .layer(Extension(cancel.clone()))
@@ -40,10 +56,15 @@ pub async fn router(cancel: CancellationToken, config: Config) -> Result<Router,
         .layer(ReqwestLayer::new(reqwest::Client::new()))
         .layer(AuthLayer::new(authz, authn))
         .layer(SessionManagerLayer::new(
-            SessionManager::new(cancel.clone(), &config).await?,
+            SessionManager::new(cancel.clone(), &config, audit).await?,
         ))
+        .layer(ProblemLayer)
         .layer(trace_layer())
-        .layer(CorsLayer::permissive())
-        .with_state(config);
-    Ok(router)
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(CorsLayer::permissive());
+    if let Some(ip_extract) = &config.application.ip_extract {
+        router = router.layer(ip_extract.clone().into_extension());
+    }
+    Ok(router.with_state(config))
 }
@@ -1,45 +1,138 @@
-use axum::Json;
-use chrono::{Duration, Utc};
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use overlay_mcp_core::{
+    hash_client_secret, unix_now, Config, Error, Error400, GeneralSessionManager, RegisteredClient,
+    TokenEndpointAuthMethod,
+};
+use overlay_mcp_session_manager::SessionManager;
 use rand::{distr::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
+use url::Url;
 use uuid::Uuid;
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct Body {
     redirect_uris: Vec<String>,
-    token_endpoint_auth_method: String,
+    #[serde(default)]
+    token_endpoint_auth_method: TokenEndpointAuthMethod,
+    #[serde(default = "default_grant_types")]
     grant_types: Vec<String>,
+    #[serde(default = "default_response_types")]
     response_types: Vec<String>,
-    client_name: String,
-    client_uri: String,
+    client_name: Option<String>,
+    client_uri: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct Response {
     client_id: String,
-    client_secret: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+    client_id_issued_at: u64,
+    client_secret_expires_at: u64,
     redirect_uris: Vec<String>,
-    client_id_issued_at: i64,
-    client_secret_expires_at: i64,
+    token_endpoint_auth_method: TokenEndpointAuthMethod,
+    grant_types: Vec<String>,
+    response_types: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_uri: Option<String>,
 }
 
-pub async fn handler(Json(value): Json<Body>) -> Json<Response> {
-    let raw_client_secret = rand::rng()
-        .sample_iter(&Alphanumeric)
-        .take(32)
-        .collect::<Vec<_>>();
-    let client_secret = String::from_utf8_lossy(&raw_client_secret).to_string();
+fn default_grant_types() -> Vec<String> {
+    vec!["authorization_code".to_string()]
+}
+
+fn default_response_types() -> Vec<String> {
+    vec!["code".to_string()]
+}
 
-    let now = Utc::now();
-    let issued_at = now.timestamp();
-    let expires_at = (now + Duration::hours(1)).timestamp();
+pub async fn handler(
+    Extension(session_manager): Extension<SessionManager>,
+    State(config): State<Config>,
+    Json(value): Json<Body>,
+) -> Result<(StatusCode, Json<Response>), Error> {
+    if value.redirect_uris.is_empty() {
+        return Err(Error400::InvalidClientMetadata("redirect_uris required").into());
+    }
+    // redirect uris are absolute and without fragment (RFC 6749 3.1.2)
+    for redirect_uri in &value.redirect_uris {
+        match Url::parse(redirect_uri) {
+            Ok(url) if url.fragment().is_none() => {}
+            _ => return Err(Error400::InvalidClientMetadata("invalid redirect_uri").into()),
+        }
+    }
 
-    Json(Response {
+    let grant_types_supported = config.server.registration.grant_types_supported();
+    if !value
+        .grant_types
+        .iter()
+        .all(|grant_type| grant_types_supported.contains(&grant_type.as_str()))
+    {
+        return Err(Error400::InvalidClientMetadata("unsupported grant_type").into());
+    }
+    // client_credentials authenticates the caller's own client at the idp, not a registered one
+    if value
+        .grant_types
+        .iter()
+        .any(|grant| grant == "client_credentials")
+    {
+        return Err(
+            Error400::InvalidClientMetadata("client_credentials uses clients of the idp").into(),
+        );
+    }
+
+    let client_secret =
+        (value.token_endpoint_auth_method != TokenEndpointAuthMethod::None).then(|| {
+            let raw_client_secret = rand::rng()
+                .sample_iter(&Alphanumeric)
+                .take(32)
+                .collect::<Vec<_>>();
+            String::from_utf8_lossy(&raw_client_secret).to_string()
+        });
+    let registration = &config.server.registration;
+    let issued_at = unix_now();
+    let expires_at = match (&client_secret, registration.client_secret_ttl) {
+        (Some(_), ttl) if ttl > 0 => issued_at + ttl,
+        _ => 0,
+    };
+    let client_secret_hash = match &client_secret {
+        Some(client_secret) => Some(hash_client_secret(client_secret.clone()).await),
+        None => None,
+    };
+
+    let client = RegisteredClient {
         client_id: Uuid::new_v4().to_string(),
-        client_secret,
-        redirect_uris: value.redirect_uris,
+        client_secret_hash,
         client_id_issued_at: issued_at,
         client_secret_expires_at: expires_at,
-    })
+        redirect_uris: value.redirect_uris,
+        token_endpoint_auth_method: value.token_endpoint_auth_method,
+        grant_types: value.grant_types,
+        response_types: value.response_types,
+        client_name: value.client_name,
+        client_uri: value.client_uri,
+    };
+    session_manager
+        .register_client(client.clone(), Duration::from_secs(registration.client_ttl))
+        .await?;
+    tracing::info!(client_id = client.client_id, client_name = ?client.client_name, "client registered");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(Response {
+            client_id: client.client_id,
+            client_secret,
+            client_id_issued_at: client.client_id_issued_at,
+            client_secret_expires_at: client.client_secret_expires_at,
+            redirect_uris: client.redirect_uris,
+            token_endpoint_auth_method: client.token_endpoint_auth_method,
+            grant_types: client.grant_types,
+            response_types: client.response_types,
+            client_name: client.client_name,
+            client_uri: client.client_uri,
+        }),
+    ))
 }
@@ -1,6 +1,6 @@
 use axum::{
     body::Body,
-    extract::Request,
+    extract::{Request, State},
     response::{sse::Event, Sse},
     Extension,
 };
@@ -9,21 +9,27 @@ use futures_util::Stream;
 use httpbuilder::http_reference::HttpReference;
 use overlay_mcp_auth::Authz;
 use overlay_mcp_core::{
-    Authentication, Error, Error404, GeneralAuthz, GeneralResolver, GeneralSession,
+    Authentication, Config, Error, Error404, GeneralAuthz, GeneralResolver, GeneralSession,
     GeneralSessionManager, MCP20241105,
 };
 use overlay_mcp_resolver::Resolver;
 use overlay_mcp_session_manager::SessionManager;
 use url::form_urlencoded;
 
-use crate::middlewares::{HttpAuthentication, HttpSessionId};
+use crate::{
+    middlewares::{HttpAuthentication, HttpRateLimit, HttpSessionId},
+    utils::{authorize_server_message, session_stream},
+};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handler(
     HttpAuthentication(authn): HttpAuthentication,
     session_id: Option<HttpSessionId<MCP20241105>>,
+    rate_limit: HttpRateLimit,
     Extension(resolver): Extension<Resolver>,
     Extension(session_manager): Extension<SessionManager>,
     Extension(authz): Extension<Authz>,
+    State(config): State<Config>,
     req: Request<Body>,
 ) -> Result<Sse<impl Stream<Item = Result<Event, Error>>>, Error> {
     let (parts, _) = req.into_parts();
@@ -37,24 +43,29 @@ pub async fn handler(
                 .await?
                 .ok_or(Error::NotFound(Error404::SessionNotFound {
                     session_id: session_id.to_string(),
-                }))?
+                }))
+                .and_then(|session| session.ensure_principal(&authn).map(|_| session))?
         }
         None => {
             tracing::info!("sse connection without session id");
-            let upstream_url = resolver.resolve(&parts).await?;
-            session_manager.create(upstream_url).await?
+            rate_limit.check_session().await?;
+            let upstream = resolver.resolve(&parts).await?;
+            session_manager.create(upstream, authn.principal()).await?
         }
     };
     session.ensure_started(&parts).await?;
+    session.renew_token(&authn).await?;
     let session_guard = session.guard_close().await?;
     let downstream_guard = session.guard_downstream().await?;
+    let messages = session_stream(session, downstream_guard, config.server.token_expiry);
 
     let mut serializer = form_urlencoded::Serializer::new(String::new());
     serializer.append_pair("session_id", session_guard.session_id());
     if let Authentication::ApiKey {
         apikey,
         apikey_from: HttpReference::Query(query),
-    } = authn
+        ..
+    } = &authn
     {
         serializer.append_pair(query.as_str(), apikey.as_str());
     }
@@ -62,13 +73,11 @@ pub async fn handler(
     let query_str = serializer.finish();
 
     let recv_stream = async_stream::stream! {
-        let mut recv = downstream_guard;
         let _guard = session_guard;
-        loop {
-            let message = match recv.recv().await {
-                Ok(message) => message,
-                Err(_) => break,
-            };
+        for await mut message in messages {
+            if !authorize_server_message(&authz, &authn, &mut message).await {
+                continue;
+            }
             let data = serde_json::to_string(&message).expect("failed to serialize message");
             yield Ok(Event::default().event("message").data(&data));
         }
@@ -1,40 +1,294 @@
 use std::borrow::Cow;
 
-use axum::{Extension, Form, Json};
-use oauth2::{basic::BasicTokenResponse, AuthorizationCode, PkceCodeVerifier, RedirectUrl};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Form, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use oauth2::{
+    basic::{BasicClient, BasicErrorResponse, BasicTokenResponse},
+    AuthorizationCode, ClientId, ClientSecret, HttpClientError, PkceCodeVerifier, RedirectUrl,
+    RefreshToken, RequestTokenError, Scope,
+};
 use overlay_mcp_auth::Authn;
-use overlay_mcp_core::{Error, FatalError, GeneralAuthn};
-use serde::Deserialize;
+use overlay_mcp_core::{Config, Error, Error400, GeneralAuthn, GeneralSessionManager};
+use overlay_mcp_session_manager::SessionManager;
+use serde::{Deserialize, Serialize};
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct Body {
     grant_type: String,
-    client_id: String,
-    code: String,
-    code_verifier: String,
-    redirect_uri: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    // authorization_code
+    code: Option<String>,
+    code_verifier: Option<String>,
+    redirect_uri: Option<String>,
+    // refresh_token
+    refresh_token: Option<String>,
+    // refresh_token and client_credentials
+    scope: Option<String>,
 }
 
 pub type Response = BasicTokenResponse;
 
+/// Error response of the token endpoint (RFC 6749 5.2).
+#[derive(Debug, Serialize)]
+pub struct TokenError {
+    #[serde(skip)]
+    status: StatusCode,
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_description: Option<String>,
+}
+
+impl TokenError {
+    fn new(status: StatusCode, error: &str, error_description: &str) -> Self {
+        Self {
+            status,
+            error: error.to_string(),
+            error_description: Some(error_description.to_string()),
+        }
+    }
+
+    fn invalid_request(error_description: &str) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            error_description,
+        )
+    }
+}
+
+impl IntoResponse for TokenError {
+    fn into_response(self) -> axum::response::Response {
+        let mut response = (
+            self.status,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(&self),
+        )
+            .into_response();
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, "Basic".parse().unwrap());
+        }
+        response
+    }
+}
+
+impl From<Error> for TokenError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Unauthorized(err) => {
+                Self::new(StatusCode::UNAUTHORIZED, "invalid_client", &err.to_string())
+            }
+            Error::BadRequest(Error400::InvalidRedirectUri) => Self::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_grant",
+                "redirect_uri not registered for the client",
+            ),
+            Error::BadRequest(err) => Self::invalid_request(&err.to_string()),
+            err => {
+                tracing::error!(error = ?err, "token endpoint error");
+                Self::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "server_error",
+                    "token request failed",
+                )
+            }
+        }
+    }
+}
+
+impl From<RequestTokenError<HttpClientError<reqwest::Error>, BasicErrorResponse>> for TokenError {
+    fn from(err: RequestTokenError<HttpClientError<reqwest::Error>, BasicErrorResponse>) -> Self {
+        match err {
+            // errors of the overlay's own client at the idp are not the caller's
+            RequestTokenError::ServerResponse(response)
+                if response.error().to_string() != "invalid_client" =>
+            {
+                Self {
+                    status: StatusCode::BAD_REQUEST,
+                    error: response.error().to_string(),
+                    error_description: response.error_description().cloned(),
+                }
+            }
+            err => {
+                tracing::error!(error = ?err, "token request error");
+                Self::new(
+                    StatusCode::BAD_GATEWAY,
+                    "server_error",
+                    "token request to the identity provider failed",
+                )
+            }
+        }
+    }
+}
+
 pub async fn handler(
     Extension(client): Extension<reqwest::Client>,
     Extension(authn): Extension<Authn>,
+    Extension(session_manager): Extension<SessionManager>,
+    State(config): State<Config>,
+    headers: HeaderMap,
     Form(query): Form<Body>,
-) -> Result<Json<Response>, Error> {
-    let redirect_url = RedirectUrl::new(query.redirect_uri).expect("redirect url");
-    let oauth_client = authn.create_oauth_client();
+) -> Result<Json<Response>, TokenError> {
+    // `client_secret_basic` credentials, or `client_id` and `client_secret` of the form
+    let (client_id, client_secret) = match basic_credentials(&headers) {
+        Some((client_id, client_secret)) => (client_id, Some(client_secret)),
+        None => (
+            query.client_id.clone().ok_or(TokenError::new(
+                StatusCode::UNAUTHORIZED,
+                "invalid_client",
+                "client authentication required",
+            ))?,
+            query.client_secret.clone(),
+        ),
+    };
+    let grant_types_supported = config.server.registration.grant_types_supported();
+    let scopes = query
+        .scope
+        .iter()
+        .flat_map(|scope| scope.split_whitespace())
+        .map(|scope| Scope::new(scope.to_string()))
+        .collect::<Vec<_>>();
+    if query.grant_type == "client_credentials"
+        && grant_types_supported.contains(&"client_credentials")
+    {
+        let token_response =
+            exchange_client_credentials(&client, &authn, &client_id, client_secret, scopes).await?;
+        tracing::info!(client_id, grant_type = query.grant_type, "token issued");
+        return Ok(Json(token_response));
+    }
 
-    let token_request = oauth_client
-        .exchange_code(AuthorizationCode::new(query.code))?
-        .set_pkce_verifier(PkceCodeVerifier::new(query.code_verifier.to_owned()))
-        .set_redirect_uri(Cow::Borrowed(&redirect_url));
+    let registered = session_manager
+        .get_client(&client_id)
+        .await?
+        .ok_or(TokenError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_client",
+            "client not registered",
+        ))?;
+    registered.verify_secret(client_secret.as_deref()).await?;
 
-    let token_response = token_request.request_async(&client).await.map_err(|err| {
-        tracing::error!(error = ?err, "token request error");
-        FatalError::TokenRequestError
-    })?;
+    if !grant_types_supported.contains(&query.grant_type.as_str()) {
+        return Err(TokenError::new(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "grant_type not supported",
+        ));
+    }
+    if !registered.allows_grant_type(&query.grant_type) {
+        return Err(TokenError::new(
+            StatusCode::BAD_REQUEST,
+            "unauthorized_client",
+            "grant_type not registered for the client",
+        ));
+    }
+
+    let oauth_client = authn.create_oauth_client();
+    let token_response = match query.grant_type.as_str() {
+        "authorization_code" => {
+            let (Some(code), Some(code_verifier), Some(redirect_uri)) =
+                (query.code, query.code_verifier, query.redirect_uri)
+            else {
+                return Err(TokenError::invalid_request(
+                    "code, code_verifier and redirect_uri required",
+                ));
+            };
+            if !registered.allows_redirect_uri(&redirect_uri) {
+                return Err(Error::from(Error400::InvalidRedirectUri).into());
+            }
+            let redirect_url = RedirectUrl::new(redirect_uri)
+                .map_err(|_| TokenError::invalid_request("invalid redirect_uri"))?;
+            oauth_client
+                .exchange_code(AuthorizationCode::new(code))
+                .map_err(Error::from)?
+                .set_pkce_verifier(PkceCodeVerifier::new(code_verifier))
+                .set_redirect_uri(Cow::Borrowed(&redirect_url))
+                .request_async(&client)
+                .await?
+        }
+        // refresh_token, client_credentials is never registered
+        _ => {
+            let refresh_token = RefreshToken::new(
+                query
+                    .refresh_token
+                    .ok_or(TokenError::invalid_request("refresh_token required"))?,
+            );
+            oauth_client
+                .exchange_refresh_token(&refresh_token)
+                .map_err(Error::from)?
+                .add_scopes(scopes)
+                .request_async(&client)
+                .await?
+        }
+    };
+    tracing::info!(client_id, grant_type = query.grant_type, "token issued");
 
     Ok(Json(token_response))
 }
+
+/// `client_credentials` authenticates the caller as its own client at the idp, the overlay's
+/// client is never used on behalf of the caller.
+async fn exchange_client_credentials(
+    client: &reqwest::Client,
+    authn: &Authn,
+    client_id: &str,
+    client_secret: Option<String>,
+    scopes: Vec<Scope>,
+) -> Result<Response, TokenError> {
+    let client_secret = client_secret.ok_or(TokenError::new(
+        StatusCode::UNAUTHORIZED,
+        "invalid_client",
+        "client authentication required",
+    ))?;
+    let token_url = authn
+        .create_oauth_client()
+        .token_uri()
+        .cloned()
+        .ok_or(Error::from(oauth2::ConfigurationError::MissingUrl("token")))?;
+    BasicClient::new(ClientId::new(client_id.to_string()))
+        .set_client_secret(ClientSecret::new(client_secret))
+        .set_token_uri(token_url)
+        .exchange_client_credentials()
+        .add_scopes(scopes)
+        .request_async(client)
+        .await
+        .map_err(|err| match err {
+            // the caller's credentials were rejected, not the overlay's
+            RequestTokenError::ServerResponse(response)
+                if response.error().to_string() == "invalid_client" =>
+            {
+                TokenError::new(
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_client",
+                    "client authentication failed",
+                )
+            }
+            err => err.into(),
+        })
+}
+
+// client id and secret are form encoded before joined (RFC 6749 2.3.1)
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let credentials = STANDARD.decode(credentials.trim()).ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (client_id, client_secret) = credentials.split_once(':')?;
+    // `=` and `&` are plain characters here, unlike in a form body
+    let decode = |value: &str| {
+        percent_encoding::percent_decode_str(&value.replace('+', " "))
+            .decode_utf8()
+            .ok()
+            .map(|decoded| decoded.into_owned())
+    };
+    Some((decode(client_id)?, decode(client_secret)?))
+}
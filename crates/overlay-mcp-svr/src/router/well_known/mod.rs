@@ -1,12 +1,21 @@
 pub mod oauth_authorization_server;
-
+pub mod oauth_protected_resource;
 
 use axum::{routing::get, Router};
 use overlay_mcp_core::Config;
 
 pub fn router(_config: &Config) -> Router<Config> {
-    Router::new().route(
-        "/oauth-authorization-server",
-        get(oauth_authorization_server::handler),
-    )
+    Router::new()
+        .route(
+            "/oauth-authorization-server",
+            get(oauth_authorization_server::handler),
+        )
+        .route(
+            "/oauth-protected-resource",
+            get(oauth_protected_resource::handler),
+        )
+        .route(
+            "/oauth-protected-resource/{*path}",
+            get(oauth_protected_resource::path_handler),
+        )
 }
@@ -36,8 +36,18 @@ pub(crate) async fn handler(
         response_types_supported: vec!["code".to_string()],
         code_challenge_methods_supported: vec!["S256".to_string()],
         token_endpoint: token_endpoint.to_string(),
-        token_endpoint_auth_methods_supported: vec!["client_secret_basic".to_string()],
-        grant_types_supported: vec!["authorization_code".to_string()],
+        token_endpoint_auth_methods_supported: vec![
+            "client_secret_basic".to_string(),
+            "client_secret_post".to_string(),
+            "none".to_string(),
+        ],
+        grant_types_supported: config
+            .server
+            .registration
+            .grant_types_supported()
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
         registration_endpoint: registration_endpoint.to_string(),
     })
 }
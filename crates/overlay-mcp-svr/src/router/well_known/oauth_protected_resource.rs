@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use http::StatusCode;
+use overlay_mcp_auth::Authn;
+use overlay_mcp_core::{Config, GeneralAuthn};
+use serde::Serialize;
+
+/// Protected resource metadata (RFC 9728), the overlay is its own authorization server.
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub resource: String,
+    pub authorization_servers: Vec<String>,
+    pub bearer_methods_supported: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes_supported: Vec<String>,
+}
+
+pub(crate) async fn handler(
+    State(config): State<Config>,
+    Extension(authn): Extension<Authn>,
+) -> Result<Json<Response>, StatusCode> {
+    metadata(&config, &authn, "")
+}
+
+/// Metadata of the resource at `/{path}`, e.g. `/.well-known/oauth-protected-resource/mcp`.
+/// Paths that do not resolve to a resource of the hostname are not found.
+pub(crate) async fn path_handler(
+    State(config): State<Config>,
+    Extension(authn): Extension<Authn>,
+    Path(path): Path<String>,
+) -> Result<Json<Response>, StatusCode> {
+    metadata(&config, &authn, &path)
+}
+
+fn metadata(config: &Config, authn: &Authn, path: &str) -> Result<Json<Response>, StatusCode> {
+    let mut hostname = config.server.hostname.clone();
+    hostname.set_path("/");
+    hostname.set_query(None);
+    hostname.set_fragment(None);
+    let authorization_server = hostname.as_str().trim_end_matches('/').to_string();
+    // the decoded path may be an absolute url
+    let resource = hostname
+        .join(path)
+        .ok()
+        .filter(|resource| resource.origin() == hostname.origin())
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(Response {
+        resource: resource.as_str().trim_end_matches('/').to_string(),
+        authorization_servers: vec![authorization_server],
+        bearer_methods_supported: vec!["header".to_string()],
+        scopes_supported: authn
+            .scopes()
+            .into_iter()
+            .map(|scope| scope.to_string())
+            .collect(),
+    }))
+}
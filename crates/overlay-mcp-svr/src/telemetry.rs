@@ -0,0 +1,54 @@
+use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{ExporterBuildError, Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{span_processor_with_async_runtime::BatchSpanProcessor, SdkTracerProvider},
+    Resource,
+};
+use overlay_mcp_core::{otel::OtlpProtocol, OpenTelemetryConfig};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// OTLP tracer provider exporting spans in batches on the tokio runtime.
+///
+/// Also installs the W3C `traceparent` propagator used for incoming and upstream requests.
+pub fn tracer_provider(
+    config: &OpenTelemetryConfig,
+) -> Result<SdkTracerProvider, ExporterBuildError> {
+    let exporter = match config.protocol {
+        OtlpProtocol::Grpc => SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(&config.endpoint)
+            .build()?,
+        OtlpProtocol::HttpProtobuf => SpanExporter::builder()
+            .with_http()
+            .with_protocol(Protocol::HttpBinary)
+            .with_endpoint(format!(
+                "{}/v1/traces",
+                config.endpoint.trim_end_matches('/')
+            ))
+            .build()?,
+    };
+    let provider = SdkTracerProvider::builder()
+        .with_span_processor(BatchSpanProcessor::builder(exporter, runtime::Tokio).build())
+        .with_resource(
+            Resource::builder()
+                .with_attribute(KeyValue::new("service.name", config.service_name.clone()))
+                .build(),
+        )
+        .build();
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    Ok(provider)
+}
+
+pub fn layer<S>(
+    provider: &SdkTracerProvider,
+) -> OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("overlay-mcp"))
+}
@@ -0,0 +1,36 @@
+use overlay_mcp_auth::Authz;
+use overlay_mcp_core::{response_denied_error, Authentication, AuthorizationResult, GeneralAuthz};
+use rmcp::model::{
+    ErrorData, JsonRpcError, JsonRpcMessage, JsonRpcResponse, JsonRpcVersion2_0,
+    ServerJsonRpcMessage,
+};
+
+/// Filter a message before it is delivered to the client, returns false if it must be dropped.
+/// A response that is not allowed is replaced by an error with the same id, the client would
+/// otherwise wait for it forever.
+pub async fn authorize_server_message(
+    authz: &Authz,
+    authn: &Authentication,
+    message: &mut ServerJsonRpcMessage,
+) -> bool {
+    let error = match authz.authorize_server_message(authn, message).await {
+        Ok(AuthorizationResult::Allow) => return true,
+        Ok(_) => {
+            tracing::warn!("server message denied by policy");
+            response_denied_error()
+        }
+        Err(err) => {
+            tracing::error!(error = ?err, "server message authorization failed");
+            ErrorData::internal_error("Authorization of the response failed", None)
+        }
+    };
+    let JsonRpcMessage::Response(JsonRpcResponse { id, .. }) = message else {
+        return false;
+    };
+    *message = JsonRpcMessage::Error(JsonRpcError {
+        jsonrpc: JsonRpcVersion2_0,
+        id: id.clone(),
+        error,
+    });
+    true
+}
@@ -1,3 +1,7 @@
+mod authorization;
 mod jsonrequest;
+mod session_stream;
 
+pub use authorization::*;
 pub use jsonrequest::*;
+pub use session_stream::*;
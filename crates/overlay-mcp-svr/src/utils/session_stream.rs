@@ -0,0 +1,99 @@
+use std::{collections::VecDeque, time::Duration};
+
+use futures_util::Stream;
+use overlay_mcp_core::{
+    server::TokenExpiryPolicy, unix_now, Downstream, GeneralSession, SessionExpiry, StreamGuard,
+};
+use overlay_mcp_session_manager::Session;
+use rmcp::model::ServerJsonRpcMessage;
+
+/// How long to wait before reading a token expiry that failed to read again.
+const TOKEN_EXPIRY_RETRY: Duration = Duration::from_secs(1);
+
+/// Server messages a paused stream withholds until the token is renewed, it closes beyond.
+const WITHHELD_LIMIT: usize = 64;
+
+/// Server messages of `session` until it closes, the caller's jwt expiry is applied by `policy`.
+pub fn session_stream(
+    session: Session,
+    downstream: StreamGuard<Downstream>,
+    policy: TokenExpiryPolicy,
+) -> impl Stream<Item = ServerJsonRpcMessage> {
+    async_stream::stream! {
+        let mut recv = downstream;
+        let closed = session.closed();
+        let expired = token_expired(session.clone());
+        tokio::pin!(closed);
+        tokio::pin!(expired);
+        'stream: loop {
+            // messages sent before the session closed, like the expiry notice, go out first
+            let message = tokio::select! {
+                biased;
+                _ = &mut expired => None,
+                message = recv.recv() => match message {
+                    Ok(message) => Some(message),
+                    Err(_) => break,
+                },
+                _ = &mut closed => break,
+            };
+            if let Some(message) = message {
+                yield message;
+                continue;
+            }
+
+            tracing::info!(session_id = %session.session_id(), ?policy, "token expired");
+            if policy == TokenExpiryPolicy::Close {
+                yield SessionExpiry::TokenExpired.notification();
+                break;
+            }
+            yield SessionExpiry::TokenRenewalRequired.notification();
+            // extended by a request on any node
+            let mut token_expiry = session.watch_token_expiry();
+            let mut withheld = VecDeque::new();
+            loop {
+                let message = tokio::select! {
+                    renewed = token_expiry.wait_for(|expires_at| *expires_at > unix_now()) => {
+                        match renewed {
+                            Ok(_) => break,
+                            Err(_) => break 'stream,
+                        }
+                    }
+                    message = recv.recv() => match message {
+                        Ok(message) => message,
+                        Err(_) => break 'stream,
+                    },
+                    _ = &mut closed => break 'stream,
+                };
+                if withheld.len() == WITHHELD_LIMIT {
+                    tracing::info!(session_id = %session.session_id(), "too many messages withheld");
+                    yield SessionExpiry::TokenExpired.notification();
+                    break 'stream;
+                }
+                withheld.push_back(message);
+            }
+            for message in withheld {
+                yield message;
+            }
+            expired.set(token_expired(session.clone()));
+        }
+    }
+}
+
+async fn token_expired(session: Session) {
+    loop {
+        match session.token_expiry().await {
+            Ok(Some(expires_at)) if expires_at <= unix_now() => return,
+            Ok(Some(expires_at)) => {
+                // a later token may have renewed it meanwhile
+                tokio::time::sleep(Duration::from_secs(expires_at.saturating_sub(unix_now())))
+                    .await;
+            }
+            // api keys do not expire
+            Ok(None) => std::future::pending().await,
+            Err(err) => {
+                tracing::error!(session_id = %session.session_id(), "failed to read token expiry: {:?}", err);
+                tokio::time::sleep(TOKEN_EXPIRY_RETRY).await;
+            }
+        }
+    }
+}
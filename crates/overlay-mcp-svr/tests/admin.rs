@@ -0,0 +1,108 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use serde_json::{json, Value};
+
+const ADMIN_KEY: &str = "admin-apikey";
+
+async fn admin_overlay(cluster: Value) -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, cluster);
+    config.application.admin =
+        Some(serde_json::from_value(json!({ "apikeys": [ADMIN_KEY] })).unwrap());
+    common::overlay(config).await
+}
+
+async fn admin(method: reqwest::Method, url: String, apikey: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .request(method, url)
+        .header("X-API-KEY", apikey)
+        .send()
+        .await
+        .unwrap()
+}
+
+fn session_id(client: &common::Client) -> String {
+    client
+        .endpoint()
+        .split_once("session_id=")
+        .unwrap()
+        .1
+        .to_string()
+}
+
+/// Lists the sessions of two clients, closes one by id and the other by subject.
+async fn assert_sessions_administered(overlay: SocketAddr, node: Value) {
+    let sessions_url = format!("http://{}/.meta/sessions", overlay);
+    let mut first = common::Client::connect(overlay).await;
+    first.initialize().await;
+    let mut second = common::Client::connect(overlay).await;
+    second.initialize().await;
+
+    let denied = admin(reqwest::Method::GET, sessions_url.clone(), common::API_KEY).await;
+    assert_eq!(denied.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let sessions: Vec<Value> = admin(reqwest::Method::GET, sessions_url.clone(), ADMIN_KEY)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let listed = sessions
+        .iter()
+        .find(|session| session["session_id"] == session_id(&first))
+        .expect("session not listed");
+    assert_eq!(listed["started"], true);
+    assert_eq!(listed["node"], node);
+    assert!(listed["upstream"].as_str().unwrap().ends_with("/mcp"));
+    assert!(listed["created_at"].as_u64().unwrap() > 0);
+    let subject = listed["principal"]["api-key"]["fingerprint"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let closed = admin(
+        reqwest::Method::DELETE,
+        format!("{}/{}", sessions_url, session_id(&first)),
+        ADMIN_KEY,
+    )
+    .await;
+    assert_eq!(closed.status(), reqwest::StatusCode::NO_CONTENT);
+    let ping = json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" });
+    assert_eq!(
+        first.send_as(common::API_KEY, ping.clone()).await,
+        reqwest::StatusCode::NOT_FOUND
+    );
+
+    let missing = admin(reqwest::Method::DELETE, sessions_url.clone(), ADMIN_KEY).await;
+    assert_eq!(missing.status(), reqwest::StatusCode::BAD_REQUEST);
+    let closed: Value = admin(
+        reqwest::Method::DELETE,
+        format!("{}?subject={}", sessions_url, subject),
+        ADMIN_KEY,
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(closed["closed"], json!([session_id(&second)]));
+    assert_eq!(
+        second.send_as(common::API_KEY, ping).await,
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn standalone_sessions_administered() {
+    let overlay = admin_overlay(json!({ "type": "none" })).await;
+    assert_sessions_administered(overlay, Value::Null).await;
+}
+
+#[tokio::test]
+async fn raft_sessions_administered() {
+    let data_dir = std::env::temp_dir().join(format!("overlay-mcp-admin-{}", std::process::id()));
+    let overlay = admin_overlay(common::raft_cluster(&data_dir).await).await;
+    assert_sessions_administered(overlay, json!(1)).await;
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
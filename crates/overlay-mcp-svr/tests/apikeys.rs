@@ -0,0 +1,218 @@
+mod common;
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use overlay_mcp_core::AuthConfig;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const CI_SECRET: &str = "ci-secret";
+const OPS_SECRET: &str = "ops-secret";
+// argon2 keys are presented with their id
+const OPS_KEY: &str = "ops.ops-secret";
+const EXPIRED_SECRET: &str = "expired-secret";
+const OTHER_TOOL: &str = "other";
+
+fn sha256(secret: &str) -> String {
+    let digest = Sha256::digest(secret.as_bytes());
+    format!(
+        "sha256:{}",
+        digest
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    )
+}
+
+fn argon2(secret: &str) -> String {
+    let salt = SaltString::from_b64("c29tZXNhbHRzb21lc2FsdA").unwrap();
+    Argon2::default()
+        .hash_password(secret.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+/// OpenFGA store allowing everything, recording the users it was checked for.
+async fn recording_openfga(users: Arc<Mutex<Vec<String>>>) -> SocketAddr {
+    async fn stores() -> Json<Value> {
+        Json(json!({
+            "stores": [{
+                "name": "test",
+                "id": "store",
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z",
+            }],
+            "continuation_token": "",
+        }))
+    }
+    async fn check(
+        State(users): State<Arc<Mutex<Vec<String>>>>,
+        Path(_store): Path<String>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        let user = body["tuple_key"]["user"].as_str().unwrap_or_default();
+        users.lock().unwrap().push(user.to_string());
+        Json(json!({ "allowed": true, "resolution": "" }))
+    }
+    async fn batch_check(Path(_store): Path<String>, Json(body): Json<Value>) -> Json<Value> {
+        let result = body["checks"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .iter()
+            .map(|check| {
+                let id = check["correlation_id"].as_str().unwrap_or_default();
+                (id.to_string(), json!({ "allowed": true }))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        Json(json!({ "result": result }))
+    }
+    let router = Router::new()
+        .route("/stores", get(stores))
+        .route("/stores/{store}/check", post(check))
+        .route("/stores/{store}/batch-check", post(batch_check))
+        .with_state(users);
+    common::serve(router).await
+}
+
+async fn apikeys_overlay(users: Arc<Mutex<Vec<String>>>) -> (SocketAddr, common::Observed) {
+    let (upstream, observed) =
+        common::mock_upstream_with_tools(&[common::ALLOWED_TOOL, OTHER_TOOL]).await;
+    let openfga = recording_openfga(users).await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.apikey.keys = serde_json::from_value(json!([
+        {
+            "id": "ci",
+            "owner": "platform",
+            "hash": sha256(CI_SECRET),
+            "tools": [common::ALLOWED_TOOL],
+        },
+        { "id": "ops", "hash": argon2(OPS_SECRET) },
+        {
+            "id": "expired",
+            "hash": sha256(EXPIRED_SECRET),
+            "expires_at": "2020-01-01T00:00:00Z",
+        },
+    ]))
+    .unwrap();
+    (common::overlay(config).await, observed)
+}
+
+async fn connects_as(overlay: SocketAddr, apikey: &str) -> bool {
+    reqwest::Client::new()
+        .get(format!("http://{}/sse", overlay))
+        .header("X-API-KEY", apikey)
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .is_success()
+}
+
+#[tokio::test]
+async fn hashed_keys_authorized_by_id() {
+    let users = Arc::new(Mutex::new(Vec::new()));
+    let (overlay, _) = apikeys_overlay(users.clone()).await;
+
+    let mut client = common::Client::connect_as(overlay, CI_SECRET).await;
+    client.initialize().await;
+    let mut ops = common::Client::connect_as(overlay, OPS_KEY).await;
+    ops.initialize().await;
+
+    let users = users.lock().unwrap().clone();
+    assert!(users.contains(&"apikey:ci".to_string()), "{:?}", users);
+    assert!(users.contains(&"apikey:ops".to_string()), "{:?}", users);
+    assert!(
+        !users
+            .iter()
+            .any(|user| user.contains(CI_SECRET) || user.contains(OPS_SECRET)),
+        "secret used as fga user: {:?}",
+        users
+    );
+}
+
+#[tokio::test]
+async fn unknown_or_expired_keys_rejected() {
+    let (overlay, _) = apikeys_overlay(Arc::default()).await;
+    assert!(connects_as(overlay, CI_SECRET).await);
+    assert!(!connects_as(overlay, common::API_KEY).await);
+    assert!(!connects_as(overlay, EXPIRED_SECRET).await);
+    // argon2 keys are only verified against the key of their id
+    assert!(connects_as(overlay, OPS_KEY).await);
+    assert!(!connects_as(overlay, OPS_SECRET).await);
+    assert!(!connects_as(overlay, "ci.ops-secret").await);
+    assert!(!connects_as(overlay, "unknown.ops-secret").await);
+}
+
+#[tokio::test]
+async fn key_tool_allowlist_applied() {
+    let (overlay, observed) = apikeys_overlay(Arc::default()).await;
+    let mut client = common::Client::connect_as(overlay, CI_SECRET).await;
+    client.initialize().await;
+
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list", "params": {} }))
+        .await;
+    let listed = client.recv().await;
+    let tools = listed["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(tools, vec![common::ALLOWED_TOOL.to_string()]);
+
+    let denied = client.call_tool(3, OTHER_TOOL).await;
+    assert_eq!(denied["error"]["code"], -32003);
+    let allowed = client.call_tool(4, common::ALLOWED_TOOL).await;
+    assert!(allowed.get("result").is_some(), "unexpected {}", allowed);
+    assert!(!observed
+        .snapshot()
+        .contains(&format!("tools/call:{}", OTHER_TOOL)));
+}
+
+#[tokio::test]
+async fn static_whitelist_lists_key_ids() {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    let mut authn = authn.clone();
+    authn.apikey.keys = serde_json::from_value(json!([
+        { "id": "ci", "hash": sha256(CI_SECRET) },
+        { "id": "ops", "hash": argon2(OPS_SECRET) },
+    ]))
+    .unwrap();
+    config.auth = AuthConfig::Static {
+        authn: authn.clone(),
+        constant: serde_json::from_value(json!({ "apikey": { "whitelist": ["ci"] }, "jwt": [] }))
+            .unwrap(),
+    };
+    let overlay = common::overlay(config.clone()).await;
+    assert!(connects_as(overlay, CI_SECRET).await);
+    assert!(!connects_as(overlay, OPS_KEY).await);
+
+    // an empty whitelist denies every key, registered or not
+    config.auth = AuthConfig::Static {
+        authn,
+        constant: serde_json::from_value(json!({ "apikey": { "whitelist": [] }, "jwt": [] }))
+            .unwrap(),
+    };
+    let overlay = common::overlay(config).await;
+    assert!(!connects_as(overlay, CI_SECRET).await);
+    assert!(!connects_as(overlay, OPS_KEY).await);
+}
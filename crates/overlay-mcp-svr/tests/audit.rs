@@ -0,0 +1,108 @@
+mod common;
+
+use std::{path::Path, time::Duration};
+
+use serde_json::{json, Value};
+
+/// Records in the audit log once `event` is written, waits for the writer thread.
+async fn records_until(path: &Path, event: &str) -> Vec<Value> {
+    for _ in 0..100 {
+        let records = std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        if records.iter().any(|record| record["event"] == event) {
+            return records;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{} never audited", event);
+}
+
+fn find<'a>(records: &'a [Value], event: &str, field: &str, value: Value) -> &'a Value {
+    records
+        .iter()
+        .find(|record| record["event"] == event && record[field] == value)
+        .unwrap_or_else(|| panic!("{} with {} {} not audited", event, field, value))
+}
+
+#[tokio::test]
+async fn session_decisions_and_tool_calls_audited() {
+    let path = std::env::temp_dir().join(format!("overlay-mcp-audit-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.application.audit = Some(
+        serde_json::from_value(json!({
+            "sink": { "type": "file", "path": path },
+            "redact": ["/password"],
+        }))
+        .unwrap(),
+    );
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": common::ALLOWED_TOOL,
+                "arguments": { "user": "alice", "password": "hunter2" },
+            },
+        }))
+        .await;
+    assert_eq!(client.recv().await["id"], 2);
+    assert_eq!(client.call_tool(3, common::FORBIDDEN_TOOL).await["id"], 3);
+    let session_id = client
+        .endpoint()
+        .split_once("session_id=")
+        .unwrap()
+        .1
+        .to_string();
+    drop(client);
+
+    let records = records_until(&path, "session_close").await;
+    let opened = find(&records, "session_open", "session_id", json!(session_id));
+    assert!(opened["upstream"].as_str().unwrap().ends_with("/mcp"));
+    assert!(opened["principal"]["api-key"]["fingerprint"].is_string());
+    assert!(opened["timestamp"].as_u64().unwrap() > 0);
+    find(&records, "session_close", "session_id", json!(session_id));
+    assert_eq!(
+        find(
+            &records,
+            "authorize_enter",
+            "event",
+            json!("authorize_enter")
+        )["result"],
+        "allow"
+    );
+
+    let allowed = find(&records, "authorize_client_message", "id", json!(2));
+    assert_eq!(allowed["result"], "allow");
+    assert_eq!(allowed["tool"], common::ALLOWED_TOOL);
+    assert_eq!(
+        allowed["arguments"],
+        json!({ "user": "alice", "password": "[REDACTED]" })
+    );
+    let denied = find(&records, "authorize_client_message", "id", json!(3));
+    assert_eq!(denied["result"], "deny");
+    assert_eq!(denied["tool"], common::FORBIDDEN_TOOL);
+
+    let called = find(&records, "tool_call", "id", json!(2));
+    assert_eq!(called["session_id"], session_id);
+    assert_eq!(called["result"], "ok");
+    assert_eq!(called["arguments"]["password"], "[REDACTED]");
+    assert!(called["duration_ms"].is_u64());
+    assert!(
+        !records
+            .iter()
+            .any(|record| record["event"] == "tool_call" && record["id"] == 3),
+        "denied call reached upstream"
+    );
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use std::{collections::HashMap, net::SocketAddr};
+
+use serde_json::json;
+use url::Url;
+
+const REDIRECT_URI: &str = "http://127.0.0.1/callback";
+const CODE_CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+async fn overlay_with_client() -> (SocketAddr, String) {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let overlay =
+        common::overlay(common::config(upstream, openfga, json!({ "type": "none" }))).await;
+    let client = common::register_client(overlay, json!({ "redirect_uris": [REDIRECT_URI] })).await;
+    (overlay, client["client_id"].as_str().unwrap().to_string())
+}
+
+/// Location `/authorize` redirects to, and its query.
+async fn authorize(overlay: SocketAddr, query: &[(&str, &str)]) -> (Url, Vec<(String, String)>) {
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("http://{}/authorize", overlay))
+        .query(query)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection(), "{}", response.status());
+    let location = Url::parse(
+        response.headers()[reqwest::header::LOCATION]
+            .to_str()
+            .unwrap(),
+    )
+    .unwrap();
+    let pairs = location
+        .query_pairs()
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    (location, pairs)
+}
+
+#[tokio::test]
+async fn state_scope_and_resource_are_forwarded() {
+    let (overlay, client_id) = overlay_with_client().await;
+
+    let (location, pairs) = authorize(
+        overlay,
+        &[
+            ("response_type", "code"),
+            ("client_id", &client_id),
+            ("code_challenge", CODE_CHALLENGE),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", REDIRECT_URI),
+            ("state", "client-state"),
+            ("scope", "read write"),
+            ("resource", "http://127.0.0.1/mcp"),
+            ("resource", "http://127.0.0.1/other"),
+        ],
+    )
+    .await;
+    assert_eq!(location.path(), "/authorize");
+    let query = pairs.iter().cloned().collect::<HashMap<_, _>>();
+    assert_eq!(query["state"], "client-state");
+    assert_eq!(query["scope"], "read write");
+    assert_eq!(query["code_challenge"], CODE_CHALLENGE);
+    assert_eq!(query["code_challenge_method"], "S256");
+    let resources = pairs
+        .iter()
+        .filter(|(key, _)| key == "resource")
+        .map(|(_, value)| value.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        resources,
+        ["http://127.0.0.1/mcp", "http://127.0.0.1/other"]
+    );
+
+    // no state invented for clients sending none
+    let (_, pairs) = authorize(
+        overlay,
+        &[
+            ("response_type", "code"),
+            ("client_id", &client_id),
+            ("code_challenge", CODE_CHALLENGE),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", REDIRECT_URI),
+        ],
+    )
+    .await;
+    assert!(pairs.iter().all(|(key, _)| key != "state"));
+}
+
+#[tokio::test]
+async fn malformed_pkce_is_redirected_as_error() {
+    let (overlay, client_id) = overlay_with_client().await;
+
+    for (challenge, method) in [
+        (None, Some("S256")),
+        (Some(CODE_CHALLENGE), None),
+        (Some(CODE_CHALLENGE), Some("plain")),
+        (Some("too-short"), Some("S256")),
+        (
+            Some("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw+cM"),
+            Some("S256"),
+        ),
+    ] {
+        let mut query = vec![
+            ("response_type", "code"),
+            ("client_id", client_id.as_str()),
+            ("redirect_uri", REDIRECT_URI),
+            ("state", "client-state"),
+        ];
+        query.extend(challenge.map(|challenge| ("code_challenge", challenge)));
+        query.extend(method.map(|method| ("code_challenge_method", method)));
+
+        let (location, pairs) = authorize(overlay, &query).await;
+        assert!(location.as_str().starts_with(REDIRECT_URI), "{}", location);
+        let query = pairs.into_iter().collect::<HashMap<_, _>>();
+        assert_eq!(
+            query["error"],
+            "invalid_request",
+            "{:?}",
+            (challenge, method)
+        );
+        assert_eq!(query["state"], "client-state");
+    }
+
+    let (location, pairs) = authorize(
+        overlay,
+        &[
+            ("response_type", "token"),
+            ("client_id", &client_id),
+            ("code_challenge", CODE_CHALLENGE),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", REDIRECT_URI),
+        ],
+    )
+    .await;
+    assert!(location.as_str().starts_with(REDIRECT_URI), "{}", location);
+    let query = pairs.into_iter().collect::<HashMap<_, _>>();
+    assert_eq!(query["error"], "unsupported_response_type");
+}
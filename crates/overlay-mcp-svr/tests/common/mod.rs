@@ -0,0 +1,551 @@
+#![allow(dead_code)]
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{sse::Event as SseEvent, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::{stream::BoxStream, StreamExt};
+use overlay_mcp_core::Config;
+use serde_json::{json, Value};
+use sse_stream::{Sse, SseStream};
+use tokio::{net::TcpListener, sync::broadcast};
+use tokio_util::sync::CancellationToken;
+
+pub const API_KEY: &str = "test-apikey";
+pub const FORBIDDEN_TOOL: &str = "forbidden";
+pub const ALLOWED_TOOL: &str = "allowed";
+
+/// Every message received by the mock upstream, as `method` or `method:tool`, with its headers.
+#[derive(Clone, Default)]
+pub struct Observed(Arc<Mutex<Vec<(String, HeaderMap)>>>);
+
+impl Observed {
+    pub fn snapshot(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(record, _)| record.clone())
+            .collect()
+    }
+
+    pub fn headers(&self) -> Vec<HeaderMap> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, headers)| headers.clone())
+            .collect()
+    }
+}
+
+pub async fn serve(router: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+    addr
+}
+
+fn mock_result(method: &str, tools: &[String]) -> Value {
+    match method {
+        "initialize" => json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "mock", "version": "0.0.0" },
+        }),
+        "tools/list" => {
+            let tools = tools
+                .iter()
+                .map(|name| {
+                    json!({
+                        "name": name,
+                        "description": "",
+                        "inputSchema": { "type": "object" },
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({ "tools": tools })
+        }
+        // prompts and resources are named like the tools
+        "prompts/list" => {
+            let prompts = tools
+                .iter()
+                .map(|name| json!({ "name": name }))
+                .collect::<Vec<_>>();
+            json!({ "prompts": prompts })
+        }
+        "resources/list" => {
+            let resources = tools
+                .iter()
+                .map(|name| json!({ "uri": format!("file:///{}", name), "name": name }))
+                .collect::<Vec<_>>();
+            json!({ "resources": resources })
+        }
+        "tools/call" => json!({
+            "content": [{ "type": "text", "text": "ok" }],
+            "isError": false,
+        }),
+        "prompts/get" => json!({
+            "messages": [{ "role": "user", "content": { "type": "text", "text": "ok" } }],
+        }),
+        "resources/read" => json!({
+            "contents": [{ "uri": "file:///ok", "text": "ok" }],
+        }),
+        _ => json!({}),
+    }
+}
+
+/// Legacy HTTP with SSE MCP server on `/sse`, answering messages POSTed to `/message` on the
+/// event stream and recording them like [`mock_upstream`].
+pub async fn mock_sse_upstream() -> (SocketAddr, Observed) {
+    type Answers = broadcast::Sender<Value>;
+
+    async fn listen(State((_, answers)): State<(Observed, Answers)>) -> Response {
+        let endpoint = futures::stream::once(async {
+            Ok::<_, Infallible>(SseEvent::default().event("endpoint").data("/message"))
+        });
+        let answers = futures::stream::unfold(answers.subscribe(), |mut answers| async move {
+            let answer = answers.recv().await.ok()?;
+            let event = SseEvent::default()
+                .event("message")
+                .data(answer.to_string());
+            Some((Ok(event), answers))
+        });
+        axum::response::Sse::new(endpoint.chain(answers)).into_response()
+    }
+
+    async fn handle(
+        State((observed, answers)): State<(Observed, Answers)>,
+        headers: HeaderMap,
+        Json(message): Json<Value>,
+    ) -> StatusCode {
+        let method = message["method"].as_str().unwrap_or_default().to_string();
+        observed.0.lock().unwrap().push((method.clone(), headers));
+        if let Some(id) = message.get("id").cloned() {
+            let result = mock_result(&method, &[ALLOWED_TOOL.to_string()]);
+            let _ = answers.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        }
+        StatusCode::ACCEPTED
+    }
+
+    let observed = Observed::default();
+    let (answers, _) = broadcast::channel(16);
+    let router = Router::new()
+        .route("/sse", get(listen))
+        .route("/message", post(handle))
+        .with_state((observed.clone(), answers));
+    (serve(router).await, observed)
+}
+
+/// Streamable HTTP MCP server answering every request and recording what it saw.
+pub async fn mock_upstream() -> (SocketAddr, Observed) {
+    mock_upstream_with_tools(&[ALLOWED_TOOL, FORBIDDEN_TOOL]).await
+}
+
+/// [`mock_upstream`] listing `tools` on `tools/list`.
+pub async fn mock_upstream_with_tools(tools: &[&str]) -> (SocketAddr, Observed) {
+    async fn handle(
+        State((observed, tools)): State<(Observed, Arc<Vec<String>>)>,
+        headers: HeaderMap,
+        Json(message): Json<Value>,
+    ) -> Response {
+        let method = message["method"].as_str().unwrap_or_default().to_string();
+        let record = match (&*method, &message["params"]) {
+            ("tools/call" | "prompts/get", params) if params["name"].is_string() => {
+                format!("{}:{}", method, params["name"].as_str().unwrap())
+            }
+            ("resources/read", params) if params["uri"].is_string() => {
+                format!("{}:{}", method, params["uri"].as_str().unwrap())
+            }
+            _ => method.clone(),
+        };
+        observed.0.lock().unwrap().push((record, headers));
+
+        let Some(id) = message.get("id").cloned() else {
+            return StatusCode::ACCEPTED.into_response();
+        };
+        let result = mock_result(&method, &tools);
+        (
+            [("mcp-session-id", "mock-session")],
+            Json(json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+        )
+            .into_response()
+    }
+
+    let observed = Observed::default();
+    let router = Router::new()
+        .route(
+            "/mcp",
+            post(handle)
+                .get(|| async { StatusCode::METHOD_NOT_ALLOWED })
+                .delete(|| async { StatusCode::OK }),
+        )
+        .with_state((
+            observed.clone(),
+            Arc::new(tools.iter().map(|tool| tool.to_string()).collect()),
+        ));
+    (serve(router).await, observed)
+}
+
+/// OpenFGA store allowing everything except objects named [`FORBIDDEN_TOOL`].
+pub async fn mock_openfga() -> SocketAddr {
+    async fn stores() -> Json<Value> {
+        Json(json!({
+            "stores": [{
+                "name": "test",
+                "id": "store",
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z",
+            }],
+            "continuation_token": "",
+        }))
+    }
+    fn allowed(tuple_key: &Value) -> bool {
+        let object = tuple_key["object"].as_str().unwrap_or_default();
+        !object.ends_with(&format!("/{}", FORBIDDEN_TOOL))
+    }
+    // object ids openfga accepts, anything else fails the whole request
+    fn valid(tuple_key: &Value) -> bool {
+        let object = tuple_key["object"].as_str().unwrap_or_default();
+        object.len() <= 256
+            && object.split_once(':').is_some_and(|(_, id)| {
+                !id.is_empty() && !id.contains(|c: char| c.is_whitespace() || c == '#' || c == ':')
+            })
+    }
+    fn invalid_object() -> Response {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "code": "validation_error", "message": "invalid object" })),
+        )
+            .into_response()
+    }
+    async fn check(Path(_store): Path<String>, Json(body): Json<Value>) -> Response {
+        if !valid(&body["tuple_key"]) {
+            return invalid_object();
+        }
+        let allowed = allowed(&body["tuple_key"]);
+        Json(json!({ "allowed": allowed, "resolution": "" })).into_response()
+    }
+    async fn batch_check(Path(_store): Path<String>, Json(body): Json<Value>) -> Response {
+        let checks = body["checks"].as_array().cloned().unwrap_or_default();
+        // openfga's default max_checks_per_batch_check
+        if checks.len() > 50 {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "code": "validation_error", "message": "too many checks" })),
+            )
+                .into_response();
+        }
+        if !checks.iter().all(|check| valid(&check["tuple_key"])) {
+            return invalid_object();
+        }
+        let result = checks
+            .iter()
+            .map(|check| {
+                let id = check["correlation_id"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                (id, json!({ "allowed": allowed(&check["tuple_key"]) }))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        Json(json!({ "result": result })).into_response()
+    }
+
+    let router = Router::new()
+        .route("/stores", get(stores))
+        .route("/stores/{store}/check", post(check))
+        .route("/stores/{store}/batch-check", post(batch_check));
+    serve(router).await
+}
+
+pub fn config(upstream: SocketAddr, openfga: SocketAddr, cluster: Value) -> Config {
+    serde_json::from_value(json!({
+        "application": {
+            "prometheus": false,
+            "health_check": false,
+            "passthrough": [],
+        },
+        "server": {
+            "addr": "127.0.0.1:0",
+            "hostname": "http://127.0.0.1",
+            "cluster": cluster,
+        },
+        "upstream": {
+            "urls": [format!("http://{}/mcp", upstream)],
+            "transport": "streamable-http",
+        },
+        "auth": {
+            "authn": {
+                "apikey": { "key_from": ["header:X-API-KEY"] },
+                "jwt": {
+                    "type": "oauth2",
+                    "issuer": "http://127.0.0.1/issuer",
+                    "auth_url": "http://127.0.0.1/authorize",
+                    "token_url": "http://127.0.0.1/token",
+                    "verifier": "no-check",
+                    "client": { "id": "client", "secret": "secret", "scopes": [] },
+                },
+            },
+            "openfga": {
+                "url": format!("http://{}", openfga),
+                "store": "test",
+                "check": { "group": "mcp", "relation": "allow" },
+                "apikey": { "group": "apikey" },
+                "jwt": { "group": "jwt", "claim_path": "/sub", "context_fields": [] },
+            },
+        },
+    }))
+    .expect("invalid test config")
+}
+
+/// Single node raft cluster keeping its data in `data_dir`.
+pub async fn raft_cluster(data_dir: &std::path::Path) -> Value {
+    async fn free_addr() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+    json!({
+        "type": "raft",
+        "id": 1,
+        "secret": "overlay-mcp-test-secret",
+        "data_dir": data_dir,
+        "nodes": [{ "id": 1, "api": free_addr().await, "raft": free_addr().await }],
+    })
+}
+
+pub async fn overlay(config: Config) -> SocketAddr {
+    let cancel = CancellationToken::new();
+    let router = overlay_mcp_svr::router::router(cancel, config)
+        .await
+        .expect("failed to build overlay router");
+    serve(router).await
+}
+
+/// Legacy SSE client connected to overlay.
+pub struct Client {
+    http: reqwest::Client,
+    // header the session was opened with
+    credential: (&'static str, String),
+    base: String,
+    endpoint: String,
+    events: BoxStream<'static, Result<Sse, sse_stream::Error>>,
+}
+
+impl Client {
+    pub async fn connect(overlay: SocketAddr) -> Self {
+        Self::connect_as(overlay, API_KEY).await
+    }
+
+    pub async fn connect_as(overlay: SocketAddr, apikey: &str) -> Self {
+        Self::connect_with(overlay, ("X-API-KEY", apikey.to_string()), &[]).await
+    }
+
+    pub async fn connect_bearer(overlay: SocketAddr, token: &str) -> Self {
+        Self::connect_with(overlay, ("Authorization", format!("Bearer {}", token)), &[]).await
+    }
+
+    /// Connects with the test api key and `headers` on the request starting the session.
+    pub async fn connect_with_headers(overlay: SocketAddr, headers: &[(&str, &str)]) -> Self {
+        Self::connect_with(overlay, ("X-API-KEY", API_KEY.to_string()), headers).await
+    }
+
+    async fn connect_with(
+        overlay: SocketAddr,
+        credential: (&'static str, String),
+        headers: &[(&str, &str)],
+    ) -> Self {
+        let http = reqwest::Client::new();
+        let base = format!("http://{}", overlay);
+        let mut request = http
+            .get(format!("{}/sse", base))
+            .header(credential.0, &credential.1);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = request.send().await.unwrap().error_for_status().unwrap();
+        let mut events = SseStream::from_byte_stream(response.bytes_stream()).boxed();
+        let endpoint = loop {
+            let event = events.next().await.unwrap().unwrap();
+            if event.event.as_deref() == Some("endpoint") {
+                break event.data.unwrap();
+            }
+        };
+        Self {
+            http,
+            credential,
+            base,
+            endpoint,
+            events,
+        }
+    }
+
+    pub async fn send(&self, message: Value) {
+        let (name, value) = &self.credential;
+        let status = self.send_with(name, value, message).await;
+        assert_eq!(status, reqwest::StatusCode::ACCEPTED);
+    }
+
+    pub async fn send_as(&self, apikey: &str, message: Value) -> reqwest::StatusCode {
+        self.send_with("X-API-KEY", apikey, message).await
+    }
+
+    pub async fn send_bearer(&self, token: &str, message: Value) -> reqwest::StatusCode {
+        self.send_with("Authorization", &format!("Bearer {}", token), message)
+            .await
+    }
+
+    async fn send_with(&self, name: &str, value: &str, message: Value) -> reqwest::StatusCode {
+        self.http
+            .post(format!("{}{}", self.base, self.endpoint))
+            .header(name, value)
+            .json(&message)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub async fn recv(&mut self) -> Value {
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(10), self.events.next())
+                .await
+                .expect("no message from overlay")
+                .unwrap()
+                .unwrap();
+            if let Some(data) = event.data {
+                return serde_json::from_str(&data).unwrap();
+            }
+        }
+    }
+
+    /// Whether overlay ends the event stream.
+    pub async fn ended(&mut self) -> bool {
+        let ended = async {
+            loop {
+                match self.events.next().await {
+                    Some(Ok(_)) => continue,
+                    Some(Err(_)) | None => return,
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), ended)
+            .await
+            .is_ok()
+    }
+
+    pub async fn initialize(&mut self) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0.0.0" },
+            },
+        }))
+        .await;
+        let response = self.recv().await;
+        assert_eq!(response["id"], 1);
+        self.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+    }
+
+    pub async fn call_tool(&mut self, id: u32, name: &str) -> Value {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": {} },
+        }))
+        .await;
+        self.recv().await
+    }
+}
+
+/// Unsigned jwt for the `no-check` verifier of the test config.
+pub fn jwt(subject: &str, expires_at: u64) -> String {
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &json!({ "iss": "http://127.0.0.1/issuer", "sub": subject, "exp": expires_at }),
+        &jsonwebtoken::EncodingKey::from_secret(b"unchecked"),
+    )
+    .unwrap()
+}
+
+/// `true` if an sse connection opened with the bearer `token` is accepted.
+pub async fn connects(overlay: SocketAddr, token: &str) -> bool {
+    reqwest::Client::new()
+        .get(format!("http://{}/sse", overlay))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .is_success()
+}
+
+/// Client registered at `/register` with the metadata of `body`.
+pub async fn register_client(overlay: SocketAddr, body: Value) -> Value {
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/register", overlay))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    response.json().await.unwrap()
+}
+
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Denied call must be answered by overlay itself and never be observed upstream.
+pub async fn assert_denied_call_not_forwarded(overlay: SocketAddr, observed: &Observed) {
+    let mut client = Client::connect(overlay).await;
+    client.initialize().await;
+
+    let denied = client.call_tool(2, FORBIDDEN_TOOL).await;
+    assert_eq!(denied["id"], 2);
+    assert_eq!(denied["error"]["code"], -32003);
+    assert_eq!(denied["error"]["data"]["reason"], "policy_denied");
+    assert_eq!(denied["error"]["data"]["method"], "tools/call");
+
+    let allowed = client.call_tool(3, ALLOWED_TOOL).await;
+    assert_eq!(allowed["id"], 3);
+    assert!(allowed.get("result").is_some(), "unexpected {}", allowed);
+
+    let observed = observed.snapshot();
+    assert!(observed.contains(&format!("tools/call:{}", ALLOWED_TOOL)));
+    assert!(
+        !observed.contains(&format!("tools/call:{}", FORBIDDEN_TOOL)),
+        "denied call reached upstream: {:?}",
+        observed
+    );
+}
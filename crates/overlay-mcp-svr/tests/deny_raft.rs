@@ -0,0 +1,14 @@
+mod common;
+
+#[tokio::test]
+async fn denied_client_message_never_reaches_upstream() {
+    let data_dir = std::env::temp_dir().join(format!("overlay-mcp-raft-{}", std::process::id()));
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let cluster = common::raft_cluster(&data_dir).await;
+    let config = common::config(upstream, openfga, cluster);
+    let overlay = common::overlay(config).await;
+
+    common::assert_denied_call_not_forwarded(overlay, &observed).await;
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
@@ -0,0 +1,13 @@
+mod common;
+
+use serde_json::json;
+
+#[tokio::test]
+async fn denied_client_message_never_reaches_upstream() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    common::assert_denied_call_not_forwarded(overlay, &observed).await;
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use overlay_mcp_core::{AuthConfig, UpstreamConfig};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const KEY_SECRET: &str = "known-secret";
+const EXPIRED_SECRET: &str = "expired-secret";
+
+fn sha256(secret: &str) -> String {
+    let digest = Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("sha256:{}", digest)
+}
+
+async fn overlay_with_upstream(upstream: SocketAddr) -> SocketAddr {
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.apikey.keys = serde_json::from_value(json!([
+        { "id": "known", "hash": sha256(KEY_SECRET) },
+        { "id": "expired", "hash": sha256(EXPIRED_SECRET), "expires_at": "2020-01-01T00:00:00Z" },
+    ]))
+    .unwrap();
+    common::overlay(config).await
+}
+
+/// Status, `x-request-id` and problem details of the response.
+async fn problem(request: reqwest::RequestBuilder) -> (reqwest::StatusCode, String, Value) {
+    let response = request.send().await.unwrap();
+    let status = response.status();
+    let request_id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        response.headers()[reqwest::header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+    (status, request_id, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn authentication_errors_are_problems() {
+    let (upstream, _) = common::mock_upstream().await;
+    let overlay = overlay_with_upstream(upstream).await;
+    let sse = format!("http://{}/sse", overlay);
+
+    let (status, request_id, body) =
+        problem(reqwest::Client::new().get(&sse).bearer_auth("not-a-jwt")).await;
+    assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(body["status"], 401);
+    assert_eq!(body["code"], "invalid_token");
+    assert_eq!(body["request_id"], request_id);
+
+    let (status, _, body) = problem(
+        reqwest::Client::new()
+            .get(&sse)
+            .header(reqwest::header::AUTHORIZATION, "Basic dXNlcjpwYXNz"),
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bearer_token_expected");
+
+    // api keys are never asked to authenticate again, unknown ones look like denied ones
+    let mut bodies = Vec::new();
+    for apikey in ["unknown-secret", EXPIRED_SECRET] {
+        let (status, _, mut body) =
+            problem(reqwest::Client::new().get(&sse).header("X-API-KEY", apikey)).await;
+        assert_eq!(status, reqwest::StatusCode::FORBIDDEN);
+        body.as_object_mut().unwrap().remove("request_id");
+        bodies.push(body);
+    }
+    assert_eq!(bodies[0]["code"], "authorization_failed");
+    assert_eq!(bodies[0], bodies[1]);
+}
+
+#[tokio::test]
+async fn request_id_is_propagated() {
+    let (upstream, _) = common::mock_upstream().await;
+    let overlay = overlay_with_upstream(upstream).await;
+
+    let (status, request_id, body) = problem(
+        reqwest::Client::new()
+            .post(format!("http://{}/message?session_id=missing", overlay))
+            .header("X-API-KEY", KEY_SECRET)
+            .header("x-request-id", "caller-request")
+            .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" })),
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(request_id, "caller-request");
+    assert_eq!(body["code"], "session_not_found");
+    assert_eq!(body["request_id"], "caller-request");
+}
+
+#[tokio::test]
+async fn upstream_failures_are_gateway_errors() {
+    // openfga which knows the store but fails every check
+    async fn stores() -> Json<Value> {
+        Json(json!({
+            "stores": [{
+                "name": "test",
+                "id": "store",
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z",
+            }],
+            "continuation_token": "",
+        }))
+    }
+    async fn check() -> (StatusCode, Json<Value>) {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "code": "internal_error", "message": "database is gone" })),
+        )
+    }
+    let openfga = common::serve(
+        Router::new()
+            .route("/stores", get(stores))
+            .route("/stores/{store}/check", post(check)),
+    )
+    .await;
+    let (upstream, _) = common::mock_upstream().await;
+    let overlay =
+        common::overlay(common::config(upstream, openfga, json!({ "type": "none" }))).await;
+
+    let (status, _, body) = problem(
+        reqwest::Client::new()
+            .get(format!("http://{}/sse", overlay))
+            .header("X-API-KEY", common::API_KEY),
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::BAD_GATEWAY);
+    assert_eq!(body["code"], "upstream_error");
+    // internals of the failure are not exposed
+    assert!(!body["detail"].as_str().unwrap().contains("database"));
+}
+
+#[tokio::test]
+async fn missing_upstream_is_unavailable() {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let UpstreamConfig::Static(upstream) = &mut config.upstream else {
+        unreachable!("test config uses static upstreams");
+    };
+    upstream.urls.clear();
+    let overlay = common::overlay(config).await;
+
+    let (status, _, body) = problem(
+        reqwest::Client::new()
+            .get(format!("http://{}/sse", overlay))
+            .header("X-API-KEY", common::API_KEY),
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["code"], "no_upstream");
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use serde_json::{json, Value};
+
+async fn limited_overlay(
+    cluster: Value,
+    idle_timeout: Option<u64>,
+    max_lifetime: Option<u64>,
+) -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, cluster);
+    config.server.idle_timeout = idle_timeout;
+    config.server.max_lifetime = max_lifetime;
+    common::overlay(config).await
+}
+
+/// The client is told why before the session is closed.
+async fn assert_expired(client: &mut common::Client, reason: &str) {
+    let notification = client.recv().await;
+    assert_eq!(notification["method"], "notifications/message");
+    assert_eq!(notification["params"]["data"]["reason"], reason);
+    assert!(client.ended().await, "event stream not ended");
+    let ping = json!({ "jsonrpc": "2.0", "id": 99, "method": "ping" });
+    assert_eq!(
+        client.send_as(common::API_KEY, ping).await,
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+async fn assert_idle_session_expired(overlay: SocketAddr) {
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    // messages keep the session alive past the idle timeout
+    for id in 2..6 {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        client
+            .send(json!({ "jsonrpc": "2.0", "id": id, "method": "ping" }))
+            .await;
+        assert_eq!(client.recv().await["id"], id);
+    }
+    assert_expired(&mut client, "idle_timeout").await;
+}
+
+#[tokio::test]
+async fn standalone_idle_session_expires() {
+    let overlay = limited_overlay(json!({ "type": "none" }), Some(1), None).await;
+    assert_idle_session_expired(overlay).await;
+}
+
+#[tokio::test]
+async fn raft_idle_session_expires() {
+    let data_dir = std::env::temp_dir().join(format!("overlay-mcp-expiry-{}", std::process::id()));
+    let cluster = common::raft_cluster(&data_dir).await;
+    let overlay = limited_overlay(cluster, Some(1), None).await;
+    assert_idle_session_expired(overlay).await;
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[tokio::test]
+async fn session_expires_at_max_lifetime() {
+    let overlay = limited_overlay(json!({ "type": "none" }), None, Some(2)).await;
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    assert_expired(&mut client, "max_lifetime").await;
+}
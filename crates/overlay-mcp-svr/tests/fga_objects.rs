@@ -0,0 +1,97 @@
+mod common;
+
+use overlay_mcp_core::{AuthConfig, Config};
+use serde_json::{json, Value};
+
+async fn read_resource(client: &mut common::Client, id: u32, uri: &str) -> Value {
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "resources/read",
+            "params": { "uri": uri },
+        }))
+        .await;
+    client.recv().await
+}
+
+async fn get_prompt_and_read_resource(client: &mut common::Client) -> (Value, Value) {
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "prompts/get",
+            "params": { "name": common::FORBIDDEN_TOOL },
+        }))
+        .await;
+    let prompt = client.recv().await;
+    let resource = read_resource(client, 3, &format!("file:///{}", common::FORBIDDEN_TOOL)).await;
+    (prompt, resource)
+}
+
+fn checked_config(config: &mut Config) {
+    let AuthConfig::OpenFga { openfga, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga")
+    };
+    openfga.objects.prompts_get = Some("prompts/get/{name}".to_string());
+    openfga.objects.resources_read = Some("resources/read/{uri}".to_string());
+}
+
+#[tokio::test]
+async fn prompts_and_resources_are_not_checked_by_default() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    let (prompt, resource) = get_prompt_and_read_resource(&mut client).await;
+
+    assert!(prompt.get("result").is_some(), "unexpected {}", prompt);
+    assert!(resource.get("result").is_some(), "unexpected {}", resource);
+    let seen = observed.snapshot();
+    assert!(seen.contains(&format!("prompts/get:{}", common::FORBIDDEN_TOOL)));
+    assert!(seen.contains(&format!(
+        "resources/read:file:///{}",
+        common::FORBIDDEN_TOOL
+    )));
+}
+
+#[tokio::test]
+async fn configured_prompt_and_resource_objects_are_checked() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    checked_config(&mut config);
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    let (prompt, resource) = get_prompt_and_read_resource(&mut client).await;
+
+    assert_eq!(prompt["error"]["code"], -32003, "unexpected {}", prompt);
+    assert_eq!(resource["error"]["code"], -32003, "unexpected {}", resource);
+    let seen = observed.snapshot();
+    assert!(!seen.iter().any(|record| record.starts_with("prompts/get")));
+    assert!(!seen
+        .iter()
+        .any(|record| record.starts_with("resources/read")));
+}
+
+#[tokio::test]
+async fn object_values_are_escaped() {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    checked_config(&mut config);
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    // whitespace, `#` and overlong values are no valid object ids as they are
+    let spaced = read_resource(&mut client, 2, "file:///a b#c").await;
+    assert!(spaced.get("result").is_some(), "unexpected {}", spaced);
+    let long = read_resource(&mut client, 3, &format!("file:///{}", "a".repeat(300))).await;
+    assert!(long.get("result").is_some(), "unexpected {}", long);
+}
@@ -0,0 +1,196 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    routing::post,
+    Form, Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use overlay_mcp_core::AuthConfig;
+use serde_json::{json, Value};
+
+/// Introspection responses of the mock idp by token, with every introspected token.
+#[derive(Clone, Default)]
+struct MockIntrospection {
+    tokens: Arc<Mutex<HashMap<&'static str, Value>>>,
+    introspected: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockIntrospection {
+    fn issue(&self, token: &'static str, response: Value) {
+        self.tokens.lock().unwrap().insert(token, response);
+    }
+
+    fn introspected(&self, token: &str) -> usize {
+        let introspected = self.introspected.lock().unwrap();
+        introspected.iter().filter(|seen| *seen == token).count()
+    }
+}
+
+async fn mock_idp(introspection: MockIntrospection) -> SocketAddr {
+    async fn handle(
+        State(introspection): State<MockIntrospection>,
+        headers: HeaderMap,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Json<Value> {
+        let credential = format!("Basic {}", STANDARD.encode("client:secret"));
+        assert_eq!(headers[header::AUTHORIZATION], credential.as_str());
+        let token = form["token"].clone();
+        introspection
+            .introspected
+            .lock()
+            .unwrap()
+            .push(token.clone());
+        let response = introspection
+            .tokens
+            .lock()
+            .unwrap()
+            .get(token.as_str())
+            .cloned();
+        Json(response.unwrap_or(json!({ "active": false })))
+    }
+    let router = Router::new()
+        .route("/introspect", post(handle))
+        .with_state(introspection);
+    common::serve(router).await
+}
+
+fn introspection_provider(idp: SocketAddr, issuer: &str) -> Value {
+    json!({
+        "type": "introspection",
+        "issuer": issuer,
+        "auth_url": "http://127.0.0.1/authorize",
+        "token_url": "http://127.0.0.1/token",
+        "introspection_url": format!("http://{}/introspect", idp),
+        "client": { "id": "client", "secret": "secret", "scopes": [] },
+    })
+}
+
+async fn introspection_overlay(idp: SocketAddr) -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.jwt =
+        serde_json::from_value(introspection_provider(idp, "http://127.0.0.1/issuer")).unwrap();
+    common::overlay(config).await
+}
+
+#[tokio::test]
+async fn opaque_token_introspected_and_cached() {
+    let introspection = MockIntrospection::default();
+    introspection.issue(
+        "opaque-alice",
+        json!({
+            "active": true,
+            "sub": "alice",
+            "client_id": "client",
+            "exp": common::unix_now() + 600,
+        }),
+    );
+    let idp = mock_idp(introspection.clone()).await;
+    let overlay = introspection_overlay(idp).await;
+
+    assert!(common::connects(overlay, "opaque-alice").await);
+    assert!(common::connects(overlay, "opaque-alice").await);
+    assert_eq!(introspection.introspected("opaque-alice"), 1);
+
+    assert!(!common::connects(overlay, "opaque-unknown").await);
+}
+
+#[tokio::test]
+async fn inactive_or_expired_token_rejected() {
+    let introspection = MockIntrospection::default();
+    introspection.issue("opaque-revoked", json!({ "active": false, "sub": "bob" }));
+    introspection.issue(
+        "opaque-expired",
+        json!({
+            "active": true,
+            "sub": "bob",
+            "client_id": "client",
+            "exp": common::unix_now() - 1,
+        }),
+    );
+    // without `exp` the response is not cached
+    introspection.issue(
+        "opaque-no-exp",
+        json!({ "active": true, "sub": "carol", "client_id": "client" }),
+    );
+    let idp = mock_idp(introspection.clone()).await;
+    let overlay = introspection_overlay(idp).await;
+
+    assert!(!common::connects(overlay, "opaque-revoked").await);
+    assert!(!common::connects(overlay, "opaque-expired").await);
+    assert!(common::connects(overlay, "opaque-no-exp").await);
+    assert!(common::connects(overlay, "opaque-no-exp").await);
+    assert_eq!(introspection.introspected("opaque-no-exp"), 2);
+}
+
+#[tokio::test]
+async fn token_of_other_client_or_issuer_rejected() {
+    let introspection = MockIntrospection::default();
+    introspection.issue(
+        "opaque-audience",
+        json!({ "active": true, "sub": "dave", "aud": ["other", "client"] }),
+    );
+    introspection.issue(
+        "opaque-other-client",
+        json!({ "active": true, "sub": "dave", "client_id": "other" }),
+    );
+    introspection.issue(
+        "opaque-no-audience",
+        json!({ "active": true, "sub": "dave" }),
+    );
+    introspection.issue(
+        "opaque-other-issuer",
+        json!({
+            "active": true,
+            "sub": "dave",
+            "client_id": "client",
+            "iss": "http://127.0.0.1/other",
+        }),
+    );
+    let idp = mock_idp(introspection.clone()).await;
+    let overlay = introspection_overlay(idp).await;
+
+    assert!(common::connects(overlay, "opaque-audience").await);
+    assert!(!common::connects(overlay, "opaque-other-client").await);
+    assert!(!common::connects(overlay, "opaque-no-audience").await);
+    assert!(!common::connects(overlay, "opaque-other-issuer").await);
+}
+
+#[tokio::test]
+async fn opaque_token_goes_to_introspecting_provider() {
+    let introspection = MockIntrospection::default();
+    introspection.issue(
+        "opaque-erin",
+        json!({ "active": true, "sub": "erin", "client_id": "client" }),
+    );
+    let idp = mock_idp(introspection.clone()).await;
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    // the interactive provider verifies jwt, the introspecting one comes second
+    authn.providers =
+        vec![
+            serde_json::from_value(introspection_provider(idp, "http://127.0.0.1/opaque")).unwrap(),
+        ];
+    let overlay = common::overlay(config).await;
+
+    assert!(common::connects(overlay, "opaque-erin").await);
+    assert_eq!(introspection.introspected("opaque-erin"), 1);
+    assert!(common::connects(overlay, &common::jwt("frank", common::unix_now() + 60)).await);
+    assert_eq!(introspection.introspected.lock().unwrap().len(), 1);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{extract::State, http::header, routing::get, Json, Router};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use overlay_mcp_core::AuthConfig;
+use serde_json::{json, Value};
+
+/// JWK set served by the mock idp, with the number of times it was fetched.
+#[derive(Clone, Default)]
+struct MockJwks {
+    kids: Arc<Mutex<Vec<&'static str>>>,
+    fetched: Arc<AtomicUsize>,
+}
+
+impl MockJwks {
+    fn serve(&self, kids: &[&'static str]) {
+        *self.kids.lock().unwrap() = kids.to_vec();
+    }
+
+    fn fetched(&self) -> usize {
+        self.fetched.load(Ordering::SeqCst)
+    }
+}
+
+fn secret(kid: &str) -> Vec<u8> {
+    format!("secret-of-{}", kid).into_bytes()
+}
+
+fn token(kid: &str) -> String {
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256);
+    header.kid = Some(kid.to_string());
+    jsonwebtoken::encode(
+        &header,
+        &json!({
+            "iss": "http://127.0.0.1/issuer",
+            "sub": kid,
+            "aud": "client",
+            "exp": common::unix_now() + 600,
+        }),
+        &jsonwebtoken::EncodingKey::from_secret(&secret(kid)),
+    )
+    .unwrap()
+}
+
+async fn mock_idp(jwks: MockJwks, max_age: u64) -> SocketAddr {
+    async fn handle(
+        State((jwks, max_age)): State<(MockJwks, u64)>,
+    ) -> ([(header::HeaderName, String); 1], Json<Value>) {
+        jwks.fetched.fetch_add(1, Ordering::SeqCst);
+        let keys = jwks
+            .kids
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|kid| {
+                json!({
+                    "kty": "oct",
+                    "kid": kid,
+                    "alg": "HS256",
+                    "k": URL_SAFE_NO_PAD.encode(secret(kid)),
+                })
+            })
+            .collect::<Vec<_>>();
+        (
+            [(header::CACHE_CONTROL, format!("max-age={}", max_age))],
+            Json(json!({ "keys": keys })),
+        )
+    }
+    let router = Router::new()
+        .route("/jwks", get(handle))
+        .with_state((jwks, max_age));
+    common::serve(router).await
+}
+
+async fn jwks_overlay(idp: SocketAddr, refresh: Value) -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.jwt = serde_json::from_value(json!({
+        "type": "oauth2",
+        "issuer": "http://127.0.0.1/issuer",
+        "auth_url": "http://127.0.0.1/authorize",
+        "token_url": "http://127.0.0.1/token",
+        "verifier": { "jwk_url": format!("http://{}/jwks", idp) },
+        "client": { "id": "client", "secret": "secret", "scopes": [] },
+    }))
+    .unwrap();
+    authn.jwks = serde_json::from_value(refresh).unwrap();
+    common::overlay(config).await
+}
+
+#[tokio::test]
+async fn unknown_kid_refetches_jwks() {
+    let jwks = MockJwks::default();
+    jwks.serve(&["k1"]);
+    let idp = mock_idp(jwks.clone(), 3600).await;
+    let overlay = jwks_overlay(idp, json!({})).await;
+    assert!(common::connects(overlay, &token("k1")).await);
+    assert_eq!(jwks.fetched(), 1);
+
+    // rotated, the previous key is still accepted for the grace period
+    jwks.serve(&["k2"]);
+    assert!(common::connects(overlay, &token("k2")).await);
+    assert_eq!(jwks.fetched(), 2);
+    assert!(common::connects(overlay, &token("k1")).await);
+
+    // unknown kids refetch at most once in the cooldown
+    jwks.serve(&["k3"]);
+    assert!(!common::connects(overlay, &token("k3")).await);
+    assert_eq!(jwks.fetched(), 2);
+}
+
+#[tokio::test]
+async fn jwks_refreshed_after_max_age() {
+    let jwks = MockJwks::default();
+    jwks.serve(&["k1"]);
+    let idp = mock_idp(jwks.clone(), 1).await;
+    let overlay = jwks_overlay(
+        idp,
+        json!({ "min_refresh_interval": 1, "unknown_kid_cooldown": 3600, "grace_period": 0 }),
+    )
+    .await;
+    jwks.serve(&["k2"]);
+    for _ in 0..50 {
+        if jwks.fetched() >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(jwks.fetched() >= 2, "jwks not refreshed");
+
+    let fetched = jwks.fetched();
+    assert!(common::connects(overlay, &token("k2")).await);
+    assert!(jwks.fetched() <= fetched + 1, "k2 fetched on demand");
+    // removed keys are dropped without a grace period
+    assert!(!common::connects(overlay, &token("k1")).await);
+}
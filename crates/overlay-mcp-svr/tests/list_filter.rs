@@ -0,0 +1,185 @@
+mod common;
+
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use overlay_mcp_core::AuthConfig;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn tools_list_hides_forbidden_tools() {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+        .await;
+    let response = client.recv().await;
+
+    assert_eq!(response["id"], 2);
+    assert_eq!(tool_names(&response), vec![common::ALLOWED_TOOL]);
+}
+
+fn tool_names(response: &Value) -> Vec<&str> {
+    names(response, "tools", "name")
+}
+
+fn names<'a>(response: &'a Value, list: &str, key: &str) -> Vec<&'a str> {
+    response["result"][list]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item[key].as_str().unwrap())
+        .collect()
+}
+
+async fn list(client: &mut common::Client, id: u32, method: &str) -> Value {
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": id, "method": method }))
+        .await;
+    let response = client.recv().await;
+    assert_eq!(response["id"], id);
+    response
+}
+
+#[tokio::test]
+async fn prompts_and_resources_lists_hide_forbidden_entries() {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { openfga, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    openfga.objects.prompts_get = Some("prompts/get/{name}".to_string());
+    openfga.objects.resources_read = Some("resources/read/{uri}".to_string());
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+
+    let prompts = list(&mut client, 2, "prompts/list").await;
+    assert_eq!(
+        names(&prompts, "prompts", "name"),
+        vec![common::ALLOWED_TOOL]
+    );
+    let resources = list(&mut client, 3, "resources/list").await;
+    assert_eq!(
+        names(&resources, "resources", "uri"),
+        vec![format!("file:///{}", common::ALLOWED_TOOL)]
+    );
+}
+
+#[tokio::test]
+async fn static_tools_rules_filter_tools_list() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    config.auth = AuthConfig::Static {
+        authn: authn.clone(),
+        constant: serde_json::from_value(json!({
+            "apikey": { "whitelist": [common::API_KEY] },
+            "jwt": [],
+            "tools": { "blacklist": [common::FORBIDDEN_TOOL] },
+        }))
+        .unwrap(),
+    };
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+
+    let tools = list(&mut client, 2, "tools/list").await;
+    assert_eq!(tool_names(&tools), vec![common::ALLOWED_TOOL]);
+    let denied = client.call_tool(3, common::FORBIDDEN_TOOL).await;
+    assert_eq!(denied["error"]["code"], -32003, "unexpected {}", denied);
+    assert!(!observed
+        .snapshot()
+        .contains(&format!("tools/call:{}", common::FORBIDDEN_TOOL)));
+    // static rules know only tools
+    let prompts = list(&mut client, 4, "prompts/list").await;
+    assert_eq!(
+        names(&prompts, "prompts", "name"),
+        vec![common::ALLOWED_TOOL, common::FORBIDDEN_TOOL]
+    );
+}
+
+#[tokio::test]
+async fn long_tools_list_is_checked_in_chunks() {
+    let names = (0..120)
+        .map(|index| format!("tool-{}", index))
+        .collect::<Vec<_>>();
+    let mut tools = names.iter().map(String::as_str).collect::<Vec<_>>();
+    tools.push(common::FORBIDDEN_TOOL);
+    let (upstream, _) = common::mock_upstream_with_tools(&tools).await;
+    let openfga = common::mock_openfga().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+        .await;
+    let response = client.recv().await;
+
+    assert_eq!(response["id"], 2);
+    assert_eq!(tool_names(&response), names);
+}
+
+#[tokio::test]
+async fn failed_list_authorization_answers_with_error() {
+    let openfga = common::serve(
+        Router::new()
+            .route(
+                "/stores",
+                get(|| async {
+                    Json(json!({
+                        "stores": [{
+                            "name": "test",
+                            "id": "store",
+                            "created_at": "2024-01-01T00:00:00Z",
+                            "updated_at": "2024-01-01T00:00:00Z",
+                        }],
+                        "continuation_token": "",
+                    }))
+                }),
+            )
+            .route(
+                "/stores/{store}/check",
+                post(|| async { Json(json!({ "allowed": true, "resolution": "" })) }),
+            )
+            .route(
+                "/stores/{store}/batch-check",
+                post(|| async {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(json!({ "code": "internal_error", "message": "unavailable" })),
+                    )
+                }),
+            ),
+    )
+    .await;
+    let (upstream, _) = common::mock_upstream().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+        .await;
+    let response = client.recv().await;
+
+    assert_eq!(response["id"], 2);
+    assert!(response.get("result").is_none(), "unexpected {}", response);
+    assert_eq!(response["error"]["code"], -32603);
+}
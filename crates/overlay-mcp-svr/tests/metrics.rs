@@ -0,0 +1,47 @@
+mod common;
+
+use serde_json::json;
+
+#[tokio::test]
+async fn mcp_metrics_rendered() {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.application.prometheus = true;
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+        .await;
+    client.recv().await;
+    client.call_tool(3, common::ALLOWED_TOOL).await;
+    client.call_tool(4, common::FORBIDDEN_TOOL).await;
+    // tools the upstream did not list share one label
+    client.call_tool(5, "unlisted").await;
+
+    let metrics = reqwest::get(format!("http://{}/.meta/metrics", overlay))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    for expected in [
+        r#"mcp_sessions_active{manager="standalone"} 1"#,
+        r#"mcp_upstream_connections{transport="streamable-http"} 1"#,
+        r#"mcp_messages_total{direction="upstream",method="tools/call"} 2"#,
+        r#"mcp_messages_total{direction="downstream",method="tools/call"} 2"#,
+        r#"mcp_tool_call_duration_seconds_count{tool="allowed",result="ok"} 1"#,
+        r#"mcp_tool_call_duration_seconds_count{tool="other",result="ok"} 1"#,
+        r#"mcp_authz_decisions_total{authorizer="openfga",stage="client_message",result="deny"} 1"#,
+        r#"openfga_check_duration_seconds_count{kind="check",result="ok"}"#,
+    ] {
+        assert!(
+            metrics.contains(expected),
+            "{} not in metrics\n{}",
+            expected,
+            metrics
+        );
+    }
+}
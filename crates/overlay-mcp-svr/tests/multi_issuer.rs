@@ -0,0 +1,218 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use overlay_mcp_core::AuthConfig;
+use serde_json::{json, Value};
+
+const WORKFORCE: &str = "http://127.0.0.1/workforce";
+const MACHINE: &str = "http://127.0.0.1/machine";
+const ADMIN_KEY: &str = "admin-apikey";
+
+fn secret(issuer: &str) -> Vec<u8> {
+    format!("secret-of-{}", issuer).into_bytes()
+}
+
+fn provider(issuer: &str, client_id: &str) -> Value {
+    json!({
+        "type": "oauth2",
+        "issuer": issuer,
+        "auth_url": format!("{}/authorize", issuer),
+        "token_url": format!("{}/token", issuer),
+        "verifier": {
+            "jwk": {
+                "keys": [{
+                    "kty": "oct",
+                    "alg": "HS256",
+                    "k": URL_SAFE_NO_PAD.encode(secret(issuer)),
+                }],
+            },
+        },
+        "client": { "id": client_id, "secret": "secret", "scopes": [] },
+    })
+}
+
+/// Token of `iss` for `aud`, signed with the key of `signer`.
+fn token(iss: &str, aud: &str, signer: &str) -> String {
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+        &json!({
+            "iss": iss,
+            "sub": "someone",
+            "aud": aud,
+            "exp": common::unix_now() + 600,
+        }),
+        &jsonwebtoken::EncodingKey::from_secret(&secret(signer)),
+    )
+    .unwrap()
+}
+
+async fn multi_issuer_overlay() -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.application.admin =
+        Some(serde_json::from_value(json!({ "apikeys": [ADMIN_KEY] })).unwrap());
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.jwt = serde_json::from_value(provider(WORKFORCE, "workforce-client")).unwrap();
+    authn.providers = vec![serde_json::from_value(provider(MACHINE, "machine-client")).unwrap()];
+    common::overlay(config).await
+}
+
+#[tokio::test]
+async fn token_verified_by_provider_of_its_issuer() {
+    let overlay = multi_issuer_overlay().await;
+
+    assert!(common::connects(overlay, &token(WORKFORCE, "workforce-client", WORKFORCE)).await);
+    assert!(common::connects(overlay, &token(MACHINE, "machine-client", MACHINE)).await);
+
+    // each provider validates with its own keys and client id
+    assert!(!common::connects(overlay, &token(MACHINE, "machine-client", WORKFORCE)).await);
+    assert!(!common::connects(overlay, &token(MACHINE, "workforce-client", MACHINE)).await);
+    // unknown issuers are rejected, not left to the interactive provider
+    assert!(
+        !common::connects(
+            overlay,
+            &token("http://127.0.0.1/other", "workforce-client", WORKFORCE)
+        )
+        .await
+    );
+    assert!(
+        !common::connects(
+            overlay,
+            &token("http://127.0.0.1/other", "machine-client", MACHINE)
+        )
+        .await
+    );
+}
+
+#[tokio::test]
+async fn oauth_endpoints_use_interactive_provider() {
+    let overlay = multi_issuer_overlay().await;
+    let client = common::register_client(
+        overlay,
+        json!({ "redirect_uris": ["http://127.0.0.1/callback"] }),
+    )
+    .await;
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("http://{}/authorize", overlay))
+        .query(&[
+            ("response_type", "code"),
+            ("client_id", client["client_id"].as_str().unwrap()),
+            (
+                "code_challenge",
+                "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM",
+            ),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", "http://127.0.0.1/callback"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    let location = response.headers()[reqwest::header::LOCATION]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(
+        location.starts_with(&format!("{}/authorize", WORKFORCE)),
+        "{}",
+        location
+    );
+    assert!(
+        location.contains("client_id=workforce-client"),
+        "{}",
+        location
+    );
+}
+
+#[tokio::test]
+async fn admin_subject_filter_is_scoped_to_issuer() {
+    let overlay = multi_issuer_overlay().await;
+    let mut workforce =
+        common::Client::connect_bearer(overlay, &token(WORKFORCE, "workforce-client", WORKFORCE))
+            .await;
+    workforce.initialize().await;
+    let mut machine =
+        common::Client::connect_bearer(overlay, &token(MACHINE, "machine-client", MACHINE)).await;
+    machine.initialize().await;
+
+    let admin = |method: reqwest::Method, query: &'static str| {
+        reqwest::Client::new()
+            .request(
+                method,
+                format!("http://{}/.meta/sessions?{}", overlay, query),
+            )
+            .header("X-API-KEY", ADMIN_KEY)
+            .send()
+    };
+    let issuers = |sessions: Value| {
+        sessions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|session| session["principal"]["jwt"]["issuer"].clone())
+            .collect::<Vec<_>>()
+    };
+
+    // the same subject of another issuer is another user
+    let sessions: Value = admin(reqwest::Method::GET, "subject=someone")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sessions, json!([]));
+    let sessions: Value = admin(
+        reqwest::Method::GET,
+        "subject=someone&issuer=http://127.0.0.1/machine",
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(issuers(sessions), vec![json!(MACHINE)]);
+
+    let closed: Value = admin(
+        reqwest::Method::DELETE,
+        "subject=someone&issuer=http://127.0.0.1/workforce",
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(closed["closed"].as_array().unwrap().len(), 1);
+    assert!(workforce.ended().await);
+    let ping = json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" });
+    machine.send(ping).await;
+    assert_eq!(machine.recv().await["id"], 2);
+}
+
+#[tokio::test]
+async fn configured_iss_selects_provider() {
+    const STS: &str = "https://sts.example.com/tenant/";
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    // tokens of the provider name another issuer than its discovery url
+    let mut machine = provider(MACHINE, "machine-client");
+    machine["verifier"]["iss"] = json!([STS]);
+    authn.jwt = serde_json::from_value(provider(WORKFORCE, "workforce-client")).unwrap();
+    authn.providers = vec![serde_json::from_value(machine).unwrap()];
+    let overlay = common::overlay(config).await;
+
+    assert!(common::connects(overlay, &token(STS, "machine-client", MACHINE)).await);
+    // the configured `iss` replaces the issuer url
+    assert!(!common::connects(overlay, &token(MACHINE, "machine-client", MACHINE)).await);
+    assert!(common::connects(overlay, &token(WORKFORCE, "workforce-client", WORKFORCE)).await);
+}
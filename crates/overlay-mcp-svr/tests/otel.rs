@@ -0,0 +1,119 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::Uri, Router};
+use overlay_mcp_core::{otel::OtlpProtocol, OpenTelemetryConfig};
+use overlay_mcp_svr::telemetry;
+use serde_json::json;
+use tokio::net::TcpListener;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+/// OTLP collector stand-in, records the path of every export request.
+async fn mock_collector() -> (String, Arc<Mutex<Vec<String>>>) {
+    let exported = Arc::new(Mutex::new(Vec::new()));
+    let router = Router::new()
+        .fallback(
+            |State(exported): State<Arc<Mutex<Vec<String>>>>, uri: Uri| async move {
+                exported.lock().unwrap().push(uri.path().to_string());
+            },
+        )
+        .with_state(exported.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    (format!("http://{}", addr), exported)
+}
+
+/// Stdio server appending every line to the file `$1` and answering requests with an
+/// initialize result.
+const STDIO_UPSTREAM: &str = r#"
+while IFS= read -r line; do
+    printf '%s\n' "$line" >> "$1"
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    if [ -n "$id" ]; then
+        printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2024-11-05","capabilities":{},"serverInfo":{"name":"mock","version":"0.0.0"}}}\n' "$id"
+    fi
+done
+"#;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spans_exported_and_traceparent_propagated() {
+    let (endpoint, exported) = mock_collector().await;
+    let provider = telemetry::tracer_provider(&OpenTelemetryConfig {
+        endpoint,
+        protocol: OtlpProtocol::HttpProtobuf,
+        service_name: "overlay-mcp-test".to_string(),
+    })
+    .unwrap();
+    tracing_subscriber::registry()
+        .with(telemetry::layer(&provider))
+        .init();
+
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    client.call_tool(2, common::ALLOWED_TOOL).await;
+
+    let headers = observed.headers();
+    let traceparent = headers
+        .iter()
+        .find_map(|headers| headers.get("traceparent"))
+        .expect("traceparent not propagated to upstream");
+    assert_eq!(traceparent.to_str().unwrap().split('-').count(), 4);
+
+    // legacy sse posts carry the header as well
+    let (upstream, observed) = common::mock_sse_upstream().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.upstream = serde_json::from_value(json!({
+        "urls": [format!("http://{}/sse", upstream)],
+        "transport": "sse",
+    }))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    assert!(
+        observed
+            .headers()
+            .iter()
+            .any(|headers| headers.contains_key("traceparent")),
+        "traceparent not propagated to sse upstream"
+    );
+
+    // stdio has no headers, the context goes in `params._meta`
+    let written =
+        std::env::temp_dir().join(format!("overlay-mcp-otel-{}.jsonl", std::process::id()));
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.upstream = serde_json::from_value(json!({
+        "program": "sh",
+        "args": ["-c", STDIO_UPSTREAM, "stdio-upstream", written],
+    }))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    let initialize = std::fs::read_to_string(&written).unwrap();
+    let _ = std::fs::remove_file(&written);
+    let initialize: serde_json::Value =
+        serde_json::from_str(initialize.lines().next().unwrap()).unwrap();
+    let traceparent = initialize["params"]["_meta"]["traceparent"]
+        .as_str()
+        .expect("traceparent not propagated to stdio upstream");
+    assert_eq!(traceparent.split('-').count(), 4);
+
+    tokio::task::spawn_blocking(move || provider.force_flush())
+        .await
+        .unwrap()
+        .unwrap();
+    let exported = exported.lock().unwrap().clone();
+    assert!(
+        exported.iter().any(|path| path == "/v1/traces"),
+        "no spans exported: {:?}",
+        exported
+    );
+}
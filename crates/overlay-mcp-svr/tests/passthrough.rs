@@ -0,0 +1,147 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use serde_json::json;
+
+fn identity_token(headers: &HeaderMap) -> &str {
+    headers["x-overlay-identity"].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn passthrough_modifiers_reach_upstream() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.application.passthrough = serde_json::from_value(json!([
+        { "from": "header:X-API-KEY", "rename": "X-Upstream-Key" },
+        { "from": "header:/^x-api-(.*)$/", "rename": "x-copied-$1" },
+        { "set": "header:X-Env", "value": "test" },
+        { "remove": "header:x-copied-key" },
+    ]))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+
+    let headers = observed.headers();
+    let initialize = headers.first().expect("initialize not forwarded");
+    assert_eq!(initialize["x-upstream-key"], common::API_KEY);
+    assert_eq!(initialize["x-env"], "test");
+    assert!(initialize.get("x-copied-key").is_none());
+    assert!(initialize.get("x-api-key").is_none());
+}
+
+#[tokio::test]
+async fn identity_reaches_upstream() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.application.identity = serde_json::from_value(json!({
+        "fields": [
+            { "to": "header:X-Overlay-Auth", "path": "/type" },
+            { "to": "header:X-Overlay-User", "path": "/apikey/fingerprint" },
+            { "to": "header:X-Overlay-Groups", "path": "/jwt/groups", "type": "string[]" },
+        ],
+        "token": { "secret": "identity-secret" },
+    }))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+
+    let headers = observed.headers();
+    let initialize = headers.first().expect("initialize not forwarded");
+    assert_eq!(initialize["x-overlay-auth"], "apikey");
+    assert_eq!(initialize["x-overlay-user"].len(), 64);
+    assert!(initialize.get("x-overlay-groups").is_none());
+    assert_eq!(identity_token(initialize).split('.').count(), 3);
+}
+
+#[tokio::test]
+async fn client_identity_headers_do_not_reach_upstream() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.application.passthrough =
+        serde_json::from_value(json!([{ "from": "header:/^x-overlay-/" }])).unwrap();
+    config.application.identity = serde_json::from_value(json!({
+        "fields": [
+            { "to": "header:X-Overlay-Groups", "path": "/jwt/groups", "type": "string[]" },
+        ],
+        "token": { "secret": "identity-secret" },
+    }))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+
+    // the groups claim does not resolve for an api key, the forged value must not pass
+    let mut client = common::Client::connect_with_headers(
+        overlay,
+        &[
+            ("X-Overlay-Groups", "admin"),
+            ("X-Overlay-Identity", "forged"),
+            ("X-Overlay-Trace", "kept"),
+        ],
+    )
+    .await;
+    client.initialize().await;
+
+    let headers = observed.headers();
+    let initialize = headers.first().expect("initialize not forwarded");
+    assert!(initialize.get("x-overlay-groups").is_none());
+    assert_eq!(initialize.get_all("x-overlay-identity").iter().count(), 1);
+    assert_ne!(identity_token(initialize), "forged");
+    assert_eq!(initialize["x-overlay-trace"], "kept");
+}
+
+#[tokio::test]
+async fn identity_token_is_issued_per_request() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.application.identity = serde_json::from_value(json!({
+        "token": { "secret": "identity-secret", "ttl": 1 },
+    }))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    // past the ttl of the token sent on initialize
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    client.call_tool(2, common::ALLOWED_TOOL).await;
+
+    let headers = observed.headers();
+    let call = headers.last().expect("tools/call not forwarded");
+    assert_ne!(identity_token(&headers[0]), identity_token(call));
+}
+
+#[tokio::test]
+async fn identity_token_reaches_sse_upstream() {
+    let (upstream, observed) = common::mock_sse_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.upstream = serde_json::from_value(json!({
+        "urls": [format!("http://{}/sse", upstream)],
+        "transport": "sse",
+    }))
+    .unwrap();
+    config.application.identity = serde_json::from_value(json!({
+        "token": { "secret": "identity-secret", "ttl": 1 },
+    }))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let call = client.call_tool(2, common::ALLOWED_TOOL).await;
+    assert_eq!(call["id"], 2);
+
+    let headers = observed.headers();
+    let call = headers.last().expect("tools/call not forwarded");
+    assert_ne!(identity_token(&headers[0]), identity_token(call));
+}
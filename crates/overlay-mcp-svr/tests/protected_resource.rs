@@ -0,0 +1,160 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use serde_json::{json, Value};
+
+async fn protected_overlay() -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay_mcp_core::AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.jwt = serde_json::from_value(json!({
+        "type": "oauth2",
+        "issuer": "http://127.0.0.1/issuer",
+        "auth_url": "http://127.0.0.1/authorize",
+        "token_url": "http://127.0.0.1/token",
+        "verifier": "no-check",
+        "client": { "id": "client", "secret": "secret", "scopes": ["openid", "mcp"] },
+    }))
+    .unwrap();
+    common::overlay(config).await
+}
+
+fn www_authenticate(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::WWW_AUTHENTICATE)
+        .map(|value| value.to_str().unwrap().to_string())
+}
+
+async fn metadata(overlay: SocketAddr, path: &str) -> Value {
+    reqwest::get(format!(
+        "http://{}/.well-known/oauth-protected-resource{}",
+        overlay, path
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn protected_resource_metadata() {
+    let overlay = protected_overlay().await;
+
+    let root = metadata(overlay, "").await;
+    assert_eq!(root["resource"], "http://127.0.0.1");
+    assert_eq!(root["authorization_servers"], json!(["http://127.0.0.1"]));
+    assert_eq!(root["bearer_methods_supported"], json!(["header"]));
+    assert_eq!(root["scopes_supported"], json!(["openid", "mcp"]));
+
+    let mcp = metadata(overlay, "/mcp").await;
+    assert_eq!(mcp["resource"], "http://127.0.0.1/mcp");
+    assert_eq!(mcp["authorization_servers"], root["authorization_servers"]);
+
+    // decoded paths that are urls of their own are not resources of the overlay
+    for path in ["/http:%2F%2F%5B", "/https:%2F%2Fevil.example.com%2Fmcp"] {
+        let response = reqwest::get(format!(
+            "http://{}/.well-known/oauth-protected-resource{}",
+            overlay, path
+        ))
+        .await
+        .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND,
+            "{}",
+            path
+        );
+    }
+}
+
+#[tokio::test]
+async fn unauthenticated_request_is_challenged() {
+    let overlay = protected_overlay().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/mcp", overlay))
+        .header(
+            reqwest::header::ACCEPT,
+            "application/json, text/event-stream",
+        )
+        .json(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0.0.0" },
+            },
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        www_authenticate(&response).unwrap(),
+        "Bearer resource_metadata=\"http://127.0.0.1/.well-known/oauth-protected-resource/mcp\""
+    );
+
+    let response = reqwest::Client::new()
+        .get(format!("http://{}/sse", overlay))
+        .bearer_auth("not-a-jwt")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let challenge = www_authenticate(&response).unwrap();
+    assert!(
+        challenge.starts_with(
+            "Bearer resource_metadata=\"http://127.0.0.1/.well-known/oauth-protected-resource/sse\""
+        ),
+        "{}",
+        challenge
+    );
+    assert!(
+        challenge.contains("error=\"invalid_token\""),
+        "{}",
+        challenge
+    );
+}
+
+#[tokio::test]
+async fn forbidden_bearer_is_challenged_for_scope() {
+    let overlay = protected_overlay().await;
+    let mut client =
+        common::Client::connect_bearer(overlay, &common::jwt("owner", common::unix_now() + 60))
+            .await;
+    client.initialize().await;
+    let query = client.endpoint().split_once('?').unwrap().1.to_string();
+
+    let response = reqwest::Client::new()
+        .get(format!("http://{}/sse?{}", overlay, query))
+        .bearer_auth(common::jwt("intruder", common::unix_now() + 60))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let challenge = www_authenticate(&response).unwrap();
+    assert!(
+        challenge.contains("error=\"insufficient_scope\""),
+        "{}",
+        challenge
+    );
+    assert!(challenge.contains("scope=\"openid mcp\""), "{}", challenge);
+
+    // api keys are not asked for a bearer token
+    let response = reqwest::Client::new()
+        .get(format!("http://{}/sse?{}", overlay, query))
+        .header("X-API-KEY", "intruder-apikey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    assert!(www_authenticate(&response).is_none());
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use serde_json::{json, Value};
+
+async fn limited_overlay(cluster: Value, rate_limit: Value) -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, cluster);
+    config.application.ip_extract = Some(serde_json::from_value(json!("ConnectInfo")).unwrap());
+    config.application.rate_limit = Some(serde_json::from_value(rate_limit).unwrap());
+    common::overlay(config).await
+}
+
+fn assert_rate_limited(message: &Value, id: u32, bucket: &str) {
+    assert_eq!(message["id"], id);
+    assert_eq!(message["error"]["code"], -32029);
+    assert_eq!(message["error"]["data"]["bucket"], bucket);
+    assert!(message["error"]["data"]["retry_after"].as_u64().unwrap() > 0);
+}
+
+/// The second session of the same client ip is refused until the window ends.
+async fn assert_sessions_limited(overlay: SocketAddr) {
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+
+    let refused = reqwest::Client::new()
+        .get(format!("http://{}/sse", overlay))
+        .header("X-API-KEY", common::API_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = refused.headers()[reqwest::header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+}
+
+#[tokio::test]
+async fn standalone_sessions_limited() {
+    let overlay = limited_overlay(
+        json!({ "type": "none" }),
+        json!({ "key": [{ "type": "ip" }], "sessions": { "limit": 1 } }),
+    )
+    .await;
+    assert_sessions_limited(overlay).await;
+}
+
+#[tokio::test]
+async fn raft_sessions_limited() {
+    let data_dir =
+        std::env::temp_dir().join(format!("overlay-mcp-ratelimit-{}", std::process::id()));
+    let overlay = limited_overlay(
+        common::raft_cluster(&data_dir).await,
+        json!({ "key": [{ "type": "ip" }], "sessions": { "limit": 1 } }),
+    )
+    .await;
+    assert_sessions_limited(overlay).await;
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[tokio::test]
+async fn messages_limited() {
+    let overlay = limited_overlay(
+        json!({ "type": "none" }),
+        json!({ "messages": { "limit": 4 } }),
+    )
+    .await;
+    let mut client = common::Client::connect(overlay).await;
+    // initialize and notifications/initialized count as well
+    client.initialize().await;
+    for id in 2..4 {
+        client
+            .send(json!({ "jsonrpc": "2.0", "id": id, "method": "ping" }))
+            .await;
+        let response = client.recv().await;
+        assert_eq!(response["id"], id);
+        assert!(response["error"].is_null(), "{}", response);
+    }
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 4, "method": "ping" }))
+        .await;
+    assert_rate_limited(&client.recv().await, 4, "messages");
+}
+
+#[tokio::test]
+async fn tool_calls_limited() {
+    let overlay = limited_overlay(
+        json!({ "type": "none" }),
+        json!({ "tool": { (common::ALLOWED_TOOL): { "limit": 1 } } }),
+    )
+    .await;
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    let response = client.call_tool(2, common::ALLOWED_TOOL).await;
+    assert!(response["error"].is_null(), "{}", response);
+    assert_rate_limited(&client.call_tool(3, common::ALLOWED_TOOL).await, 3, "tools");
+}
+
+#[tokio::test]
+async fn unlisted_tools_share_a_counter() {
+    let overlay = limited_overlay(
+        json!({ "type": "none" }),
+        json!({ "tools": { "limit": 1 } }),
+    )
+    .await;
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    let response = client.call_tool(2, "unlisted-first").await;
+    assert!(response["error"].is_null(), "{}", response);
+    assert_rate_limited(&client.call_tool(3, "unlisted-second").await, 3, "tools");
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use axum::{routing::post, Json, Router};
+use overlay_mcp_core::{AuthConfig, Config};
+use serde_json::{json, Value};
+
+const REDIRECT_URI: &str = "http://127.0.0.1/callback";
+
+/// Authorization server exchanging every code for the same token.
+async fn mock_idp() -> SocketAddr {
+    let router = Router::new().route(
+        "/token",
+        post(|| async {
+            Json(json!({ "access_token": "issued", "token_type": "bearer", "expires_in": 60 }))
+        }),
+    );
+    common::serve(router).await
+}
+
+async fn registration_config(cluster: Value) -> Config {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let idp = mock_idp().await;
+    let mut config = common::config(upstream, openfga, cluster);
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.jwt = serde_json::from_value(json!({
+        "type": "oauth2",
+        "issuer": "http://127.0.0.1/issuer",
+        "auth_url": "http://127.0.0.1/authorize",
+        "token_url": format!("http://{}/token", idp),
+        "verifier": "no-check",
+        "client": { "id": "client", "secret": "secret", "scopes": [] },
+    }))
+    .unwrap();
+    config
+}
+
+async fn authorize(overlay: SocketAddr, client_id: &str, redirect_uri: &str) -> reqwest::Response {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("http://{}/authorize", overlay))
+        .query(&[
+            ("response_type", "code"),
+            ("client_id", client_id),
+            (
+                "code_challenge",
+                "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM",
+            ),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", redirect_uri),
+        ])
+        .send()
+        .await
+        .unwrap()
+}
+
+fn exchange(overlay: SocketAddr, redirect_uri: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::new()
+        .post(format!("http://{}/token", overlay))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", "code"),
+            (
+                "code_verifier",
+                "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            ),
+            ("redirect_uri", redirect_uri),
+        ])
+}
+
+#[tokio::test]
+async fn registered_client_is_validated_at_authorize() {
+    let overlay = common::overlay(registration_config(json!({ "type": "none" })).await).await;
+
+    let client = common::register_client(
+        overlay,
+        json!({ "redirect_uris": [REDIRECT_URI], "client_name": "test" }),
+    )
+    .await;
+    assert_eq!(client["redirect_uris"], json!([REDIRECT_URI]));
+    assert_eq!(client["token_endpoint_auth_method"], "client_secret_basic");
+    assert_eq!(client["grant_types"], json!(["authorization_code"]));
+    assert_eq!(client["client_name"], "test");
+    assert!(client["client_secret"].is_string());
+    // secrets live as long as the client unless configured otherwise
+    assert_eq!(client["client_secret_expires_at"], 0);
+    let client_id = client["client_id"].as_str().unwrap();
+
+    let response = authorize(overlay, client_id, REDIRECT_URI).await;
+    assert!(response.status().is_redirection());
+    let response = authorize(overlay, client_id, "http://127.0.0.1/elsewhere").await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let response = authorize(overlay, "unregistered", REDIRECT_URI).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn invalid_client_metadata_is_rejected() {
+    let overlay = common::overlay(registration_config(json!({ "type": "none" })).await).await;
+
+    for body in [
+        json!({ "redirect_uris": [] }),
+        json!({ "redirect_uris": ["/relative"] }),
+        json!({ "redirect_uris": ["http://127.0.0.1/callback#fragment"] }),
+    ] {
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/register", overlay))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::BAD_REQUEST,
+            "{}",
+            body
+        );
+    }
+}
+
+#[tokio::test]
+async fn token_endpoint_authenticates_client() {
+    let overlay = common::overlay(registration_config(json!({ "type": "none" })).await).await;
+    let client = common::register_client(overlay, json!({ "redirect_uris": [REDIRECT_URI] })).await;
+    let client_id = client["client_id"].as_str().unwrap();
+    let client_secret = client["client_secret"].as_str().unwrap();
+
+    let response = exchange(overlay, REDIRECT_URI)
+        .basic_auth(client_id, Some(client_secret))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let token: Value = response.json().await.unwrap();
+    assert_eq!(token["access_token"], "issued");
+
+    let response = exchange(overlay, REDIRECT_URI)
+        .basic_auth(client_id, Some("wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = exchange(overlay, REDIRECT_URI)
+        .basic_auth("unregistered", Some(client_secret))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = exchange(overlay, "http://127.0.0.1/elsewhere")
+        .basic_auth(client_id, Some(client_secret))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn public_client_needs_no_secret() {
+    let overlay = common::overlay(registration_config(json!({ "type": "none" })).await).await;
+    let client = common::register_client(
+        overlay,
+        json!({ "redirect_uris": [REDIRECT_URI], "token_endpoint_auth_method": "none" }),
+    )
+    .await;
+    assert!(client.get("client_secret").is_none());
+    assert_eq!(client["client_secret_expires_at"], 0);
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/token", overlay))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", client["client_id"].as_str().unwrap()),
+            ("code", "code"),
+            (
+                "code_verifier",
+                "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            ),
+            ("redirect_uri", REDIRECT_URI),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn expired_client_secret_is_rejected() {
+    let mut config = registration_config(json!({ "type": "none" })).await;
+    config.server.registration.client_secret_ttl = 1;
+    let overlay = common::overlay(config).await;
+    let client = common::register_client(overlay, json!({ "redirect_uris": [REDIRECT_URI] })).await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+    // client_secret_post
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/token", overlay))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", client["client_id"].as_str().unwrap()),
+            ("client_secret", client["client_secret"].as_str().unwrap()),
+            ("code", "code"),
+            (
+                "code_verifier",
+                "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            ),
+            ("redirect_uri", REDIRECT_URI),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn registered_client_is_kept_in_raft() {
+    let data_dir = std::env::temp_dir().join(format!(
+        "overlay-mcp-raft-registration-{}",
+        std::process::id()
+    ));
+    let cluster = common::raft_cluster(&data_dir).await;
+    let overlay = common::overlay(registration_config(cluster).await).await;
+
+    let client = common::register_client(overlay, json!({ "redirect_uris": [REDIRECT_URI] })).await;
+    let response = authorize(overlay, client["client_id"].as_str().unwrap(), REDIRECT_URI).await;
+    assert!(response.status().is_redirection());
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
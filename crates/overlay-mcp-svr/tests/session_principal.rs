@@ -0,0 +1,61 @@
+mod common;
+
+use serde_json::json;
+
+#[tokio::test]
+async fn session_rejects_other_principal() {
+    let (upstream, observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+
+    let ping = json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" });
+    let status = client.send_as("intruder-apikey", ping).await;
+    assert_eq!(status, reqwest::StatusCode::FORBIDDEN);
+
+    let reconnect = reqwest::Client::new()
+        .get(format!(
+            "http://{}/sse?{}",
+            overlay,
+            client.endpoint().split_once('?').unwrap().1
+        ))
+        .header("X-API-KEY", "intruder-apikey")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reconnect.status(), reqwest::StatusCode::FORBIDDEN);
+
+    assert!(!observed.snapshot().contains(&"ping".to_string()));
+}
+
+fn unchecked_jwt(claims: serde_json::Value) -> String {
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(b"unchecked"),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn jwt_without_issuer_and_subject_is_rejected() {
+    let (upstream, _observed) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let overlay = common::overlay(config).await;
+
+    let exp = common::unix_now() + 60;
+    assert!(!common::connects(overlay, &unchecked_jwt(json!({ "exp": exp }))).await);
+    // one issuer's tokens without sub would all be the same principal
+    for jti in ["first", "second"] {
+        let subless =
+            unchecked_jwt(json!({ "iss": "http://127.0.0.1/issuer", "jti": jti, "exp": exp }));
+        assert!(!common::connects(overlay, &subless).await);
+    }
+    let issuerless = unchecked_jwt(json!({ "sub": "user", "exp": exp }));
+    assert!(!common::connects(overlay, &issuerless).await);
+    assert!(common::connects(overlay, &common::jwt("user", common::unix_now() + 60)).await);
+}
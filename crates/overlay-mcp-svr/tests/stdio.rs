@@ -0,0 +1,102 @@
+mod common;
+
+use std::{path::Path, time::Duration};
+
+use overlay_mcp_core::UpstreamConfig;
+use serde_json::{json, Value};
+
+const SECRET: &str = "stdio-secret";
+
+/// Stdio MCP server writing its pid to `$1`, answering initialize with the `UPSTREAM_SECRET` of
+/// its env and every other request with no tools. It writes more than a pipe buffer to stderr
+/// first, so it only answers when overlay drains stderr.
+const SERVER: &str = r#"
+echo $$ > "$1"
+head -c 131072 /dev/zero | tr '\0' x >&2
+echo >&2
+while IFS= read -r line; do
+    id=$(printf '%s\n' "$line" | sed -n 's/^.*"id":\([0-9][0-9]*\).*$/\1/p')
+    [ -n "$id" ] || continue
+    case "$line" in
+        *'"method":"initialize"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"%s","version":"0.0.0"}}}\n' "$id" "$UPSTREAM_SECRET" ;;
+        *)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[]}}\n' "$id" ;;
+    esac
+done
+"#;
+
+async fn process_exited(pid: &str) -> bool {
+    let proc = Path::new("/proc").join(pid);
+    for _ in 0..100 {
+        if !proc.exists() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+async fn assert_stdio_upstream(cluster: Value, pid_file: &Path) {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, cluster);
+    config.upstream = serde_json::from_value::<UpstreamConfig>(json!({
+        "program": "sh",
+        "args": ["-c", SERVER, "sh", pid_file],
+        "env": { "UPSTREAM_SECRET": SECRET },
+    }))
+    .unwrap();
+    let overlay = common::overlay(config).await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0.0.0" },
+            },
+        }))
+        .await;
+    let response = client.recv().await;
+    // the env of the local config reaches the process
+    assert_eq!(response["result"]["serverInfo"]["name"], SECRET);
+    client
+        .send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+        .await;
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+        .await;
+    assert_eq!(client.recv().await["result"]["tools"], json!([]));
+
+    let pid = std::fs::read_to_string(pid_file).unwrap();
+    let pid = pid.trim();
+    assert!(Path::new("/proc").join(pid).exists());
+    drop(client);
+    assert!(
+        process_exited(pid).await,
+        "upstream process {} not killed",
+        pid
+    );
+}
+
+#[tokio::test]
+async fn standalone_stdio_upstream() {
+    let pid_file =
+        std::env::temp_dir().join(format!("overlay-mcp-stdio-{}.pid", std::process::id()));
+    assert_stdio_upstream(json!({ "type": "none" }), &pid_file).await;
+    let _ = std::fs::remove_file(&pid_file);
+}
+
+#[tokio::test]
+async fn raft_stdio_upstream() {
+    let data_dir = std::env::temp_dir().join(format!("overlay-mcp-stdio-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let pid_file = data_dir.join("upstream.pid");
+    assert_stdio_upstream(common::raft_cluster(&data_dir).await, &pid_file).await;
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+/// Streamable HTTP server whose `slow` tool takes a while and whose `expire` tool answers 404
+/// like a server that dropped the session. GET opens a stream that never sends anything, GETs
+/// without a session are counted and rejected like a server without the legacy SSE transport.
+async fn upstream() -> (SocketAddr, Arc<Mutex<usize>>) {
+    async fn handle(Json(message): Json<Value>) -> Response {
+        let Some(id) = message.get("id").cloned() else {
+            return StatusCode::ACCEPTED.into_response();
+        };
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "mock", "version": "0.0.0" },
+            }),
+            "tools/call" => match message["params"]["name"].as_str().unwrap_or_default() {
+                "slow" => {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                    json!({ "content": [{ "type": "text", "text": "slow" }], "isError": false })
+                }
+                "expire" => return StatusCode::NOT_FOUND.into_response(),
+                _ => json!({ "content": [{ "type": "text", "text": "fast" }], "isError": false }),
+            },
+            _ => json!({}),
+        };
+        (
+            [("mcp-session-id", "mock-session")],
+            Json(json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+        )
+            .into_response()
+    }
+
+    async fn listen(State(probes): State<Arc<Mutex<usize>>>, headers: HeaderMap) -> Response {
+        if !headers.contains_key("mcp-session-id") {
+            *probes.lock().unwrap() += 1;
+            return StatusCode::METHOD_NOT_ALLOWED.into_response();
+        }
+        let stream = futures::stream::pending::<Result<Bytes, std::io::Error>>();
+        (
+            [("content-type", "text/event-stream")],
+            Body::from_stream(stream),
+        )
+            .into_response()
+    }
+
+    let probes = Arc::new(Mutex::new(0));
+    let router = Router::new()
+        .route(
+            "/mcp",
+            post(handle).get(listen).delete(|| async { StatusCode::OK }),
+        )
+        .with_state(probes.clone());
+    (common::serve(router).await, probes)
+}
+
+async fn overlay(upstream: SocketAddr, transport: &str) -> SocketAddr {
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.upstream = serde_json::from_value(json!({
+        "urls": [format!("http://{}/mcp", upstream)],
+        "transport": transport,
+    }))
+    .unwrap();
+    common::overlay(config).await
+}
+
+#[tokio::test]
+async fn slow_request_does_not_hold_back_later_ones() {
+    let (upstream, _) = upstream().await;
+    let overlay = overlay(upstream, "streamable-http").await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    for (id, name) in [(2, "slow"), (3, "fast")] {
+        client
+            .send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": {} },
+            }))
+            .await;
+    }
+
+    assert_eq!(client.recv().await["id"], 3);
+    assert_eq!(client.recv().await["id"], 2);
+}
+
+#[tokio::test]
+async fn expired_upstream_session_answers_pending_request() {
+    let (upstream, _) = upstream().await;
+    let overlay = overlay(upstream, "streamable-http").await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    let expired = client.call_tool(2, "expire").await;
+
+    assert_eq!(expired["id"], 2);
+    assert_eq!(expired["error"]["message"], "Upstream session expired");
+}
+
+#[tokio::test]
+async fn auto_transport_is_detected_once() {
+    let (upstream, probes) = upstream().await;
+    let overlay = overlay(upstream, "auto").await;
+
+    // the first session probes the sse handshake, which the upstream rejects
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+
+    let mut client = common::Client::connect(overlay).await;
+    client.initialize().await;
+    assert_eq!(*probes.lock().unwrap(), 1);
+    assert_eq!(client.call_tool(2, "fast").await["id"], 2);
+}
+
+#[tokio::test]
+async fn json_response_times_out() {
+    let (upstream, _) = upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.upstream = serde_json::from_value(json!({
+        "urls": [format!("http://{}/mcp", upstream)],
+        "transport": "streamable-http",
+    }))
+    .unwrap();
+    config.server.response_timeout = 1;
+    let overlay = common::overlay(config).await;
+    let post = |session_id: Option<&str>, message: Value| {
+        let mut request = reqwest::Client::new()
+            .post(format!("http://{}/mcp", overlay))
+            .header("X-API-KEY", common::API_KEY)
+            .header("Accept", "application/json")
+            .json(&message);
+        if let Some(session_id) = session_id {
+            request = request.header("mcp-session-id", session_id);
+        }
+        request.send()
+    };
+
+    let initialized = post(
+        None,
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0.0.0" },
+            },
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(initialized.status(), reqwest::StatusCode::OK);
+    let session_id = initialized.headers()["mcp-session-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let call = |id: u32, name: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": {} },
+        })
+    };
+
+    let fast = post(Some(&session_id), call(2, "fast")).await.unwrap();
+    assert_eq!(fast.status(), reqwest::StatusCode::OK);
+    assert_eq!(fast.json::<Value>().await.unwrap()["id"], 2);
+
+    let slow = post(Some(&session_id), call(3, "slow")).await.unwrap();
+    assert!(slow.status().is_server_error(), "{}", slow.status());
+}
@@ -0,0 +1,299 @@
+mod common;
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    routing::post,
+    Form, Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use overlay_mcp_core::{AuthConfig, Config};
+use serde_json::{json, Value};
+
+const REDIRECT_URI: &str = "http://127.0.0.1/callback";
+const REFRESH_TOKEN: &str = "valid-refresh-token";
+const MACHINE_CREDENTIALS: &str = "machine:machine-secret";
+
+type Forms = Arc<Mutex<Vec<Vec<(String, String)>>>>;
+
+/// Authorization server answering every grant, rejecting unknown refresh tokens and machine
+/// credentials like an idp would. The basic credentials are recorded as `authorization`.
+async fn mock_idp() -> (SocketAddr, Forms) {
+    async fn token(
+        State(forms): State<Forms>,
+        headers: HeaderMap,
+        Form(mut form): Form<Vec<(String, String)>>,
+    ) -> (StatusCode, Json<Value>) {
+        if let Some(authorization) = headers.get(header::AUTHORIZATION) {
+            let credentials = authorization.to_str().unwrap().trim_start_matches("Basic ");
+            let credentials = String::from_utf8(STANDARD.decode(credentials).unwrap()).unwrap();
+            form.push(("authorization".to_string(), credentials));
+        }
+        forms.lock().unwrap().push(form.clone());
+        let value = |key: &str| {
+            form.iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default()
+        };
+        match value("grant_type").as_str() {
+            "client_credentials" if value("authorization") != MACHINE_CREDENTIALS => (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "invalid_client" })),
+            ),
+            "refresh_token" if value("refresh_token") != REFRESH_TOKEN => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    json!({ "error": "invalid_grant", "error_description": "refresh token expired" }),
+                ),
+            ),
+            grant_type => (
+                StatusCode::OK,
+                Json(json!({
+                    "access_token": format!("{}-token", grant_type),
+                    "token_type": "bearer",
+                    "expires_in": 60,
+                    "refresh_token": REFRESH_TOKEN,
+                })),
+            ),
+        }
+    }
+
+    let forms = Forms::default();
+    let router = Router::new()
+        .route("/token", post(token))
+        .with_state(forms.clone());
+    (common::serve(router).await, forms)
+}
+
+async fn token_config() -> (Config, Forms) {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let (idp, forms) = mock_idp().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    let AuthConfig::OpenFga { authn, .. } = &mut config.auth else {
+        unreachable!("test config uses openfga");
+    };
+    authn.jwt = serde_json::from_value(json!({
+        "type": "oauth2",
+        "issuer": "http://127.0.0.1/issuer",
+        "auth_url": "http://127.0.0.1/authorize",
+        "token_url": format!("http://{}/token", idp),
+        "verifier": "no-check",
+        "client": { "id": "client", "secret": "secret", "scopes": ["openid"] },
+    }))
+    .unwrap();
+    (config, forms)
+}
+
+async fn request_token(
+    overlay: SocketAddr,
+    client: &Value,
+    form: &[(&str, &str)],
+) -> (reqwest::StatusCode, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/token", overlay))
+        .basic_auth(
+            client["client_id"].as_str().unwrap(),
+            client["client_secret"].as_str(),
+        )
+        .form(form)
+        .send()
+        .await
+        .unwrap();
+    (response.status(), response.json().await.unwrap())
+}
+
+async fn grant_types_supported(overlay: SocketAddr) -> Value {
+    let metadata: Value = reqwest::get(format!(
+        "http://{}/.well-known/oauth-authorization-server",
+        overlay
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    metadata["grant_types_supported"].clone()
+}
+
+#[tokio::test]
+async fn refresh_token_grant_is_proxied() {
+    let (config, forms) = token_config().await;
+    let overlay = common::overlay(config).await;
+    let client = common::register_client(
+        overlay,
+        json!({
+            "redirect_uris": [REDIRECT_URI],
+            "grant_types": ["authorization_code", "refresh_token"],
+        }),
+    )
+    .await;
+
+    let (status, token) = request_token(
+        overlay,
+        &client,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", REFRESH_TOKEN),
+            ("scope", "read"),
+        ],
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(token["access_token"], "refresh_token-token");
+    let form = forms.lock().unwrap().last().cloned().unwrap();
+    assert!(form.contains(&("scope".to_string(), "read".to_string())));
+
+    // errors of the idp are returned as they are
+    let (status, error) = request_token(
+        overlay,
+        &client,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", "expired"),
+        ],
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "invalid_grant");
+    assert_eq!(error["error_description"], "refresh token expired");
+
+    let (status, error) = request_token(overlay, &client, &[("grant_type", "refresh_token")]).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "invalid_request");
+}
+
+#[tokio::test]
+async fn unregistered_grant_is_rejected() {
+    let (config, _) = token_config().await;
+    let overlay = common::overlay(config).await;
+    let client = common::register_client(overlay, json!({ "redirect_uris": [REDIRECT_URI] })).await;
+
+    let (status, error) = request_token(
+        overlay,
+        &client,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", REFRESH_TOKEN),
+        ],
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "unauthorized_client");
+
+    let (status, error) = request_token(overlay, &client, &[("grant_type", "password")]).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "unsupported_grant_type");
+}
+
+#[tokio::test]
+async fn client_authentication_error_is_json() {
+    let (config, _) = token_config().await;
+    let overlay = common::overlay(config).await;
+    let client = common::register_client(overlay, json!({ "redirect_uris": [REDIRECT_URI] })).await;
+    let secret = client["client_secret"].as_str().unwrap();
+
+    let authenticate = |secret: String| {
+        reqwest::Client::new()
+            .post(format!("http://{}/token", overlay))
+            .basic_auth(client["client_id"].as_str().unwrap(), Some(secret))
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", REFRESH_TOKEN),
+            ])
+            .send()
+    };
+    // `&` and `=` are part of the secret, not form separators
+    for wrong in [
+        "wrong".to_string(),
+        format!("{}&wrong", secret),
+        format!("{}=wrong", secret),
+    ] {
+        let response = authenticate(wrong).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert!(response
+            .headers()
+            .contains_key(reqwest::header::WWW_AUTHENTICATE));
+        let error: Value = response.json().await.unwrap();
+        assert_eq!(error["error"], "invalid_client");
+    }
+
+    // percent encoded credentials authenticate, the grant is not registered for the client
+    let encoded = secret
+        .bytes()
+        .map(|b| format!("%{:02X}", b))
+        .collect::<String>();
+    let response = authenticate(encoded).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"], "unauthorized_client");
+}
+
+#[tokio::test]
+async fn client_credentials_grant_is_opt_in() {
+    let (config, _) = token_config().await;
+    let overlay = common::overlay(config).await;
+    assert_eq!(
+        grant_types_supported(overlay).await,
+        json!(["authorization_code", "refresh_token"])
+    );
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/register", overlay))
+        .json(&json!({ "redirect_uris": [REDIRECT_URI], "grant_types": ["client_credentials"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let client = common::register_client(overlay, json!({ "redirect_uris": [REDIRECT_URI] })).await;
+    let (status, error) =
+        request_token(overlay, &client, &[("grant_type", "client_credentials")]).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "unsupported_grant_type");
+}
+
+#[tokio::test]
+async fn client_credentials_grant_is_proxied() {
+    let (mut config, forms) = token_config().await;
+    config.server.registration.client_credentials = true;
+    let overlay = common::overlay(config).await;
+    assert_eq!(
+        grant_types_supported(overlay).await,
+        json!(["authorization_code", "refresh_token", "client_credentials"])
+    );
+
+    // registered clients can not use it, only clients of the idp
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/register", overlay))
+        .json(&json!({ "redirect_uris": [REDIRECT_URI], "grant_types": ["client_credentials"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let machine = json!({ "client_id": "machine", "client_secret": "machine-secret" });
+    let (status, token) = request_token(
+        overlay,
+        &machine,
+        &[("grant_type", "client_credentials"), ("scope", "read")],
+    )
+    .await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(token["access_token"], "client_credentials-token");
+    // the caller's own credentials and scopes, never the overlay's client
+    let form = forms.lock().unwrap().last().cloned().unwrap();
+    assert!(form.contains(&("authorization".to_string(), MACHINE_CREDENTIALS.to_string())));
+    assert!(form.contains(&("scope".to_string(), "read".to_string())));
+
+    let machine = json!({ "client_id": "machine", "client_secret": "wrong" });
+    let (status, error) =
+        request_token(overlay, &machine, &[("grant_type", "client_credentials")]).await;
+    assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(error["error"], "invalid_client");
+}
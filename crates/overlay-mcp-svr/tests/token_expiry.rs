@@ -0,0 +1,48 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use serde_json::json;
+
+async fn token_overlay(policy: &str) -> SocketAddr {
+    let (upstream, _) = common::mock_upstream().await;
+    let openfga = common::mock_openfga().await;
+    let mut config = common::config(upstream, openfga, json!({ "type": "none" }));
+    config.server.token_expiry = serde_json::from_value(json!(policy)).unwrap();
+    common::overlay(config).await
+}
+
+#[tokio::test]
+async fn stream_closed_when_token_expires() {
+    let overlay = token_overlay("close").await;
+    let mut client =
+        common::Client::connect_bearer(overlay, &common::jwt("user", common::unix_now() + 2)).await;
+    client.initialize().await;
+
+    let notification = client.recv().await;
+    assert_eq!(notification["method"], "notifications/message");
+    assert_eq!(notification["params"]["data"]["reason"], "token_expired");
+    assert!(client.ended().await, "event stream not ended");
+}
+
+#[tokio::test]
+async fn stream_resumes_after_token_renewed() {
+    let overlay = token_overlay("reauthenticate").await;
+    let mut client =
+        common::Client::connect_bearer(overlay, &common::jwt("user", common::unix_now() + 2)).await;
+    client.initialize().await;
+
+    let notification = client.recv().await;
+    assert_eq!(
+        notification["params"]["data"]["reason"],
+        "token_renewal_required"
+    );
+
+    let renewed = common::jwt("user", common::unix_now() + 60);
+    let ping = json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" });
+    assert_eq!(
+        client.send_bearer(&renewed, ping).await,
+        reqwest::StatusCode::ACCEPTED
+    );
+    assert_eq!(client.recv().await["id"], 2);
+}